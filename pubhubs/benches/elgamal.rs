@@ -0,0 +1,99 @@
+//! Compares the single-item ElGamal operations with their batched counterparts
+//! from [pubhubs::elgamal::batch].
+//!
+//! Run using `cargo bench --bench elgamal`.
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+use pubhubs::elgamal::{batch, random_point, random_scalar, rsk, PrivateKey, Triple};
+
+/// Number of triples per batch, e.g. the members of a hub.
+const N: usize = 1000;
+
+fn triples() -> Vec<Triple> {
+    let pk = PrivateKey::random().public_key();
+    (0..N).map(|_| pk.encrypt_random()).collect()
+}
+
+fn bench_rsk(c: &mut Criterion) {
+    let s = random_scalar();
+    let k = random_scalar();
+
+    let mut group = c.benchmark_group("rsk");
+
+    group.bench_function("single", |b| {
+        b.iter_batched(
+            triples,
+            |ts| {
+                ts.into_iter()
+                    .map(|t| t.rsk(rsk::SAndK::new(&s, &k)))
+                    .collect::<Vec<Triple>>()
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("batch", |b| {
+        b.iter_batched(
+            triples,
+            |mut ts| {
+                batch::rsk(&mut ts, rsk::SAndK::new(&s, &k));
+                ts
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+fn bench_rerandomize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rerandomize");
+
+    group.bench_function("single", |b| {
+        b.iter_batched(
+            triples,
+            |ts| {
+                ts.into_iter()
+                    .map(Triple::rerandomize)
+                    .collect::<Vec<Triple>>()
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("batch", |b| {
+        b.iter_batched(
+            triples,
+            |mut ts| {
+                batch::rerandomize(&mut ts);
+                ts
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+fn bench_encrypt(c: &mut Criterion) {
+    let pk = PrivateKey::random().public_key();
+    let plaintexts: Vec<_> = (0..N).map(|_| random_point()).collect();
+
+    let mut group = c.benchmark_group("encrypt");
+
+    group.bench_function("single", |b| {
+        b.iter(|| {
+            plaintexts
+                .iter()
+                .map(|m| pk.encrypt(*m))
+                .collect::<Vec<Triple>>()
+        })
+    });
+
+    group.bench_function("batch", |b| b.iter(|| batch::encrypt(&pk, &plaintexts)));
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_rsk, bench_rerandomize, bench_encrypt);
+criterion_main!(benches);
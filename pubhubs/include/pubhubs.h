@@ -0,0 +1,166 @@
+#ifndef PUBHUBS_H
+#define PUBHUBS_H
+
+/* Generated by cbindgen from src/common/abi.rs - do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Version of the interface described by `pubhubs.h`.
+#define PUBHUBS_ABI_VERSION 1
+
+// Maximal length of a short pseudonym, excluding the terminating zero.
+#define PUBHUBS_SHORT_PSEUDONYM_MAX_LEN 33
+
+// Result of [decrypt].
+enum DecryptResult {
+  DecryptResult_Ok = 1,
+  DecryptResult_WrongPublicKey = 2,
+  DecryptResult_InvalidTriple = 3,
+  DecryptResult_InvalidPrivateKey = 4,
+};
+typedef uint8_t DecryptResult;
+
+// Result of the other functions in this interface.
+//
+// The first four values coincide with those of [DecryptResult].
+enum PubhubsResult {
+  PubhubsResult_Ok = 1,
+  PubhubsResult_WrongPublicKey = 2,
+  PubhubsResult_InvalidTriple = 3,
+  PubhubsResult_InvalidPrivateKey = 4,
+  PubhubsResult_InvalidPublicKey = 5,
+  // Returned when a hex string has the wrong length or contains non-hex digits
+  PubhubsResult_InvalidHex = 6,
+  // Returned when a local pseudonym is not a valid 32-byte plaintext, or `n` is too large
+  PubhubsResult_InvalidArgument = 7,
+};
+typedef uint8_t PubhubsResult;
+
+// Private key loaded by [pubhubs_key_load], opaque to the caller.
+//
+// Since a loaded key is never modified, a handle may be used by several threads at the same time.
+// The key is zeroized when the handle is freed using [pubhubs_key_free].
+typedef struct PubhubsKey PubhubsKey;
+
+// Returns the [PUBHUBS_ABI_VERSION] of this library, so that a hub can check that the
+// library it loaded matches the header it was written against.
+uint32_t pubhubs_abi_version(void);
+
+// Decrypts the given `ciphertext` using the given `private_key` and stores the result in
+// `plaintext`.
+//
+//   * `plaintext` - pointer to a writable 32-byte buffer
+//   * `ciperhtext` - pointer to a 96-byte buffer holding the result of [Triple::to_bytes]
+//   * `private_key` - pointer to a 32-byte buffer holding the result of [PrivateKey::to_bytes]
+//
+// # Safety
+// The caller must make sure the pointers are aligned, point to valid memory regions,
+// are readable, and plaintext is writable, and are not otherwise modified.
+//
+// For more details, see [core::slice::from_raw_parts] and [core::slice::from_raw_parts_mut].
+DecryptResult decrypt(uint8_t *plaintext, const uint8_t *ciphertext, const uint8_t *private_key);
+
+// Loads the 32-byte `private_key` into a new handle, stored in `key`.
+//
+// Returns [DecryptResult::InvalidPrivateKey] (and leaves `key` untouched) when `private_key`
+// is invalid.  The caller may overwrite the buffer `private_key` afterwards.
+//
+// The handle must be freed using [pubhubs_key_free].
+//
+// # Safety
+// See [decrypt].  Also, `key` must be writable.
+DecryptResult pubhubs_key_load(struct PubhubsKey **key, const uint8_t *private_key);
+
+// Frees the handle `key` obtained via [pubhubs_key_load].  Does nothing when `key` is null.
+//
+// # Safety
+// The handle `key` must not be freed twice, and must not be used after, or while, being freed.
+void pubhubs_key_free(struct PubhubsKey *key);
+
+// Like [decrypt], but using a private key loaded with [pubhubs_key_load].
+//
+// # Safety
+// See [decrypt].  Also, `key` must be a valid handle that is not freed during the call.
+DecryptResult pubhubs_key_decrypt(uint8_t *plaintext,
+                                  const uint8_t *ciphertext,
+                                  const struct PubhubsKey *key);
+
+// Like [pubhubs_key_decrypt], but tries each of the `count` handles in `keys`, for example the
+// hub's current and previous key during the rotation of the hub's secret.
+//
+// On success, the index of the key used is written to `used`.  Returns
+// [DecryptResult::WrongPublicKey] when the ciphertext is not encrypted for any of the keys.
+//
+// # Safety
+// See [pubhubs_key_decrypt].  Also, `keys` must point to `count` valid handles,
+// and `used` must be writable.  When `count` is zero, `keys` may be NULL.
+DecryptResult pubhubs_keys_decrypt(uint8_t *plaintext,
+                                   const uint8_t *ciphertext,
+                                   const struct PubhubsKey *const *keys,
+                                   size_t count,
+                                   size_t *used);
+
+// Derives the public key from `private_key`.
+//
+//   * `public_key` - pointer to a writable 32-byte buffer
+//   * `private_key` - pointer to a 32-byte buffer holding an encoded private key
+//
+// # Safety
+// The caller must make sure the pointers are aligned, point to valid memory regions of the
+// given sizes, `public_key` is writable, and they are not otherwise modified during the call.
+PubhubsResult pubhubs_public_key(uint8_t *public_key, const uint8_t *private_key);
+
+// Rerandomizes the 96-byte `triple`, see [Triple::rerandomize], and stores the result in
+// `result`, which may coincide with `triple`.
+//
+// # Safety
+// See [pubhubs_public_key].
+PubhubsResult pubhubs_rerandomize(uint8_t *result, const uint8_t *triple);
+
+// Returns [PubhubsResult::Ok] when the 96-byte `triple` claims to be encrypted for the
+// 32-byte `public_key`, and [PubhubsResult::WrongPublicKey] when it's not.
+//
+// # Safety
+// See [pubhubs_public_key].
+PubhubsResult pubhubs_triple_targets(const uint8_t *triple, const uint8_t *public_key);
+
+// Writes the lower-case hex encoding of the `len` bytes at `bytes` to the `2*len` bytes at
+// `hex`.  No terminating zero is written.
+//
+// # Safety
+// See [pubhubs_public_key].
+void pubhubs_hex_encode(uint8_t *hex, const uint8_t *bytes, size_t len);
+
+// Decodes the `2*len` hex digits (of any case) at `hex` into the `len` bytes at `bytes`.
+//
+// # Safety
+// See [pubhubs_public_key].
+PubhubsResult pubhubs_hex_decode(uint8_t *bytes, const uint8_t *hex, size_t len);
+
+// Writes the `n`th short pseudonym for the 32-byte (decrypted) `local_pseudonym` as
+// zero-terminated string to `short_pseudonym`,
+// see [short_pseudonym::short_pseudonym].
+//
+//   * `short_pseudonym` - pointer to a writable buffer of at least
+//     [PUBHUBS_SHORT_PSEUDONYM_MAX_LEN]+1 bytes
+//   * `local_pseudonym` - pointer to 32-byte buffer, e.g. the plaintext obtained via `decrypt`
+//   * `n` - at most 13;  the short pseudonym will have `2*n + 7` characters
+//
+// # Safety
+// See [pubhubs_public_key].
+PubhubsResult pubhubs_short_pseudonym(uint8_t *short_pseudonym,
+                                      const uint8_t *local_pseudonym,
+                                      size_t n);
+
+// Computes the check digit for the `len` base 17 digits at `digits`,
+// see [short_pseudonym::checkdigit], and writes it to `checkdigit`.
+//
+// # Safety
+// See [pubhubs_public_key].  When `len` is zero, `digits` may be NULL.
+PubhubsResult pubhubs_checkdigit(uint8_t *checkdigit, const uint8_t *digits, size_t len);
+
+#endif  /* PUBHUBS_H */
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use crate::misc::{fmt_ext, serde_ext};
+use crate::misc::{fmt_ext, serde_ext, task};
 use crate::servers::server;
 
 /// The result of an API-request to a PubHubs server endpoint.
@@ -104,6 +104,15 @@ pub enum ErrorCode {
 
     #[error("server encountered an unexpected problem")]
     InternalError,
+
+    #[error("the request is invalid")]
+    BadRequest,
+
+    #[error("the request could not be authenticated")]
+    NotAuthenticated,
+
+    #[error("no hub with this name or id is known")]
+    UnknownHub,
 }
 use ErrorCode::*;
 
@@ -121,7 +130,12 @@ impl ErrorCode {
     /// Returns additional information about this error code.
     pub fn info(&self) -> ErrorInfo {
         match self {
-            AlreadyRunning | NoLongerInCorrectState | Malconfigured => ErrorInfo {
+            AlreadyRunning
+            | NoLongerInCorrectState
+            | Malconfigured
+            | BadRequest
+            | NotAuthenticated
+            | UnknownHub => ErrorInfo {
                 retryable: Some(false),
             },
             CouldNotConnectYet | TemporaryFailure | NotYetReady => ErrorInfo {
@@ -195,20 +209,86 @@ pub trait EndpointDetails {
     const PATH: &'static str;
 }
 
-/// Like [query], but retries the query when it fails with a [ErrorInfo::retryable] [ErrorCode].
+/// Like [query], but retries the query when it fails with a [ErrorInfo::retryable] [ErrorCode],
+/// using the default [task::RetryOptions].
+///
+/// While we repeatedly failed to connect to the server at `server_url` recently, see
+/// [task::CircuitBreaker], tries are skipped instead of sent, as if they failed.  When retrying
+/// is given up during such a period, [ErrorCode::CouldNotConnect] is returned.
 pub async fn query_with_retry<EP: EndpointDetails>(
     server_url: &url::Url,
     req: &EP::RequestType,
 ) -> Result<EP::ResponseType> {
-    match crate::misc::task::retry(|| async { query::<EP>(server_url, req).await.retryable() })
-        .await
-    {
+    query_with_retry_options::<EP>(server_url, req, &Default::default()).await
+}
+
+/// Like [query_with_retry], but using the given [task::RetryOptions].
+pub async fn query_with_retry_options<EP: EndpointDetails>(
+    server_url: &url::Url,
+    req: &EP::RequestType,
+    options: &task::RetryOptions,
+) -> Result<EP::ResponseType> {
+    let peer = server_url.origin().ascii_serialization();
+    let circuit_breaker = circuit_breaker();
+
+    // whether the last try was skipped
+    let circuit_open = std::cell::Cell::new(false);
+
+    let (result, outcome) = options
+        .retry_with_outcome(|| async {
+            if !circuit_breaker.allows(&peer) {
+                log::warn!(
+                    "not querying {} {server_url} for now: failed to connect too often",
+                    EP::PATH
+                );
+                circuit_open.set(true);
+                return Ok(None);
+            }
+            circuit_open.set(false);
+
+            let result = query::<EP>(server_url, req).await;
+
+            circuit_breaker.record(
+                &peer,
+                !matches!(
+                    result,
+                    Result::Err(ErrorCode::CouldNotConnectYet | ErrorCode::CouldNotConnect)
+                ),
+            );
+
+            result.retryable()
+        })
+        .await;
+
+    let outcome = match outcome {
+        task::RetryOutcome::Exhausted
+        | task::RetryOutcome::DeadlineExceeded
+        | task::RetryOutcome::Cancelled
+            if circuit_open.get() =>
+        {
+            task::RetryOutcome::CircuitOpen
+        }
+        outcome => outcome,
+    };
+    outcome.record();
+
+    match result {
         Ok(Some(resp)) => Result::Ok(resp),
+        Ok(None) if outcome == task::RetryOutcome::CircuitOpen => {
+            Result::Err(ErrorCode::CouldNotConnect)
+        }
         Ok(None) => Result::Err(ErrorCode::TemporaryFailure),
         Err(ec) => Result::Err(ec),
     }
 }
 
+/// The [task::CircuitBreaker] used by [query_with_retry].
+fn circuit_breaker() -> &'static task::CircuitBreaker {
+    static CIRCUIT_BREAKER: std::sync::OnceLock<task::CircuitBreaker> = std::sync::OnceLock::new();
+
+    CIRCUIT_BREAKER.get_or_init(Default::default)
+}
+
 /// Sends a request to `EP` [endpoint](EndpointDetails) at `server_url`.
 pub async fn query<EP: EndpointDetails>(
     server_url: &url::Url,
@@ -334,6 +414,12 @@ pub async fn query<EP: EndpointDetails>(
     response
 }
 
+/// Path at which each PubHubs server publishes the key it signs JWTs with as a standard
+/// JSON Web Key Set, see [crate::misc::jwt::Jwks].
+///
+/// Not described by [EndpointDetails], because the response is not wrapped in a [Result].
+pub const JWKS_PATH: &str = ".well-known/jwks.json";
+
 pub struct DiscoveryInfo {}
 impl EndpointDetails for DiscoveryInfo {
     type RequestType = ();
@@ -351,3 +437,35 @@ impl EndpointDetails for DiscoveryRun {
     const METHOD: http::Method = http::Method::POST;
     const PATH: &'static str = ".ph/discovery/run";
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn query_with_retry_open_circuit() {
+        // nothing listens on port 1
+        let url: url::Url = "http://127.0.0.1:1".parse().unwrap();
+        let options = task::RetryOptions {
+            initial_wait_time: std::time::Duration::from_millis(1),
+            max_wait_time: std::time::Duration::from_millis(1),
+            max_retries: Some(2 * circuit_breaker().failure_threshold as usize),
+            ..Default::default()
+        };
+
+        let circuit_open = || {
+            task::retry_metrics()
+                .with_label_values(&[task::RetryOutcome::CircuitOpen.as_str()])
+                .get()
+        };
+        let before = circuit_open();
+
+        // the circuit opens halfway, after which the remaining tries are skipped
+        assert!(matches!(
+            query_with_retry_options::<crate::api::DiscoveryInfo>(&url, &(), &options).await,
+            Result::Err(ErrorCode::CouldNotConnect)
+        ));
+        assert_eq!(circuit_open(), before + 1);
+        assert!(!circuit_breaker().allows(&url.origin().ascii_serialization()));
+    }
+}
@@ -3,3 +3,4 @@ mod common;
 pub use common::*;
 pub mod hub;
 pub mod phc;
+pub mod transcryptor;
@@ -1,12 +1,131 @@
 //! Additional endpoints provided by PubHubs Central
-//use serde::{Deserialize, Serialize};
-//use crate::api::*;
-//use crate::misc::serde_ext;
+use serde::{Deserialize, Serialize};
+
+use crate::api::*;
+use crate::misc::serde_ext;
 
 /// `.ph/hubs/...` endpoints
 pub mod hub {
-    /*
     use super::*;
+
+    /// Used by a hub to obtain its local decryption key(s) (the 'Hub secret') from PubHubs
+    /// Central.  The keys are sealed (see [crate::misc::sealed_box]) to the `verifying_key`
+    /// advertised by the hub info endpoint (see [crate::api::hub::Info]) at `info_url`,
+    /// so they can only be read by the hub.
+    ///
+    /// Currently only provided by the old PubHubs Central.
+    pub struct DecryptionKey {}
+    impl EndpointDetails for DecryptionKey {
+        type RequestType = DecryptionKeyReq;
+        type ResponseType = DecryptionKeyResp;
+
+        const METHOD: http::Method = http::Method::POST;
+        const PATH: &'static str = ".ph/hubs/decryption-key";
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct DecryptionKeyReq {
+        /// Name of the hub
+        pub hub: String,
+
+        /// The hub's OpenID Connect client credentials, which authenticate the request.
+        pub client_id: String,
+        pub client_password: String,
+
+        /// The hub's info endpoint.  Must have the same origin as the hub's OpenID Connect
+        /// redirect uri known to PubHubs Central.
+        pub info_url: url::Url,
+    }
+
+    /// Hides the client password.
+    impl std::fmt::Debug for DecryptionKeyReq {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("DecryptionKeyReq")
+                .field("hub", &self.hub)
+                .field("client_id", &self.client_id)
+                .field("info_url", &self.info_url)
+                .finish_non_exhaustive()
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct DecryptionKeyResp {
+        /// The hub's hex-encoded local decryption keys, comma-separated, newest first
+        /// (like the `HUB_SECRET` environmental variable used by the hub), sealed to the
+        /// hub's verifying key with the hub's name as associated data.
+        pub sealed_keys: serde_ext::B64UU,
+    }
+
+    /// Looks up a hub by any of its names, or by its id.  Used by clients to find the hub
+    /// a (possibly old) link refers to.
+    ///
+    /// Returns [ErrorCode::UnknownHub] if there's no such hub.
+    pub struct Resolve {}
+    impl EndpointDetails for Resolve {
+        type RequestType = ResolveReq;
+        type ResponseType = ResolveResp;
+
+        const METHOD: http::Method = http::Method::POST;
+        const PATH: &'static str = ".ph/hubs/resolve";
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct ResolveReq {
+        /// One of the hub's names, or its id
+        pub name_or_id: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct ResolveResp {
+        pub hub: crate::hub::BasicInfo,
+
+        /// Set to the hub's default name when the request did not use it, in which case
+        /// the client should use this name from now on (e.g. by redirecting.)
+        pub redirect_to: Option<crate::hub::Name>,
+    }
+
+    /// Lists the hubs known to PubHubs Central, with their descriptions in the language
+    /// requested via [DirectoryReq::lang], or else via the `Accept-Language` header.
+    pub struct Directory {}
+    impl EndpointDetails for Directory {
+        type RequestType = DirectoryReq;
+        type ResponseType = DirectoryResp;
+
+        const METHOD: http::Method = http::Method::POST;
+        const PATH: &'static str = ".ph/hubs/directory";
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Default)]
+    pub struct DirectoryReq {
+        /// Language code, like `en` or `nl`, taking precedence over the `Accept-Language` header.
+        #[serde(default)]
+        pub lang: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct DirectoryResp {
+        pub hubs: Vec<DirectoryEntry>,
+    }
+
+    /// A hub's [crate::hub::BasicInfo] with the description in one language.
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct DirectoryEntry {
+        /// The hub's default name
+        pub name: crate::hub::Name,
+        pub id: crate::hub::Id,
+        pub description: String,
+
+        /// The language of `description`, or [None] if no translation in a requested language
+        /// was available, and the default description was used.
+        pub description_lang: Option<String>,
+
+        pub info_url: url::Url,
+        pub tags: Vec<String>,
+        pub contact_url: Option<url::Url>,
+        pub logo_url: Option<url::Url>,
+    }
+
+    /* TODO: use
     /// Used by a hub to request ticket (see [TocketContent]) from PubHubs Central.
     /// The request must be signed for the `verifying_key` advertised by the hub info endoint
     /// (see crate::api::hub::Info).
@@ -0,0 +1,131 @@
+//! Endpoints provided by the Transcryptor
+use serde::{Deserialize, Serialize};
+
+use crate::api::*;
+use crate::elgamal::{proof, Triple};
+use crate::misc::serde_ext;
+
+/// Used by PubHubs Central to have the Transcryptor apply [Triple::rsk] to `triples` with the
+/// Transcryptor's factors for `hub`.  Each resulting triple comes with a [proof::RskProof],
+/// which PubHubs Central must check using [RskResp::verify], so that a buggy or malicious
+/// Transcryptor can't swap or garble pseudonyms unnoticed.
+///
+/// The factors are derived from the Transcryptor's `factor_secret` and `hub` using
+/// [crate::factors::Version::V1].
+pub struct Rsk {}
+impl EndpointDetails for Rsk {
+    type RequestType = RskReq;
+    type ResponseType = RskResp;
+
+    const METHOD: http::Method = http::Method::POST;
+    const PATH: &'static str = ".ph/rsk";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RskReq {
+    /// The hub whose factors are to be used
+    pub hub: crate::hub::Id,
+
+    pub triples: Vec<serde_ext::B16<Triple>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RskResp {
+    /// Commitments to the Transcryptor's factors for the hub.  These should not change between
+    /// requests for the same hub, so PubHubs Central should pin them.
+    pub commitments: serde_ext::B16<proof::FactorCommitments>,
+
+    /// The results, in the same order as [RskReq::triples]
+    pub results: Vec<RskResult>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RskResult {
+    pub triple: serde_ext::B16<Triple>,
+    pub proof: serde_ext::B16<proof::RskProof>,
+}
+
+impl RskResp {
+    /// Checks that this is a response to `req` in which every triple was obtained by [Triple::rsk]
+    /// from the corresponding triple in `req`, using the factors committed to by `commitments`,
+    /// which must equal [RskResp::commitments].
+    ///
+    /// Returns the resulting triples if so.
+    pub fn verify(
+        &self,
+        req: &RskReq,
+        commitments: &proof::FactorCommitments,
+    ) -> Option<Vec<Triple>> {
+        if *commitments != *self.commitments || req.triples.len() != self.results.len() {
+            return None;
+        }
+
+        req.triples
+            .iter()
+            .zip(self.results.iter())
+            .map(|(input, result)| {
+                result
+                    .proof
+                    .verify(input, &result.triple, commitments)
+                    .then(|| (*result.triple).clone())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elgamal::{random_scalar, rsk, PrivateKey};
+
+    #[test]
+    fn rsk_resp_verify() {
+        let pk = PrivateKey::random().public_key();
+        let (s, k) = (random_scalar(), random_scalar());
+        let commitments = proof::FactorCommitments::new(&s, &k);
+
+        let req = RskReq {
+            hub: crate::hub::Id::random(),
+            triples: (0..3).map(|_| pk.encrypt_random().into()).collect(),
+        };
+
+        let results: Vec<RskResult> = req
+            .triples
+            .iter()
+            .map(|triple| {
+                let (triple, proof) = (**triple).clone().rsk_with_proof(rsk::SAndK::new(&s, &k));
+                RskResult {
+                    triple: triple.into(),
+                    proof: proof.into(),
+                }
+            })
+            .collect();
+
+        let resp = RskResp {
+            commitments: commitments.clone().into(),
+            results: results.clone(),
+        };
+
+        let outputs = resp.verify(&req, &commitments).unwrap();
+        assert_eq!(outputs.len(), 3);
+        assert_eq!(outputs[0], *results[0].triple);
+
+        // survives serialization
+        let resp: RskResp = serde_json::from_str(&serde_json::to_string(&resp).unwrap()).unwrap();
+        assert!(resp.verify(&req, &commitments).is_some());
+
+        // other factors
+        let other = proof::FactorCommitments::new(&k, &s);
+        assert!(resp.verify(&req, &other).is_none());
+
+        // swapped pseudonyms
+        let mut swapped = resp.clone();
+        swapped.results.swap(0, 1);
+        assert!(swapped.verify(&req, &commitments).is_none());
+
+        // missing result
+        let mut missing = resp.clone();
+        missing.results.pop();
+        assert!(missing.verify(&req, &commitments).is_none());
+    }
+}
@@ -1,6 +1,8 @@
+use crate::api;
 use crate::hub;
+use crate::misc::{sealed_box, serde_ext};
 
-use anyhow::Result;
+use anyhow::{Context as _, Result};
 
 #[derive(clap::Args, Debug)]
 pub struct ToolsArgs {
@@ -12,6 +14,14 @@ impl ToolsArgs {
     pub fn run(self, _spec: &mut clap::Command) -> Result<()> {
         match self.command {
             Commands::GenerateHubid(args) => args.run(),
+            Commands::GenerateHubSigningKey(args) => args.run(),
+            Commands::FetchHubSecret(args) => args.run(),
+            #[cfg(feature = "old")]
+            Commands::MigrateLegacy(args) => args.run(),
+            #[cfg(feature = "old")]
+            Commands::BackupLegacy(args) => args.run(),
+            #[cfg(feature = "old")]
+            Commands::RestoreLegacy(args) => args.run(),
         }
     }
 }
@@ -20,6 +30,24 @@ impl ToolsArgs {
 enum Commands {
     /// Generates a random hub identifier
     GenerateHubid(GenerateHubidArgs),
+
+    /// Generates a signing key for a hub, and the response its info endpoint should return
+    GenerateHubSigningKey(GenerateHubSigningKeyArgs),
+
+    /// Fetches the hub's local decryption key (the 'Hub secret') from PubHubs Central
+    FetchHubSecret(Box<FetchHubSecretArgs>),
+
+    /// Exports the hubs, users and policy of the legacy PubHubs Central for the new servers
+    #[cfg(feature = "old")]
+    MigrateLegacy(MigrateLegacyArgs),
+
+    /// Lets a running legacy PubHubs Central back up its database to its backup directory
+    #[cfg(feature = "old")]
+    BackupLegacy(BackupLegacyArgs),
+
+    /// Replaces the database of a stopped legacy PubHubs Central by a backup
+    #[cfg(feature = "old")]
+    RestoreLegacy(RestoreLegacyArgs),
 }
 
 #[derive(clap::Args, Debug)]
@@ -32,3 +60,285 @@ impl GenerateHubidArgs {
         Ok(())
     }
 }
+
+#[derive(clap::Args, Debug)]
+pub struct GenerateHubSigningKeyArgs {}
+
+impl GenerateHubSigningKeyArgs {
+    fn run(self) -> Result<()> {
+        let signing_key: serde_ext::B16<ed25519_dalek::SigningKey> =
+            ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng).into();
+
+        let info = api::Result::Ok(api::hub::InfoResp {
+            verifying_key: signing_key.verifying_key().into(),
+        });
+
+        println!("signing key (keep secret):  {signing_key}");
+        println!(
+            "info endpoint response:     {}",
+            serde_json::to_string(&info)?
+        );
+
+        Ok(())
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct FetchHubSecretArgs {
+    /// URL of PubHubs Central
+    #[arg(long, value_name = "URL")]
+    phc_url: url::Url,
+
+    /// Name of the hub
+    #[arg(long)]
+    hub: String,
+
+    /// The hub's OpenID Connect client id
+    #[arg(long)]
+    client_id: String,
+
+    /// File containing the hub's OpenID Connect client password
+    #[arg(long, value_name = "PATH")]
+    client_password_file: std::path::PathBuf,
+
+    /// URL of the hub's info endpoint, advertising the verifying key belonging to the signing key
+    #[arg(long, value_name = "URL")]
+    info_url: url::Url,
+
+    /// File containing the hub's hex-encoded signing key, see `generate-hub-signing-key`
+    #[arg(long, value_name = "PATH")]
+    signing_key_file: std::path::PathBuf,
+}
+
+impl FetchHubSecretArgs {
+    /// Prints the hub's local decryption keys in the format expected by the `HUB_SECRET`
+    /// environmental variable.
+    fn run(self) -> Result<()> {
+        let signing_key: serde_ext::B16<ed25519_dalek::SigningKey> =
+            std::fs::read_to_string(&self.signing_key_file)
+                .with_context(|| format!("could not read {}", self.signing_key_file.display()))?
+                .trim()
+                .parse()
+                .map_err(|err| anyhow::anyhow!("{err:?}"))
+                .context("invalid signing key")?;
+
+        let client_password = std::fs::read_to_string(&self.client_password_file)
+            .with_context(|| format!("could not read {}", self.client_password_file.display()))?
+            .trim()
+            .to_string();
+
+        let req = api::phc::hub::DecryptionKeyReq {
+            hub: self.hub.clone(),
+            client_id: self.client_id.clone(),
+            client_password,
+            info_url: self.info_url.clone(),
+        };
+
+        let resp = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?
+            .block_on(
+                tokio::task::LocalSet::new().run_until(api::query_with_retry::<
+                    api::phc::hub::DecryptionKey,
+                >(&self.phc_url, &req)),
+            );
+
+        let resp = match resp {
+            api::Result::Ok(resp) => resp,
+            api::Result::Err(ec) => {
+                anyhow::bail!("PubHubs Central could not deliver the hub secret: {ec}")
+            }
+        };
+
+        let keys = zeroize::Zeroizing::new(
+            sealed_box::unseal(&resp.sealed_keys, &signing_key, &self.hub)
+                .context("could not unseal the hub secret")?,
+        );
+
+        println!(
+            "{}",
+            std::str::from_utf8(&keys).context("hub secret is not valid utf8")?
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "old")]
+#[derive(clap::Args, Debug)]
+pub struct MigrateLegacyArgs {
+    /// The legacy database file.  Pending migrations of its schema are applied, so consider
+    /// passing a copy.
+    #[arg(long, value_name = "PATH")]
+    database: std::path::PathBuf,
+
+    /// Path of the hubs' info endpoints, relative to the origin of their redirect uri
+    #[arg(long, value_name = "PATH")]
+    info_path: String,
+
+    /// Directory to write hubs.yaml (for `phc.hubs`), hub_secrets.yaml, users.json, policy.json
+    /// and report.txt to.  These files may not exist yet.
+    #[arg(long, value_name = "DIR")]
+    out_dir: std::path::PathBuf,
+}
+
+#[cfg(feature = "old")]
+impl MigrateLegacyArgs {
+    fn run(self) -> Result<()> {
+        use crate::migrate;
+
+        // get_manager would create an empty database instead
+        if !self.database.is_file() {
+            anyhow::bail!("no database at {}", self.database.display());
+        }
+
+        let db = crate::data::get_manager(&self.database)
+            .with_context(|| format!("could not open {}", self.database.display()))?;
+
+        let migration = migrate::migrate(&db, &self.info_path)?;
+
+        std::fs::create_dir_all(&self.out_dir)
+            .with_context(|| format!("could not create {}", self.out_dir.display()))?;
+
+        self.write("hubs.yaml", serde_yaml::to_string(&migration.hubs)?)?;
+        self.write(
+            "hub_secrets.yaml",
+            serde_yaml::to_string(&migration.hub_secrets)?,
+        )?;
+        self.write(
+            "users.json",
+            serde_json::to_string_pretty(&migration.users)?,
+        )?;
+        self.write(
+            "policy.json",
+            serde_json::to_string_pretty(&migration.policy)?,
+        )?;
+        self.write("report.txt", migration.report.to_string())?;
+
+        print!("{}", migration.report);
+        println!(
+            "migrated {} hub(s) and {} user(s);  {} item(s) could not be migrated",
+            migration.hubs.len(),
+            migration.users.len(),
+            migration.report.unmapped.len()
+        );
+
+        Ok(())
+    }
+
+    /// Writes `contents` to the new file `name` in [Self::out_dir], only readable by its owner,
+    /// because it might contain personal data or secrets.
+    fn write(&self, name: &str, contents: String) -> Result<()> {
+        use std::io::Write as _;
+
+        let path = self.out_dir.join(name);
+
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        options
+            .open(&path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+            .with_context(|| format!("could not write {}", path.display()))
+    }
+}
+
+#[cfg(feature = "old")]
+#[derive(clap::Args, Debug)]
+pub struct BackupLegacyArgs {
+    /// URL of the legacy PubHubs Central
+    #[arg(long, value_name = "URL")]
+    phc_url: url::Url,
+
+    /// File containing PubHubs Central's admin API key
+    #[arg(long, value_name = "PATH")]
+    admin_api_key_file: std::path::PathBuf,
+
+    /// File name of the backup in PubHubs Central's backup directory;  by default based on the
+    /// current time
+    #[arg(long)]
+    name: Option<String>,
+}
+
+#[cfg(feature = "old")]
+impl BackupLegacyArgs {
+    /// Prints the path of the backup on PubHubs Central's side.
+    fn run(self) -> Result<()> {
+        let admin_api_key = std::fs::read_to_string(&self.admin_api_key_file)
+            .with_context(|| format!("could not read {}", self.admin_api_key_file.display()))?
+            .trim()
+            .to_string();
+
+        let mut url = self.phc_url.join("admin/backup")?;
+        if let Some(name) = self.name.as_ref() {
+            url.query_pairs_mut().append_pair("name", name);
+        }
+
+        let path = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?
+            .block_on(tokio::task::LocalSet::new().run_until(async {
+                let mut resp = awc::Client::default()
+                    .post(url.as_str())
+                    .insert_header(("X-Admin-API-Key", admin_api_key))
+                    .send()
+                    .await
+                    .map_err(|err| anyhow::anyhow!("could not reach {url}: {err}"))?;
+
+                let status = resp.status();
+                let body = resp.body().await?;
+
+                anyhow::ensure!(
+                    status.is_success(),
+                    "backup failed with status {status};  see PubHubs Central's logs"
+                );
+
+                Ok::<_, anyhow::Error>(String::from_utf8(body.to_vec())?)
+            }))?;
+
+        println!("{path}");
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "old")]
+#[derive(clap::Args, Debug)]
+pub struct RestoreLegacyArgs {
+    /// The backup, made by `backup-legacy`
+    #[arg(long, value_name = "PATH")]
+    backup: std::path::PathBuf,
+
+    /// The database file of the legacy PubHubs Central, which must not be running
+    #[arg(long, value_name = "PATH")]
+    database: std::path::PathBuf,
+}
+
+#[cfg(feature = "old")]
+impl RestoreLegacyArgs {
+    fn run(self) -> Result<()> {
+        if !self.backup.is_file() {
+            anyhow::bail!("no backup at {}", self.backup.display());
+        }
+
+        let moved_aside = crate::data::restore(&self.backup, &self.database)?;
+
+        println!(
+            "restored {} to {}",
+            self.backup.display(),
+            self.database.display()
+        );
+
+        if let Some(moved_aside) = moved_aside {
+            println!(
+                "the previous database was moved to {};  remove it when all is well",
+                moved_aside.display()
+            );
+        }
+
+        Ok(())
+    }
+}
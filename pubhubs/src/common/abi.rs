@@ -0,0 +1,588 @@
+//! Application binary interface of `libpubhubs.so`, used by the hubs.
+//!
+//! The C header `include/pubhubs.h` is generated from this interface using cbindgen.  The test
+//! `common::abi::tests::header` fails when the header is out of date, and rewrites it when run
+//! with `PUBHUBS_UPDATE_HEADER=1`:
+//!
+//! ```sh
+//! PUBHUBS_UPDATE_HEADER=1 cargo test --no-default-features --features=abi --lib header
+//! ```
+//!
+//! Increase [PUBHUBS_ABI_VERSION] whenever the interface changes in a backwards incompatible
+//! manner.
+//!
+//! The functions exported here take pointers to fixed size buffers holding the
+//! [Encoding](crate::elgamal::Encoding) of the objects involved, and return a [PubhubsResult]
+//! (or [DecryptResult]).
+use crate::elgamal::{Encoding as _, PrivateKey, PublicKey, Triple};
+use crate::short_pseudonym;
+use curve25519_dalek::ristretto::RistrettoPoint;
+
+/// Version of the interface described by `pubhubs.h`.
+pub const PUBHUBS_ABI_VERSION: u32 = 1;
+
+/// Returns the [PUBHUBS_ABI_VERSION] of this library, so that a hub can check that the
+/// library it loaded matches the header it was written against.
+#[no_mangle]
+pub extern "C" fn pubhubs_abi_version() -> u32 {
+    PUBHUBS_ABI_VERSION
+}
+
+/// Decrypts the given `ciphertext` using the given `private_key` and stores the result in
+/// `plaintext`.
+///
+///   * `plaintext` - pointer to a writable 32-byte buffer
+///   * `ciperhtext` - pointer to a 96-byte buffer holding the result of [Triple::to_bytes]
+///   * `private_key` - pointer to a 32-byte buffer holding the result of [PrivateKey::to_bytes]
+///
+/// # Safety
+/// The caller must make sure the pointers are aligned, point to valid memory regions,
+/// are readable, and plaintext is writable, and are not otherwise modified.
+///
+/// For more details, see [core::slice::from_raw_parts] and [core::slice::from_raw_parts_mut].
+#[no_mangle]
+pub unsafe extern "C" fn decrypt(
+    plaintext: *mut u8,
+    ciphertext: *const u8,
+    private_key: *const u8,
+) -> DecryptResult {
+    let pk = match unsafe { PrivateKey::from_ptr(private_key) } {
+        Some(pk) => pk,
+        None => return DecryptResult::InvalidPrivateKey,
+    };
+
+    let ct = match unsafe { Triple::from_ptr(ciphertext) } {
+        Some(ct) => ct,
+        None => return DecryptResult::InvalidTriple,
+    };
+
+    let pt = match ct.decrypt_and_check_pk(&pk) {
+        Some(pt) => pt,
+        None => return DecryptResult::WrongPublicKey,
+    };
+
+    unsafe { pt.copy_to_ptr(plaintext) }
+
+    DecryptResult::Ok
+}
+
+/// Result of [decrypt].
+#[repr(u8)]
+pub enum DecryptResult {
+    Ok = 1,
+    WrongPublicKey = 2,
+    InvalidTriple = 3,
+    InvalidPrivateKey = 4,
+}
+
+/// Result of the other functions in this interface.
+///
+/// The first four values coincide with those of [DecryptResult].
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PubhubsResult {
+    Ok = 1,
+    WrongPublicKey = 2,
+    InvalidTriple = 3,
+    InvalidPrivateKey = 4,
+    InvalidPublicKey = 5,
+    /// Returned when a hex string has the wrong length or contains non-hex digits
+    InvalidHex = 6,
+    /// Returned when a local pseudonym is not a valid 32-byte plaintext, or `n` is too large
+    InvalidArgument = 7,
+}
+
+/// Private key loaded by [pubhubs_key_load], opaque to the caller.
+///
+/// Since a loaded key is never modified, a handle may be used by several threads at the same time.
+/// The key is zeroized when the handle is freed using [pubhubs_key_free].
+pub struct PubhubsKey {
+    private_key: PrivateKey,
+    /// cached, to quickly check whether a triple is encrypted for this key
+    public_key: PublicKey,
+}
+
+impl PubhubsKey {
+    fn decrypt(&self, triple: Triple) -> Option<RistrettoPoint> {
+        triple
+            .targets(&self.public_key)
+            .then(|| triple.decrypt(&self.private_key))
+    }
+}
+
+/// Loads the 32-byte `private_key` into a new handle, stored in `key`.
+///
+/// Returns [DecryptResult::InvalidPrivateKey] (and leaves `key` untouched) when `private_key`
+/// is invalid.  The caller may overwrite the buffer `private_key` afterwards.
+///
+/// The handle must be freed using [pubhubs_key_free].
+///
+/// # Safety
+/// See [decrypt].  Also, `key` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn pubhubs_key_load(
+    key: *mut *mut PubhubsKey,
+    private_key: *const u8,
+) -> DecryptResult {
+    let private_key = match unsafe { PrivateKey::from_ptr(private_key) } {
+        Some(private_key) => private_key,
+        None => return DecryptResult::InvalidPrivateKey,
+    };
+
+    let handle = Box::new(PubhubsKey {
+        public_key: private_key.public_key(),
+        private_key,
+    });
+
+    unsafe { *key = Box::into_raw(handle) };
+
+    DecryptResult::Ok
+}
+
+/// Frees the handle `key` obtained via [pubhubs_key_load].  Does nothing when `key` is null.
+///
+/// # Safety
+/// The handle `key` must not be freed twice, and must not be used after, or while, being freed.
+#[no_mangle]
+pub unsafe extern "C" fn pubhubs_key_free(key: *mut PubhubsKey) {
+    if !key.is_null() {
+        drop(unsafe { Box::from_raw(key) });
+    }
+}
+
+/// Like [decrypt], but using a private key loaded with [pubhubs_key_load].
+///
+/// # Safety
+/// See [decrypt].  Also, `key` must be a valid handle that is not freed during the call.
+#[no_mangle]
+pub unsafe extern "C" fn pubhubs_key_decrypt(
+    plaintext: *mut u8,
+    ciphertext: *const u8,
+    key: *const PubhubsKey,
+) -> DecryptResult {
+    let key = unsafe { &*key };
+
+    let ct = match unsafe { Triple::from_ptr(ciphertext) } {
+        Some(ct) => ct,
+        None => return DecryptResult::InvalidTriple,
+    };
+
+    let pt = match key.decrypt(ct) {
+        Some(pt) => pt,
+        None => return DecryptResult::WrongPublicKey,
+    };
+
+    unsafe { pt.copy_to_ptr(plaintext) }
+
+    DecryptResult::Ok
+}
+
+/// Like [pubhubs_key_decrypt], but tries each of the `count` handles in `keys`, for example the
+/// hub's current and previous key during the rotation of the hub's secret.
+///
+/// On success, the index of the key used is written to `used`.  Returns
+/// [DecryptResult::WrongPublicKey] when the ciphertext is not encrypted for any of the keys.
+///
+/// # Safety
+/// See [pubhubs_key_decrypt].  Also, `keys` must point to `count` valid handles,
+/// and `used` must be writable.  When `count` is zero, `keys` may be NULL.
+#[no_mangle]
+pub unsafe extern "C" fn pubhubs_keys_decrypt(
+    plaintext: *mut u8,
+    ciphertext: *const u8,
+    keys: *const *const PubhubsKey,
+    count: usize,
+    used: *mut usize,
+) -> DecryptResult {
+    // from_raw_parts requires a non-null pointer, even for an empty slice
+    let keys: &[*const PubhubsKey] = if count == 0 {
+        &[]
+    } else {
+        unsafe { core::slice::from_raw_parts(keys, count) }
+    };
+
+    let ct = match unsafe { Triple::from_ptr(ciphertext) } {
+        Some(ct) => ct,
+        None => return DecryptResult::InvalidTriple,
+    };
+
+    for (i, key) in keys.iter().enumerate() {
+        let key = unsafe { &**key };
+
+        if let Some(pt) = key.decrypt(ct.clone()) {
+            unsafe {
+                pt.copy_to_ptr(plaintext);
+                *used = i;
+            }
+
+            return DecryptResult::Ok;
+        }
+    }
+
+    DecryptResult::WrongPublicKey
+}
+
+/// Derives the public key from `private_key`.
+///
+///   * `public_key` - pointer to a writable 32-byte buffer
+///   * `private_key` - pointer to a 32-byte buffer holding an encoded private key
+///
+/// # Safety
+/// The caller must make sure the pointers are aligned, point to valid memory regions of the
+/// given sizes, `public_key` is writable, and they are not otherwise modified during the call.
+#[no_mangle]
+pub unsafe extern "C" fn pubhubs_public_key(
+    public_key: *mut u8,
+    private_key: *const u8,
+) -> PubhubsResult {
+    let sk = match unsafe { PrivateKey::from_ptr(private_key) } {
+        Some(sk) => sk,
+        None => return PubhubsResult::InvalidPrivateKey,
+    };
+
+    unsafe { sk.public_key().copy_to_ptr(public_key) }
+
+    PubhubsResult::Ok
+}
+
+/// Rerandomizes the 96-byte `triple`, see [Triple::rerandomize], and stores the result in
+/// `result`, which may coincide with `triple`.
+///
+/// # Safety
+/// See [pubhubs_public_key].
+#[no_mangle]
+pub unsafe extern "C" fn pubhubs_rerandomize(result: *mut u8, triple: *const u8) -> PubhubsResult {
+    let triple = match unsafe { Triple::from_ptr(triple) } {
+        Some(triple) => triple,
+        None => return PubhubsResult::InvalidTriple,
+    };
+
+    unsafe { triple.rerandomize().copy_to_ptr(result) }
+
+    PubhubsResult::Ok
+}
+
+/// Returns [PubhubsResult::Ok] when the 96-byte `triple` claims to be encrypted for the
+/// 32-byte `public_key`, and [PubhubsResult::WrongPublicKey] when it's not.
+///
+/// # Safety
+/// See [pubhubs_public_key].
+#[no_mangle]
+pub unsafe extern "C" fn pubhubs_triple_targets(
+    triple: *const u8,
+    public_key: *const u8,
+) -> PubhubsResult {
+    let pk = match unsafe { PublicKey::from_ptr(public_key) } {
+        Some(pk) => pk,
+        None => return PubhubsResult::InvalidPublicKey,
+    };
+
+    let triple = match unsafe { Triple::from_ptr(triple) } {
+        Some(triple) => triple,
+        None => return PubhubsResult::InvalidTriple,
+    };
+
+    if triple.targets(&pk) {
+        PubhubsResult::Ok
+    } else {
+        PubhubsResult::WrongPublicKey
+    }
+}
+
+/// Writes the lower-case hex encoding of the `len` bytes at `bytes` to the `2*len` bytes at
+/// `hex`.  No terminating zero is written.
+///
+/// # Safety
+/// See [pubhubs_public_key].
+#[no_mangle]
+pub unsafe extern "C" fn pubhubs_hex_encode(hex: *mut u8, bytes: *const u8, len: usize) {
+    let bytes = unsafe { core::slice::from_raw_parts(bytes, len) };
+    let hex = unsafe { core::slice::from_raw_parts_mut(hex, 2 * len) };
+
+    // Note: only fails when `hex` is too small, which it isn't
+    base16ct::lower::encode(bytes, hex).unwrap();
+}
+
+/// Decodes the `2*len` hex digits (of any case) at `hex` into the `len` bytes at `bytes`.
+///
+/// # Safety
+/// See [pubhubs_public_key].
+#[no_mangle]
+pub unsafe extern "C" fn pubhubs_hex_decode(
+    bytes: *mut u8,
+    hex: *const u8,
+    len: usize,
+) -> PubhubsResult {
+    let hex = unsafe { core::slice::from_raw_parts(hex, 2 * len) };
+    let bytes = unsafe { core::slice::from_raw_parts_mut(bytes, len) };
+
+    match base16ct::mixed::decode(hex, bytes) {
+        Ok(_) => PubhubsResult::Ok,
+        Err(_) => PubhubsResult::InvalidHex,
+    }
+}
+
+/// Maximal length of a short pseudonym, excluding the terminating zero.
+// Note: a literal, and not short_pseudonym::MAX_LEN, so that cbindgen picks it up.
+pub const PUBHUBS_SHORT_PSEUDONYM_MAX_LEN: usize = 33;
+const _: () = assert!(PUBHUBS_SHORT_PSEUDONYM_MAX_LEN == short_pseudonym::MAX_LEN);
+
+/// Writes the `n`th short pseudonym for the 32-byte (decrypted) `local_pseudonym` as
+/// zero-terminated string to `short_pseudonym`,
+/// see [short_pseudonym::short_pseudonym].
+///
+///   * `short_pseudonym` - pointer to a writable buffer of at least
+///     [PUBHUBS_SHORT_PSEUDONYM_MAX_LEN]+1 bytes
+///   * `local_pseudonym` - pointer to 32-byte buffer, e.g. the plaintext obtained via `decrypt`
+///   * `n` - at most 13;  the short pseudonym will have `2*n + 7` characters
+///
+/// # Safety
+/// See [pubhubs_public_key].
+#[no_mangle]
+pub unsafe extern "C" fn pubhubs_short_pseudonym(
+    short_pseudonym: *mut u8,
+    local_pseudonym: *const u8,
+    n: usize,
+) -> PubhubsResult {
+    let lp = unsafe { core::slice::from_raw_parts(local_pseudonym, 32) };
+
+    let sp = match short_pseudonym::short_pseudonym(&base16ct::lower::encode_string(lp), n) {
+        Some(sp) => sp,
+        None => return PubhubsResult::InvalidArgument,
+    };
+
+    let buf = unsafe { core::slice::from_raw_parts_mut(short_pseudonym, sp.len() + 1) };
+    buf[..sp.len()].copy_from_slice(sp.as_bytes());
+    buf[sp.len()] = 0;
+
+    PubhubsResult::Ok
+}
+
+/// Computes the check digit for the `len` base 17 digits at `digits`,
+/// see [short_pseudonym::checkdigit], and writes it to `checkdigit`.
+///
+/// # Safety
+/// See [pubhubs_public_key].  When `len` is zero, `digits` may be NULL.
+#[no_mangle]
+pub unsafe extern "C" fn pubhubs_checkdigit(
+    checkdigit: *mut u8,
+    digits: *const u8,
+    len: usize,
+) -> PubhubsResult {
+    let digits: &[u8] = if len == 0 {
+        &[]
+    } else {
+        unsafe { core::slice::from_raw_parts(digits, len) }
+    };
+
+    let Some(cd) = core::str::from_utf8(digits)
+        .ok()
+        .and_then(short_pseudonym::checkdigit)
+    else {
+        return PubhubsResult::InvalidArgument;
+    };
+
+    unsafe { *checkdigit = cd as u8 };
+
+    PubhubsResult::Ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header() {
+        let crate_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let path = crate_dir.join("include/pubhubs.h");
+
+        let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))
+            .expect("failed to load cbindgen.toml");
+
+        let mut generated: Vec<u8> = Vec::new();
+        cbindgen::Builder::new()
+            .with_src(crate_dir.join("src/common/abi.rs"))
+            .with_config(config)
+            .generate()
+            .expect("failed to generate C header")
+            .write(&mut generated);
+
+        if std::env::var_os("PUBHUBS_UPDATE_HEADER").is_some() {
+            std::fs::write(&path, &generated).unwrap();
+            return;
+        }
+
+        assert!(
+            std::fs::read(&path).unwrap() == generated,
+            "include/pubhubs.h is out of date; rerun this test with PUBHUBS_UPDATE_HEADER=1"
+        );
+    }
+
+    #[test]
+    fn abi() {
+        let sk = PrivateKey::random();
+        let mut pk = [0u8; 32];
+
+        assert_eq!(
+            unsafe { pubhubs_public_key(pk.as_mut_ptr(), sk.to_bytes().as_ptr()) },
+            PubhubsResult::Ok
+        );
+        assert_eq!(pk, sk.public_key().to_bytes());
+        assert_eq!(
+            unsafe { pubhubs_public_key(pk.as_mut_ptr(), [0xff; 32].as_ptr()) },
+            PubhubsResult::InvalidPrivateKey
+        );
+
+        let mut triple = sk.public_key().encrypt_random().to_bytes();
+        let original = triple;
+
+        assert_eq!(
+            unsafe { pubhubs_rerandomize(triple.as_mut_ptr(), triple.as_ptr()) },
+            PubhubsResult::Ok
+        );
+        assert_ne!(triple, original);
+        assert_eq!(
+            Triple::from_bytes(triple).unwrap().decrypt(&sk),
+            Triple::from_bytes(original).unwrap().decrypt(&sk)
+        );
+
+        assert_eq!(
+            unsafe { pubhubs_triple_targets(triple.as_ptr(), pk.as_ptr()) },
+            PubhubsResult::Ok
+        );
+        let other_pk = PrivateKey::random().public_key().to_bytes();
+        assert_eq!(
+            unsafe { pubhubs_triple_targets(triple.as_ptr(), other_pk.as_ptr()) },
+            PubhubsResult::WrongPublicKey
+        );
+
+        let mut hex = [0u8; 64];
+        let mut decoded = [0u8; 32];
+        unsafe { pubhubs_hex_encode(hex.as_mut_ptr(), pk.as_ptr(), 32) };
+        assert_eq!(&hex, sk.public_key().to_hex().as_bytes());
+        assert_eq!(
+            unsafe { pubhubs_hex_decode(decoded.as_mut_ptr(), hex.as_ptr(), 32) },
+            PubhubsResult::Ok
+        );
+        assert_eq!(decoded, pk);
+        hex[0] = b'x';
+        assert_eq!(
+            unsafe { pubhubs_hex_decode(decoded.as_mut_ptr(), hex.as_ptr(), 32) },
+            PubhubsResult::InvalidHex
+        );
+
+        let lp = base16ct::mixed::decode_vec("0123456789".repeat(6) + "abcd").unwrap();
+        let mut sp = [0xffu8; PUBHUBS_SHORT_PSEUDONYM_MAX_LEN + 1];
+        assert_eq!(
+            unsafe { pubhubs_short_pseudonym(sp.as_mut_ptr(), lp.as_ptr(), 1) },
+            PubhubsResult::Ok
+        );
+        assert_eq!(&sp[..10], b"012a-dbcd\0");
+        assert_eq!(
+            unsafe { pubhubs_short_pseudonym(sp.as_mut_ptr(), lp.as_ptr(), 14) },
+            PubhubsResult::InvalidArgument
+        );
+
+        let mut cd = 0u8;
+        assert_eq!(
+            unsafe { pubhubs_checkdigit(&mut cd, b"20".as_ptr(), 2) },
+            PubhubsResult::Ok
+        );
+        assert_eq!(cd, b'b');
+        assert_eq!(
+            unsafe { pubhubs_checkdigit(&mut cd, core::ptr::null(), 0) },
+            PubhubsResult::Ok
+        );
+        assert_eq!(cd, short_pseudonym::checkdigit("").unwrap() as u8);
+    }
+
+    #[test]
+    fn key_handles() {
+        let old_sk = PrivateKey::random();
+        let new_sk = PrivateKey::random();
+
+        let mut old_key: *mut PubhubsKey = core::ptr::null_mut();
+        let mut new_key: *mut PubhubsKey = core::ptr::null_mut();
+
+        unsafe {
+            assert_eq!(
+                pubhubs_key_load(&mut old_key, old_sk.to_bytes().as_ptr()) as u8,
+                DecryptResult::Ok as u8
+            );
+            assert_eq!(
+                pubhubs_key_load(&mut new_key, new_sk.to_bytes().as_ptr()) as u8,
+                DecryptResult::Ok as u8
+            );
+            assert_eq!(
+                pubhubs_key_load(&mut new_key, [0xff; 32].as_ptr()) as u8,
+                DecryptResult::InvalidPrivateKey as u8
+            );
+        }
+
+        let pt = crate::elgamal::random_point();
+        let ct = old_sk.public_key().encrypt(pt).to_bytes();
+        let mut result = [0u8; 32];
+
+        unsafe {
+            assert_eq!(
+                pubhubs_key_decrypt(result.as_mut_ptr(), ct.as_ptr(), old_key) as u8,
+                DecryptResult::Ok as u8
+            );
+            assert_eq!(result, pt.to_bytes());
+            assert_eq!(
+                pubhubs_key_decrypt(result.as_mut_ptr(), ct.as_ptr(), new_key) as u8,
+                DecryptResult::WrongPublicKey as u8
+            );
+        }
+
+        // decrypt using several keys from several threads
+        let keys: [usize; 2] = [new_key as usize, old_key as usize];
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let keys = keys.map(|k| k as *const PubhubsKey);
+                    let mut result = [0u8; 32];
+                    let mut used: usize = 0;
+
+                    unsafe {
+                        assert_eq!(
+                            pubhubs_keys_decrypt(
+                                result.as_mut_ptr(),
+                                ct.as_ptr(),
+                                keys.as_ptr(),
+                                keys.len(),
+                                &mut used
+                            ) as u8,
+                            DecryptResult::Ok as u8
+                        );
+                    }
+                    assert_eq!(result, pt.to_bytes());
+                    assert_eq!(used, 1);
+                });
+            }
+        });
+
+        // no keys at all
+        let mut result = [0u8; 32];
+        let mut used: usize = 0;
+        assert_eq!(
+            unsafe {
+                pubhubs_keys_decrypt(
+                    result.as_mut_ptr(),
+                    ct.as_ptr(),
+                    core::ptr::null(),
+                    0,
+                    &mut used,
+                )
+            } as u8,
+            DecryptResult::WrongPublicKey as u8
+        );
+
+        unsafe {
+            pubhubs_key_free(old_key);
+            pubhubs_key_free(new_key);
+            pubhubs_key_free(core::ptr::null_mut());
+        }
+    }
+}
@@ -12,6 +12,7 @@ use curve25519_dalek::{
 /// not authenticated in any way.  This means that anyone intercepting a triple may
 /// modify the public key without detection (but this does not cause the
 /// triple to be decryptable to the same plaintext by another public key.)
+/// To detect such tampering, use [signed::SignedTriple] - see [Triple::sign].
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Triple {
     /// Ephemeral key
@@ -39,13 +40,26 @@ impl Triple {
     /// garbled, using [Self::rerandomize].
     ///
     pub fn decrypt_and_check_pk(self, sk: &PrivateKey) -> Option<RistrettoPoint> {
-        if self.pk == B * &sk.scalar {
+        if self.targets(&sk.public_key()) {
             Some(self.decrypt(sk))
         } else {
             None
         }
     }
 
+    /// Signs this triple using `signing_key`, so that its receiver can check (using
+    /// [signed::SignedTriple::open]) that it was not tampered with.
+    pub fn sign(self, signing_key: &PrivateKey) -> signed::SignedTriple {
+        signed::SignedTriple::new(self, signing_key)
+    }
+
+    /// Returns whether this triple claims to be encrypted for the given public key.
+    ///
+    /// **Warning** See [Self::decrypt_and_check_pk].
+    pub fn targets(&self, pk: &PublicKey) -> bool {
+        self.pk == pk.point
+    }
+
     /// Changes the public key of this triple, likely resulting in garbage down the road.
     ///
     /// Used for demonstration purposes.
@@ -119,10 +133,60 @@ impl Triple {
     /// If you only need to specify `s` and `k`, use `triple.rsk_with_s(s).and_k(k)` instead.
     pub fn rsk(self, params: impl rsk::Params) -> Triple {
         let r: Scalar = params.r();
+        self.rsk_with_r(&params, &r)
+    }
+
+    /// Like [Self::rsk], but also returns a [proof::RskProof] that the resulting triple
+    /// was obtained from `self` by the `rsk` operation with the factors `s` and `k`
+    /// committed to by [proof::FactorCommitments::from_params].
+    ///
+    /// ```
+    /// use pubhubs::elgamal::{PrivateKey, random_scalar, rsk, proof::FactorCommitments};
+    ///
+    /// let sk = PrivateKey::random();
+    /// let s = random_scalar();
+    /// let k = random_scalar();
+    ///
+    /// let input = sk.public_key().encrypt_random();
+    /// let (output, proof) = input.clone().rsk_with_proof(rsk::SAndK::new(&s, &k));
+    ///
+    /// let commitments = FactorCommitments::new(&s, &k);
+    /// assert!(proof.verify(&input, &output, &commitments));
+    ///
+    /// // the proof is bound to the input, output and committed factors
+    /// assert!(!proof.verify(&input, &output.clone().rerandomize(), &commitments));
+    /// assert!(!proof.verify(&input.clone().rerandomize(), &output, &commitments));
+    /// assert!(!proof.verify(&input, &output, &FactorCommitments::new(&k, &s)));
+    /// ```
+    pub fn rsk_with_proof(self, params: impl rsk::Params) -> (Triple, proof::RskProof) {
+        let r: Scalar = params.r();
+        let result = self.clone().rsk_with_r(&params, &r);
+        let proof = proof::RskProof::create(&self, &result, &params, &r);
+        (result, proof)
+    }
+
+    /// Multiplies the target public/private key by `k`, and rerandomizes, but leaves the
+    /// plaintext unaltered.  Equals [Self::rsk] with `s` equal to one.
+    ///
+    /// Use this instead of [Self::rsk] to transcrypt triples encrypting [data].
+    pub fn rekey(self, k: &Scalar) -> Triple {
+        self.rsk(rsk::SAndK::new(&Scalar::ONE, k))
+    }
+
+    /// Decrypts the triple using `sk` (see [Self::decrypt_and_check_pk]), and decodes
+    /// the resulting plaintext as data (see [data::decode]).
+    ///
+    /// Returns `None` when the triple was not encrypted for `sk` or does not contain data.
+    pub fn decrypt_data(self, sk: &PrivateKey) -> Option<Vec<u8>> {
+        data::decode(&self.decrypt_and_check_pk(sk)?)
+    }
+
+    /// Implementation of [Self::rsk] given the rerandomization scalar `r`.
+    fn rsk_with_r(self, params: &impl rsk::Params, r: &Scalar) -> Triple {
         let kpk = self.pk * params.k();
 
         Triple {
-            ek: params.s_over_k() * self.ek + &r * B,
+            ek: params.s_over_k() * self.ek + r * B,
             ct: params.s() * self.ct + r * kpk,
             pk: kpk,
         }
@@ -139,6 +203,12 @@ pub mod rsk {
         k: &'k Scalar,
     }
 
+    impl<'s, 'k> SAndK<'s, 'k> {
+        pub fn new(s: &'s Scalar, k: &'k Scalar) -> Self {
+            SAndK { s, k }
+        }
+    }
+
     impl<'s, 'k> Params for SAndK<'s, 'k> {
         fn s(&self) -> &Scalar {
             self.s
@@ -149,6 +219,28 @@ pub mod rsk {
         }
     }
 
+    impl<P: Params + ?Sized> Params for &P {
+        fn s(&self) -> &Scalar {
+            (**self).s()
+        }
+
+        fn k(&self) -> &Scalar {
+            (**self).k()
+        }
+
+        fn k_inv(&self) -> Scalar {
+            (**self).k_inv()
+        }
+
+        fn s_over_k(&self) -> Scalar {
+            (**self).s_over_k()
+        }
+
+        fn r(&self) -> Scalar {
+            (**self).r()
+        }
+    }
+
     /// The result of [Triple::rsk_with_s]. You should call [WithS::and_k] on it.
     pub struct WithS<'a> {
         pub(crate) t: Triple,
@@ -188,6 +280,562 @@ pub mod rsk {
     }
 }
 
+/// Non-interactive zero-knowledge proofs concerning [Triple]s.
+///
+/// The proofs are Chaum-Pedersen style proofs of knowledge of scalars satisfying a set of linear
+/// relations between Ristretto points, made non-interactive using the Fiat-Shamir heuristic.
+pub mod proof {
+    use super::*;
+
+    use curve25519_dalek::traits::VartimeMultiscalarMul as _;
+    use sha2::Digest as _;
+
+    /// Public commitments `S = sB` and `K = kB` to the factors `s` and `k` used in [Triple::rsk].
+    ///
+    /// These are published (once) by the party performing the `rsk` operation, so that the
+    /// [RskProof]s it produces can be checked by others.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct FactorCommitments {
+        /// `sB`
+        s: RistrettoPoint,
+        /// `kB`
+        k: RistrettoPoint,
+    }
+
+    impl FactorCommitments {
+        pub fn new(s: &Scalar, k: &Scalar) -> Self {
+            FactorCommitments { s: s * B, k: k * B }
+        }
+
+        /// Returns the commitments to the factors `params.s()` and `params.k()`.
+        pub fn from_params(params: &impl rsk::Params) -> Self {
+            Self::new(params.s(), params.k())
+        }
+    }
+
+    /// Proof that `output = input.rsk(params)` for some `params` whose factors `s` and `k` are
+    /// committed to by given [FactorCommitments] - see [Triple::rsk_with_proof].
+    ///
+    /// Writing `input = (ek, ct, pk)`, `output = (ek', ct', pk')`, `S = sB`, `K = kB`,
+    /// and `u = s/k`, this is a proof of knowledge of scalars `k`, `s`, `u` and `r` with
+    ///
+    /// ```text
+    ///   K   = kB,        pk'  = k pk,
+    ///   S   = sB,        S    = uK,
+    ///   ek' = u ek + rB, ct'  = s ct + r pk'.
+    /// ```
+    ///
+    /// Note that `S = uK` guarantees that `u = s/k`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct RskProof {
+        /// Fiat-Shamir challenge
+        c: Scalar,
+        z_k: Scalar,
+        z_s: Scalar,
+        z_u: Scalar,
+        z_r: Scalar,
+    }
+
+    /// Domain separation tag for the Fiat-Shamir challenge of an [RskProof].
+    const RSK_PROOF_DST: &[u8] = b"pubhubs elgamal rsk proof v1";
+
+    impl RskProof {
+        /// Creates proof that `output` equals `input.rsk_with_r(params, r)`.
+        pub(super) fn create(
+            input: &Triple,
+            output: &Triple,
+            params: &impl rsk::Params,
+            r: &Scalar,
+        ) -> Self {
+            let commitments = FactorCommitments::from_params(params);
+
+            let k = params.k();
+            let s = params.s();
+            let u = params.s_over_k();
+
+            let a_k = random_scalar();
+            let a_s = random_scalar();
+            let a_u = random_scalar();
+            let a_r = random_scalar();
+
+            let c = Self::challenge(
+                input,
+                output,
+                &commitments,
+                &[
+                    &a_k * B,
+                    a_k * input.pk,
+                    &a_s * B,
+                    a_u * commitments.k,
+                    a_u * input.ek + &a_r * B,
+                    a_s * input.ct + a_r * output.pk,
+                ],
+            );
+
+            RskProof {
+                c,
+                z_k: a_k + c * k,
+                z_s: a_s + c * s,
+                z_u: a_u + c * u,
+                z_r: a_r + c * r,
+            }
+        }
+
+        /// Checks that `output` was obtained from `input` by [Triple::rsk] using factors
+        /// committed to in `commitments`.
+        pub fn verify(
+            &self,
+            input: &Triple,
+            output: &Triple,
+            commitments: &FactorCommitments,
+        ) -> bool {
+            let c = self.c;
+            let bp = curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+
+            let msm = |scalars: &[Scalar], points: &[RistrettoPoint]| {
+                RistrettoPoint::vartime_multiscalar_mul(scalars, points)
+            };
+
+            let announcements = [
+                msm(&[self.z_k, -c], &[bp, commitments.k]),
+                msm(&[self.z_k, -c], &[input.pk, output.pk]),
+                msm(&[self.z_s, -c], &[bp, commitments.s]),
+                msm(&[self.z_u, -c], &[commitments.k, commitments.s]),
+                msm(&[self.z_u, self.z_r, -c], &[input.ek, bp, output.ek]),
+                msm(&[self.z_s, self.z_r, -c], &[input.ct, output.pk, output.ct]),
+            ];
+
+            Self::challenge(input, output, commitments, &announcements) == c
+        }
+
+        /// Computes the Fiat-Shamir challenge from the statement and the announcements.
+        fn challenge(
+            input: &Triple,
+            output: &Triple,
+            commitments: &FactorCommitments,
+            announcements: &[RistrettoPoint; 6],
+        ) -> Scalar {
+            let mut h = sha2::Sha512::new()
+                .chain_update(RSK_PROOF_DST)
+                .chain_update(commitments.to_bytes())
+                .chain_update(input.to_bytes())
+                .chain_update(output.to_bytes());
+
+            for a in announcements {
+                h.update(a.to_bytes());
+            }
+
+            Scalar::from_hash(h)
+        }
+    }
+
+    impl Encoding<64> for FactorCommitments {
+        fn from_bytes(bytes: [u8; 64]) -> Option<FactorCommitments> {
+            Some(FactorCommitments {
+                s: RistrettoPoint::from_slice(&bytes[..32])?,
+                k: RistrettoPoint::from_slice(&bytes[32..])?,
+            })
+        }
+
+        fn to_bytes(&self) -> [u8; 64] {
+            let mut result = [0u8; 64];
+
+            self.s.copy_to_slice(&mut result[..32]).unwrap();
+            self.k.copy_to_slice(&mut result[32..]).unwrap();
+
+            result
+        }
+    }
+
+    impl Encoding<160> for RskProof {
+        fn from_bytes(bytes: [u8; 160]) -> Option<RskProof> {
+            Some(RskProof {
+                c: Scalar::from_slice(&bytes[..32])?,
+                z_k: Scalar::from_slice(&bytes[32..64])?,
+                z_s: Scalar::from_slice(&bytes[64..96])?,
+                z_u: Scalar::from_slice(&bytes[96..128])?,
+                z_r: Scalar::from_slice(&bytes[128..])?,
+            })
+        }
+
+        fn to_bytes(&self) -> [u8; 160] {
+            let mut result = [0u8; 160];
+
+            for (i, scalar) in [&self.c, &self.z_k, &self.z_s, &self.z_u, &self.z_r]
+                .into_iter()
+                .enumerate()
+            {
+                scalar
+                    .copy_to_slice(&mut result[32 * i..32 * (i + 1)])
+                    .unwrap();
+            }
+
+            result
+        }
+    }
+}
+
+/// Reversible encoding of short byte strings, like attribute values, as Ristretto points,
+/// so that they can be encrypted using [PublicKey::encrypt_data], and decrypted using
+/// [Triple::decrypt_data].
+///
+/// Since [Triple::rsk] multiplies the plaintext by `s`, the encoded data is destroyed
+/// by pseudonymisation;  transcrypt such triples using [Triple::rekey] instead.
+///
+/// ```
+/// use pubhubs::elgamal::data;
+///
+/// let point = data::encode(b"over 18").unwrap();
+/// assert_eq!(data::decode(&point).unwrap(), b"over 18");
+///
+/// assert!(data::encode(&[0u8; data::MAX_LEN + 1]).is_none());
+/// assert!(data::decode(&pubhubs::elgamal::random_point()).is_none());
+/// ```
+pub mod data {
+    use super::*;
+
+    /// Maximal number of bytes that can be encoded into a single Ristretto point.
+    pub const MAX_LEN: usize = 16;
+
+    /// The data is stored in the 32-byte compressed Ristretto encoding of the point thusly:
+    ///
+    /// ```text
+    ///   [0]       0
+    ///   [1..17]   data, padded with zeroes
+    ///   [17]      length of the data
+    ///   [18..20]  counter, increased until the encoding is a valid Ristretto point
+    ///   [20..32]  0
+    /// ```
+    ///
+    /// About one in four counters yields a valid encoding.  The zero bytes make it
+    /// very unlikely that a random point decodes to data.
+    const DATA: core::ops::Range<usize> = 1..1 + MAX_LEN;
+    const LEN: usize = 1 + MAX_LEN;
+    const COUNTER: core::ops::Range<usize> = LEN + 1..LEN + 3;
+    const ZEROES: core::ops::Range<usize> = COUNTER.end..32;
+
+    /// Encodes `data` as Ristretto point.  Returns `None` when `data` is longer than [MAX_LEN].
+    ///
+    /// The encoding is deterministic, so encrypt the point before sharing it.
+    pub fn encode(data: &[u8]) -> Option<RistrettoPoint> {
+        if data.len() > MAX_LEN {
+            return None;
+        }
+
+        let mut bytes = [0u8; 32];
+        bytes[DATA.start..DATA.start + data.len()].copy_from_slice(data);
+        bytes[LEN] = data.len() as u8;
+
+        for counter in 0..=u16::MAX {
+            bytes[COUNTER].copy_from_slice(&counter.to_le_bytes());
+
+            if let Some(point) = RistrettoPoint::from_bytes(bytes) {
+                return Some(point);
+            }
+        }
+
+        // The probability of getting here is about (3/4)^65536.
+        None
+    }
+
+    /// Decodes the data from a Ristretto point obtained via [encode].  Returns `None` when
+    /// `point` does not encode data.
+    pub fn decode(point: &RistrettoPoint) -> Option<Vec<u8>> {
+        let bytes = point.to_bytes();
+        let len = bytes[LEN] as usize;
+
+        if bytes[0] != 0
+            || len > MAX_LEN
+            || bytes[DATA.start + len..DATA.end].iter().any(|b| *b != 0)
+            || bytes[ZEROES].iter().any(|b| *b != 0)
+        {
+            return None;
+        }
+
+        Some(bytes[DATA.start..DATA.start + len].to_vec())
+    }
+}
+
+/// [Triple]s whose public key can't be tampered with.
+///
+/// The party that produces a triple (for example the transcryptor after [Triple::rsk]) can sign
+/// `(ek, ct, pk)` using a Schnorr signature, yielding a [SignedTriple].
+/// A receiver that knows the signer's public key can then reject tampered triples
+/// instead of silently decrypting them to garbage.
+///
+/// Since [Triple::rerandomize] and [Triple::rsk] change the triple, they invalidate the
+/// signature;  only the last party to transform the triple should sign it.
+pub mod signed {
+    use super::*;
+
+    use sha2::Digest as _;
+
+    /// A [Triple] signed by its producer - see [Triple::sign].
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct SignedTriple {
+        triple: Triple,
+        /// Fiat-Shamir challenge
+        c: Scalar,
+        z: Scalar,
+    }
+
+    /// Domain separation tag for the Fiat-Shamir challenge of a [SignedTriple].
+    const SIGNATURE_DST: &[u8] = b"pubhubs elgamal signed triple v1";
+
+    impl SignedTriple {
+        /// Signs `triple` using `signing_key`.
+        ///
+        /// **Warning** Do not use the same key for signing as for decryption.
+        pub fn new(triple: Triple, signing_key: &PrivateKey) -> Self {
+            let a = random_scalar();
+            let c = Self::challenge(&triple, &signing_key.public_key(), &(&a * B));
+
+            SignedTriple {
+                triple,
+                c,
+                z: a + c * signing_key.scalar,
+            }
+        }
+
+        /// Checks that this triple was signed by the holder of the private key
+        /// belonging to `signer`.
+        pub fn verify(&self, signer: &PublicKey) -> bool {
+            let announcement = RistrettoPoint::vartime_double_scalar_mul_basepoint(
+                &-self.c,
+                &signer.point,
+                &self.z,
+            );
+
+            Self::challenge(&self.triple, signer, &announcement) == self.c
+        }
+
+        /// Returns the underlying triple if it was signed by `signer`, and `None` otherwise.
+        pub fn open(self, signer: &PublicKey) -> Option<Triple> {
+            self.verify(signer).then_some(self.triple)
+        }
+
+        /// Decrypts the triple using `sk` after checking that it was signed by `signer` and
+        /// that it was encrypted for (the public key belonging to) `sk`.
+        pub fn decrypt(self, signer: &PublicKey, sk: &PrivateKey) -> Option<RistrettoPoint> {
+            self.open(signer)?.decrypt_and_check_pk(sk)
+        }
+
+        /// Returns the underlying triple, without checking the signature.
+        pub fn unverified_triple(&self) -> &Triple {
+            &self.triple
+        }
+
+        /// Computes the Fiat-Shamir challenge.
+        fn challenge(triple: &Triple, signer: &PublicKey, announcement: &RistrettoPoint) -> Scalar {
+            Scalar::from_hash(
+                sha2::Sha512::new()
+                    .chain_update(SIGNATURE_DST)
+                    .chain_update(signer.to_bytes())
+                    .chain_update(triple.to_bytes())
+                    .chain_update(announcement.to_bytes()),
+            )
+        }
+    }
+
+    impl Encoding<160> for SignedTriple {
+        fn from_bytes(bytes: [u8; 160]) -> Option<SignedTriple> {
+            Some(SignedTriple {
+                triple: Triple::from_slice(&bytes[..96])?,
+                c: Scalar::from_slice(&bytes[96..128])?,
+                z: Scalar::from_slice(&bytes[128..])?,
+            })
+        }
+
+        fn to_bytes(&self) -> [u8; 160] {
+            let mut result = [0u8; 160];
+
+            self.triple.copy_to_slice(&mut result[..96]).unwrap();
+            self.c.copy_to_slice(&mut result[96..128]).unwrap();
+            self.z.copy_to_slice(&mut result[128..]).unwrap();
+
+            result
+        }
+    }
+}
+
+/// Operations on many [Triple]s at once.
+///
+/// The results are identical to those of the corresponding single-item operations
+/// (given the same random scalars), but they are computed faster by
+///
+///  - computing per-batch quantities (like `s/k` for [Triple::rsk]) only once;
+///  - using precomputed tables (see [PrecomputedPublicKey]) for public keys that are shared
+///    by several triples;
+///  - using multiscalar multiplication for the remaining variable-base multiplications.
+pub mod batch {
+    use super::*;
+
+    use curve25519_dalek::ristretto::RistrettoBasepointTable;
+    use curve25519_dalek::traits::MultiscalarMul as _;
+
+    /// A [PublicKey] together with a precomputed table to speed up multiplication of the public
+    /// key with a scalar.
+    ///
+    /// Creating the table is costly (comparable to a handful of scalar multiplications),
+    /// so only use this when encrypting or rerandomizing many triples for the same public key.
+    pub struct PrecomputedPublicKey {
+        pk: PublicKey,
+        table: RistrettoBasepointTable,
+    }
+
+    impl PrecomputedPublicKey {
+        pub fn new(pk: PublicKey) -> Self {
+            PrecomputedPublicKey {
+                table: RistrettoBasepointTable::create(&pk.point),
+                pk,
+            }
+        }
+
+        pub fn public_key(&self) -> &PublicKey {
+            &self.pk
+        }
+
+        /// Like [PublicKey::encrypt].
+        pub fn encrypt(&self, plaintext: RistrettoPoint) -> Triple {
+            self.encrypt_with_random(random_scalar(), plaintext)
+        }
+
+        /// Like [PublicKey::encrypt_with_random].
+        pub fn encrypt_with_random(&self, r: Scalar, plaintext: RistrettoPoint) -> Triple {
+            Triple {
+                ek: &r * B,
+                ct: plaintext + &r * &self.table,
+                pk: self.pk.point,
+            }
+        }
+
+        /// Like [Triple::rerandomize_with_random], but `triple.pk` must equal this public key.
+        fn rerandomize_with_random(&self, triple: Triple, r: &Scalar) -> Triple {
+            debug_assert!(triple.pk == self.pk.point);
+
+            Triple {
+                ek: triple.ek + r * B,
+                ct: triple.ct + r * &self.table,
+                pk: triple.pk,
+            }
+        }
+    }
+
+    /// Keeps [PrecomputedPublicKey]s for the first few distinct public keys encountered
+    /// in a batch.  In a typical batch all triples are encrypted for the same public key.
+    ///
+    /// The tables are indexed by the public key of the input triple, but may be for a
+    /// public key derived from it, like `k * pk` in the case of [rsk].
+    struct Tables {
+        entries: Vec<(RistrettoPoint, PrecomputedPublicKey)>,
+    }
+
+    impl Tables {
+        /// Maximal number of tables kept; triples for other public keys are processed without
+        /// precomputed table.
+        const MAX: usize = 8;
+
+        fn new() -> Self {
+            Tables {
+                entries: Vec::new(),
+            }
+        }
+
+        /// Returns the table for `derive(pk)`, computing it if needed and possible.
+        fn get(
+            &mut self,
+            pk: &RistrettoPoint,
+            derive: impl FnOnce(&RistrettoPoint) -> RistrettoPoint,
+        ) -> Option<&PrecomputedPublicKey> {
+            match self.entries.iter().position(|(source, _)| source == pk) {
+                Some(i) => Some(&self.entries[i].1),
+                None => {
+                    if self.entries.len() >= Self::MAX {
+                        return None;
+                    }
+                    let target = PublicKey { point: derive(pk) };
+                    self.entries.push((*pk, PrecomputedPublicKey::new(target)));
+                    self.entries.last().map(|(_, ppk)| ppk)
+                }
+            }
+        }
+    }
+
+    /// Encrypts the given `plaintexts` for `pk`.
+    pub fn encrypt(pk: &PublicKey, plaintexts: &[RistrettoPoint]) -> Vec<Triple> {
+        let ppk = PrecomputedPublicKey::new(pk.clone());
+
+        plaintexts.iter().map(|m| ppk.encrypt(*m)).collect()
+    }
+
+    /// Decrypts the given `triples` using `sk`, see [Triple::decrypt].
+    ///
+    /// Since each decryption involves a different point, there is not much to gain here,
+    /// but it's included for completeness.
+    pub fn decrypt(triples: &[Triple], sk: &PrivateKey) -> Vec<RistrettoPoint> {
+        triples.iter().map(|t| t.ct - sk.scalar * t.ek).collect()
+    }
+
+    /// Rerandomizes the given `triples` in place, see [Triple::rerandomize].
+    pub fn rerandomize(triples: &mut [Triple]) {
+        let rs: Vec<Scalar> = triples.iter().map(|_| random_scalar()).collect();
+        rerandomize_with_random(triples, &rs)
+    }
+
+    /// Like [rerandomize], but you can specify the random scalars used - which you shouldn't
+    /// except to make deterministic tests.
+    ///
+    /// Panics when `triples` and `rs` have different lengths.
+    pub fn rerandomize_with_random(triples: &mut [Triple], rs: &[Scalar]) {
+        assert_eq!(triples.len(), rs.len());
+
+        let mut tables = Tables::new();
+
+        for (t, r) in triples.iter_mut().zip(rs) {
+            let old = t.clone();
+
+            *t = match tables.get(&old.pk, |pk| *pk) {
+                Some(ppk) => ppk.rerandomize_with_random(old, r),
+                None => old.rerandomize_with_random(*r),
+            };
+        }
+    }
+
+    /// Applies [Triple::rsk] with the given `params` to all `triples` in place.
+    ///
+    /// Calls `params.r()` once for each triple.
+    pub fn rsk(triples: &mut [Triple], params: impl rsk::Params) {
+        let s: &Scalar = params.s();
+        let k: &Scalar = params.k();
+        let s_over_k: Scalar = params.s_over_k();
+
+        let mut tables = Tables::new();
+
+        for t in triples.iter_mut() {
+            let r: Scalar = params.r();
+
+            let ek = s_over_k * t.ek + &r * B;
+
+            *t = match tables.get(&t.pk, |pk| pk * k) {
+                Some(kppk) => Triple {
+                    ek,
+                    ct: s * t.ct + &r * &kppk.table,
+                    pk: kppk.pk.point,
+                },
+                None => {
+                    let kpk = t.pk * k;
+
+                    Triple {
+                        ek,
+                        ct: RistrettoPoint::multiscalar_mul([s, &r], [t.ct, kpk]),
+                        pk: kpk,
+                    }
+                }
+            };
+        }
+    }
+}
+
 /// `osrng!()` is an abbreviation for `&mut rand_07::rngs::OsRng` the rng used by this module.
 macro_rules! osrng {
     () => {
@@ -209,12 +857,38 @@ pub fn random_scalar() -> Scalar {
 }
 
 /// Private key - load using [PrivateKey::from_hex] or generate with [PrivateKey::random].
-#[derive(Clone, PartialEq, Eq)]
+///
+/// The underlying scalar is zeroized on drop, and is compared in constant time.
+/// Prefer sharing a private key using [std::sync::Arc] over cloning it.
+#[derive(Clone)]
 pub struct PrivateKey {
     /// underlying scalar
     scalar: Scalar,
 }
 
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.scalar);
+    }
+}
+
+impl zeroize::ZeroizeOnDrop for PrivateKey {}
+
+impl PartialEq for PrivateKey {
+    fn eq(&self, other: &Self) -> bool {
+        subtle::ConstantTimeEq::ct_eq(&self.scalar, &other.scalar).into()
+    }
+}
+
+impl Eq for PrivateKey {}
+
+/// Does not reveal the underlying scalar.
+impl core::fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PrivateKey").finish_non_exhaustive()
+    }
+}
+
 impl PrivateKey {
     /// Returns reference to underlying scalar.
     pub fn as_scalar(&self) -> &Scalar {
@@ -241,7 +915,7 @@ impl From<Scalar> for PrivateKey {
 }
 
 /// Public key - obtained using [PublicKey::from_hex] or [PrivateKey::public_key].
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PublicKey {
     point: RistrettoPoint,
 }
@@ -273,6 +947,25 @@ impl PublicKey {
         }
     }
 
+    /// Encrypts at most [data::MAX_LEN] bytes of `data` for this public key.  Returns `None` when
+    /// `data` is too long.
+    ///
+    /// ```
+    /// use pubhubs::elgamal::{PrivateKey, random_scalar};
+    ///
+    /// let sk = PrivateKey::random();
+    /// let k = random_scalar();
+    ///
+    /// let triple = sk.public_key().encrypt_data(b"age >= 18").unwrap();
+    /// let rekeyed = triple.rekey(&k);
+    ///
+    /// let rekeyed_sk = PrivateKey::from(k * sk.as_scalar());
+    /// assert_eq!(rekeyed.decrypt_data(&rekeyed_sk).unwrap(), b"age >= 18");
+    /// ```
+    pub fn encrypt_data(&self, data: &[u8]) -> Option<Triple> {
+        Some(self.encrypt(data::encode(data)?))
+    }
+
     /// Effectively encrypts a random plaintext for this public key.
     ///
     /// Instead of picking random Ristretto point M and random scalar r and computing
@@ -435,55 +1128,244 @@ impl Encoding<96> for Triple {
     }
 }
 
-/// Application binary interface
-#[cfg(feature = "abi")]
-pub mod abi {
+/// Implements [serde::Serialize] and [serde::Deserialize] for a type implementing [Encoding]
+/// by (de)serializing its encoding using the serde byte array data type.
+///
+/// To get a hex or base64 string instead, wrap the type in [crate::misc::serde_ext::B16]
+/// or [crate::misc::serde_ext::B64UU], as with the other keys.
+#[cfg(feature = "bin")]
+macro_rules! impl_serde_via_encoding {
+    ($t:ty, $n:literal, $expecting:literal) => {
+        impl serde::Serialize for $t {
+            fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                s.serialize_bytes(&self.to_bytes())
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $t {
+            fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+                let bytes = crate::misc::serde_ext::ByteArray::<$n>::deserialize(d)?;
+
+                <$t>::from_bytes(*bytes).ok_or_else(|| {
+                    <D::Error as serde::de::Error>::invalid_value(
+                        serde::de::Unexpected::Bytes(&*bytes),
+                        &$expecting,
+                    )
+                })
+            }
+        }
+    };
+}
+
+#[cfg(feature = "bin")]
+impl_serde_via_encoding!(PrivateKey, 32, "the canonical encoding of a scalar");
+#[cfg(feature = "bin")]
+impl_serde_via_encoding!(PublicKey, 32, "the encoding of a Ristretto point");
+#[cfg(feature = "bin")]
+impl_serde_via_encoding!(Triple, 96, "the encoding of three Ristretto points");
+#[cfg(feature = "bin")]
+impl_serde_via_encoding!(
+    signed::SignedTriple,
+    160,
+    "the encoding of three Ristretto points and two scalars"
+);
+#[cfg(feature = "bin")]
+impl_serde_via_encoding!(
+    proof::FactorCommitments,
+    64,
+    "the encoding of two Ristretto points"
+);
+#[cfg(feature = "bin")]
+impl_serde_via_encoding!(proof::RskProof, 160, "the encoding of five scalars");
+
+#[cfg(test)]
+mod tests {
     use super::*;
 
-    /// Decrypts the given `ciphertext` using the given `private_key` and stores the result in
-    /// `plaintext`.
-    ///
-    ///   * `plaintext` - pointer to a writable 32-byte buffer
-    ///   * `ciperhtext` - pointer to a 96-byte buffer holding the result of [Triple::to_bytes]
-    ///   * `private_key` - pointer to a 32-byte buffer holding the result of [Scalar::to_bytes]
-    ///
-    /// # Safety
-    /// The caller must make sure the pointers are aligned, point to valid memory regions,
-    /// are readable, and plaintext is writable, and are not otherwise modified.
-    ///
-    /// For more details, see [core::slice::from_raw_parts] and [core::slice::from_raw_parts_mut].
-    #[no_mangle]
-    pub unsafe extern "C" fn decrypt(
-        plaintext: *mut u8,
-        ciphertext: *const u8,
-        private_key: *const u8,
-    ) -> DecryptResult {
-        let pk = match unsafe { PrivateKey::from_ptr(private_key) } {
-            Some(pk) => pk,
-            None => return DecryptResult::InvalidPrivateKey,
-        };
+    /// [rsk::Params] with fixed rerandomization scalar
+    struct FixedR {
+        s: Scalar,
+        k: Scalar,
+        r: Scalar,
+    }
 
-        let ct = match unsafe { Triple::from_ptr(ciphertext) } {
-            Some(ct) => ct,
-            None => return DecryptResult::InvalidTriple,
+    impl rsk::Params for FixedR {
+        fn s(&self) -> &Scalar {
+            &self.s
+        }
+
+        fn k(&self) -> &Scalar {
+            &self.k
+        }
+
+        fn r(&self) -> Scalar {
+            self.r
+        }
+    }
+
+    #[test]
+    fn rsk_proof() {
+        let params = FixedR {
+            s: random_scalar(),
+            k: random_scalar(),
+            r: random_scalar(),
         };
+        let commitments = proof::FactorCommitments::from_params(&params);
+
+        let input = PrivateKey::random().public_key().encrypt_random();
+        let (output, proof) = input.clone().rsk_with_proof(&params);
+
+        assert_eq!(output, input.clone().rsk(&params));
+        assert!(proof.verify(&input, &output, &commitments));
+
+        // encoding roundtrips
+        let proof = proof::RskProof::from_hex(&proof.to_hex()).unwrap();
+        let commitments = proof::FactorCommitments::from_hex(&commitments.to_hex()).unwrap();
+        assert!(proof.verify(&input, &output, &commitments));
+
+        // the proof does not verify for a different output with the same plaintext
+        assert!(!proof.verify(&input, &output.rerandomize(), &commitments));
+    }
+
+    #[test]
+    fn batch_equals_single() {
+        // two public keys shared by many triples, and a few more than fit in batch::Tables
+        let pks: Vec<PublicKey> = (0..12).map(|_| PrivateKey::random().public_key()).collect();
+        let triples: Vec<Triple> = (0..40)
+            .map(|i| pks[if i < 20 { i % 2 } else { i % pks.len() }].encrypt_random())
+            .collect();
+
+        // rerandomize
+        let rs: Vec<Scalar> = triples.iter().map(|_| random_scalar()).collect();
+        let mut batched = triples.clone();
+        batch::rerandomize_with_random(&mut batched, &rs);
+
+        for ((t, r), b) in triples.iter().zip(&rs).zip(&batched) {
+            assert_eq!(
+                t.clone().rerandomize_with_random(*r).to_bytes(),
+                b.to_bytes()
+            );
+        }
 
-        let pt = match ct.decrypt_and_check_pk(&pk) {
-            Some(pt) => pt,
-            None => return DecryptResult::WrongPublicKey,
+        // rsk
+        let params = FixedR {
+            s: random_scalar(),
+            k: random_scalar(),
+            r: random_scalar(),
         };
+        let mut batched = triples.clone();
+        batch::rsk(&mut batched, &params);
+
+        for (t, b) in triples.iter().zip(&batched) {
+            assert_eq!(t.clone().rsk(&params).to_bytes(), b.to_bytes());
+        }
+
+        // encrypt and decrypt
+        let sk = PrivateKey::random();
+        let plaintexts: Vec<RistrettoPoint> = (0..10).map(|_| random_point()).collect();
+        let ppk = batch::PrecomputedPublicKey::new(sk.public_key());
+
+        for m in &plaintexts {
+            let r = random_scalar();
+            assert_eq!(
+                ppk.encrypt_with_random(r, *m).to_bytes(),
+                sk.public_key().encrypt_with_random(r, *m).to_bytes()
+            );
+        }
+
+        assert_eq!(
+            batch::decrypt(&batch::encrypt(&sk.public_key(), &plaintexts), &sk),
+            plaintexts
+        );
+    }
+
+    #[cfg(feature = "bin")]
+    #[test]
+    fn serde() {
+        use crate::misc::serde_ext::{B16, B64UU};
+
+        let sk = PrivateKey::random();
+        let pk = sk.public_key();
+        let triple = pk.encrypt_random();
+
+        let json = serde_json::to_string(&B16::<_>::new(sk.clone())).unwrap();
+        assert_eq!(json, format!("\"{}\"", sk.to_hex()));
+        assert_eq!(
+            serde_json::from_str::<B16<PrivateKey>>(&json)
+                .unwrap()
+                .into_inner(),
+            sk
+        );
+
+        let json = serde_json::to_string(&B16::<_>::new(pk.clone())).unwrap();
+        assert_eq!(
+            serde_json::from_str::<B16<PublicKey>>(&json)
+                .unwrap()
+                .into_inner(),
+            pk
+        );
+
+        let json = serde_json::to_string(&B64UU::<_>::new(triple.clone())).unwrap();
+        assert_eq!(
+            serde_json::from_str::<B64UU<Triple>>(&json)
+                .unwrap()
+                .into_inner(),
+            triple
+        );
+
+        // non-canonical scalar
+        assert!(
+            serde_json::from_str::<B16<PrivateKey>>(&format!("\"{}\"", "ff".repeat(32))).is_err()
+        );
+        // not a Ristretto point
+        assert!(
+            serde_json::from_str::<B16<PublicKey>>(&format!("\"{}\"", "ff".repeat(32))).is_err()
+        );
+        // wrong length
+        assert!(serde_json::from_str::<B16<Triple>>(&format!("\"{}\"", pk.to_hex())).is_err());
+    }
+
+    #[test]
+    fn signed_triple() {
+        let signer = PrivateKey::random();
+        let sk = PrivateKey::random();
+        let pt = random_point();
+
+        let signed = sk.public_key().encrypt(pt).sign(&signer);
+
+        assert!(signed.verify(&signer.public_key()));
+        assert!(!signed.verify(&sk.public_key()));
+        assert_eq!(signed.clone().decrypt(&signer.public_key(), &sk), Some(pt));
 
-        unsafe { pt.copy_to_ptr(plaintext) }
+        let decoded = signed::SignedTriple::from_hex(&signed.to_hex()).unwrap();
+        assert_eq!(decoded, signed);
 
-        DecryptResult::Ok
+        // tamper with the public key
+        let mut bytes = signed.to_bytes();
+        bytes[64..96].copy_from_slice(&PrivateKey::random().public_key().to_bytes());
+        let tampered = signed::SignedTriple::from_bytes(bytes).unwrap();
+        assert!(tampered.clone().open(&signer.public_key()).is_none());
+        assert_eq!(tampered.decrypt(&signer.public_key(), &sk), None);
     }
 
-    /// Result of [decrypt].
-    #[repr(u8)]
-    pub enum DecryptResult {
-        Ok = 1,
-        WrongPublicKey = 2,
-        InvalidTriple = 3,
-        InvalidPrivateKey = 4,
+    #[test]
+    fn data_encoding() {
+        use rand::RngCore as _;
+
+        for len in 0..=data::MAX_LEN {
+            for _ in 0..10 {
+                let mut bytes = vec![0u8; len];
+                rand::rngs::OsRng.fill_bytes(&mut bytes);
+
+                let point = data::encode(&bytes).unwrap();
+                assert_eq!(data::decode(&point).unwrap(), bytes);
+            }
+        }
+
+        // trailing zeroes are kept
+        assert_eq!(
+            data::decode(&data::encode(&[1, 0]).unwrap()).unwrap(),
+            [1, 0]
+        );
     }
 }
@@ -0,0 +1,199 @@
+//! Derivation of the pseudonymisation factor `s` and decryption factor `k` used in
+//! [Triple::rsk](crate::elgamal::Triple::rsk) from a secret and a context (like a hub's id).
+//!
+//! The derivation is versioned:  changing the derivation changes all local pseudonyms and
+//! local decryption keys, so a [Version], once released, must never be changed.
+//!
+//! ```
+//! use pubhubs::factors::{derive, FactorType, Version};
+//!
+//! let s = derive(Version::V1, FactorType::Pseudonymisation, b"secret", "Hub #1");
+//! let k = derive(Version::V1, FactorType::Decryption, b"secret", "Hub #1");
+//! assert_ne!(s, k);
+//! ```
+use curve25519_dalek::scalar::Scalar;
+use hmac::Mac as _;
+use sha2::Digest as _;
+
+/// What the derived factor is used for;  factors of different types are independent
+/// even when derived from the same secret and context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FactorType {
+    /// The factor `s` by which [Triple::rsk](crate::elgamal::Triple::rsk) multiplies the
+    /// plaintext.
+    Pseudonymisation,
+
+    /// The factor `k` by which [Triple::rsk](crate::elgamal::Triple::rsk) multiplies the
+    /// target key.
+    Decryption,
+}
+
+/// Version of the factor derivation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    /// As used by libpep-cpp (and the `libpepcli` tool):
+    /// `SHA-512("pseudonym|<secret>|<context>")` or `SHA-512("decryption|<secret>|<context>")`,
+    /// reduced modulo the group order.
+    ///
+    /// Since the secret and context are not length-prefixed, a `|` in the secret makes the
+    /// derivation ambiguous.  Only use this version for existing local pseudonyms.
+    ///
+    /// <https://gitlab.science.ru.nl/bernardg/libpep-cpp/-/blob/65b1f346e0edb8a6606b32e8df7b0c23f8832cec/src/libpep.cpp#L37>
+    V0,
+
+    /// `HMAC-SHA-512` keyed by the secret over a domain separation tag, the factor type
+    /// and the length-prefixed context, reduced modulo the group order.
+    V1,
+}
+
+impl Version {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Version::V0 => "v0",
+            Version::V1 => "v1",
+        }
+    }
+}
+
+impl core::str::FromStr for Version {
+    type Err = UnknownVersion;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "v0" => Ok(Version::V0),
+            "v1" => Ok(Version::V1),
+            _ => Err(UnknownVersion),
+        }
+    }
+}
+
+impl core::fmt::Display for Version {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Returned by [Version]'s `from_str` for anything but `v0` or `v1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownVersion;
+
+impl core::fmt::Display for UnknownVersion {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("unknown factor derivation version;  expected 'v0' or 'v1'")
+    }
+}
+
+impl std::error::Error for UnknownVersion {}
+
+/// Domain separation tag for [Version::V1].
+const V1_DST: &[u8] = b"pubhubs factor v1";
+
+/// Derives the factor of type `typ` from `secret` and `context` according to `version`.
+pub fn derive(version: Version, typ: FactorType, secret: &[u8], context: &str) -> Scalar {
+    match version {
+        Version::V0 => {
+            let typ: &str = match typ {
+                FactorType::Pseudonymisation => "pseudonym",
+                FactorType::Decryption => "decryption",
+            };
+
+            Scalar::from_hash(
+                sha2::Sha512::new()
+                    .chain_update(typ)
+                    .chain_update("|")
+                    .chain_update(secret)
+                    .chain_update("|")
+                    .chain_update(context),
+            )
+        }
+        Version::V1 => {
+            let typ: u8 = match typ {
+                FactorType::Pseudonymisation => 1,
+                FactorType::Decryption => 2,
+            };
+
+            // Note: HMAC accepts keys of any length
+            let mut mac = hmac::Hmac::<sha2::Sha512>::new_from_slice(secret).unwrap();
+            mac.update(V1_DST);
+            mac.update(&[typ]);
+            mac.update(&(context.len() as u64).to_be_bytes());
+            mac.update(context.as_bytes());
+
+            Scalar::from_bytes_mod_order_wide(&mac.finalize().into_bytes().into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elgamal::Encoding as _;
+
+    /// DO NOT CHANGE these vectors:  they pin the derivations, and thus all local pseudonyms.
+    ///
+    /// The vectors were computed independently using Python's `hashlib` and `hmac`.
+    #[test]
+    fn pinned_vectors() {
+        let secret = b"is also called server secret";
+        let context = "Hub decryption key #936da01f-9abd-4d9d-80c7-02af85c822a8";
+
+        for (version, typ, expected) in [
+            (
+                Version::V0,
+                FactorType::Pseudonymisation,
+                "c111d2a3a4366581682471213ed55b768d8e33630e194a82aec8f6ec566e520c",
+            ),
+            (
+                Version::V0,
+                FactorType::Decryption,
+                "bd2c86b85b1f2483daf6015240f76d8d4fb768365f221094691a2bb5db632b0a",
+            ),
+            (
+                Version::V1,
+                FactorType::Pseudonymisation,
+                "5c870e46c89500802f37b8b5f68f6c7a6107ba073ee68d7c9491c732a3d8b20c",
+            ),
+            (
+                Version::V1,
+                FactorType::Decryption,
+                "1143fda05a58a9d9bff2c0c9aa18d54922902581d70b485f7e6dee97a7b40b01",
+            ),
+        ] {
+            assert_eq!(
+                derive(version, typ, secret, context).to_hex(),
+                expected,
+                "{version} {typ:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn libpepcli_decryption_key() {
+        // libpepcli make-local-decryption-key
+        // cfd09aaaa3ef22bfda97805c2fa864be371a07e154025c53a008698e21a74702 "is also called server
+        // secret" "Hub decryption key #936da01f-9abd-4d9d-80c7-02af85c822a8"
+        let global_secret_key =
+            Scalar::from_hex("cfd09aaaa3ef22bfda97805c2fa864be371a07e154025c53a008698e21a74702")
+                .unwrap();
+
+        let k = derive(
+            Version::V0,
+            FactorType::Decryption,
+            b"is also called server secret",
+            "Hub decryption key #936da01f-9abd-4d9d-80c7-02af85c822a8",
+        );
+
+        assert_eq!(
+            (k * global_secret_key).to_hex(),
+            "02a1348c03aa13dde10a2eb8f2c5aacd2201703e3a5944a2d6c7aaf219d0500f"
+        );
+    }
+
+    #[test]
+    fn version_from_str() {
+        for version in [Version::V0, Version::V1] {
+            assert_eq!(version.as_str().parse::<Version>(), Ok(version));
+        }
+        assert!("v2".parse::<Version>().is_err());
+    }
+}
@@ -2,4 +2,8 @@
 //!
 //! Please keep this as small as possible.
 
+#[cfg(feature = "abi")]
+pub mod abi;
 pub mod elgamal;
+pub mod factors;
+pub mod short_pseudonym;
@@ -0,0 +1,140 @@
+//! Short pseudonyms, like `01f-6cd`, derived from a hub's (decrypted) local pseudonym,
+//! and used by the hub as the localpart of the user's Matrix id.
+//!
+//! The hub's `pubhubs_hub/modules/pseudonyms.py` uses this implementation via `libpubhubs.so`.
+
+/// Alphabet used for the check digits; `g` is the 17th 'digit'.
+pub const CHECKDIGIT_ALPHABET: &[u8; 17] = b"0123456789abcdefg";
+
+/// The largest `n` accepted by [short_pseudonym].
+pub const MAX_N: usize = 13;
+
+/// Maximal length of a short pseudonym, obtained for `n` = [MAX_N].
+pub const MAX_LEN: usize = 2 * (MAX_N + 2) + 3;
+
+/// Computes an ISBN10-style check digit for `s` using mod 17 instead of mod 11,
+/// so that `s` can be a string of at most 15 hex digits (instead of 9 decimal digits.)
+///
+/// Returns `None` when `s` is too long or contains characters that are not base 17 digits.
+///
+/// ```
+/// use pubhubs::short_pseudonym::checkdigit;
+///
+/// assert_eq!(checkdigit("000"), Some('0'));
+/// assert_eq!(checkdigit("1"), Some('f'));
+/// assert_eq!(checkdigit("20"), Some('b'));
+/// assert_eq!(checkdigit("0123456789abcdef"), None);
+/// ```
+pub fn checkdigit(s: &str) -> Option<char> {
+    let n = s.len();
+
+    if n > 15 {
+        return None;
+    }
+
+    let mut cs: u32 = 0;
+
+    for (i, c) in s.chars().rev().enumerate() {
+        let d = c.to_digit(17)?;
+        // weight of the i-th digit from the right is i+2;  we subtract
+        cs = (cs + 17 * 17 - (i as u32 + 2) * d) % 17;
+    }
+
+    Some(CHECKDIGIT_ALPHABET[cs as usize] as char)
+}
+
+/// Returns whether `s` is a lower-case hex-encoded local pseudonym (of 64 digits).
+pub fn is_local_pseudonym(s: &str) -> bool {
+    s.len() == 64 && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Returns the `n`th short pseudonym associated to the hex-encoded `local_pseudonym`,
+/// consisting of `2*(n+3)` digits and a `-`.
+///
+/// Returns `None` when `local_pseudonym` is not a valid local pseudonym (see
+/// [is_local_pseudonym]) or when `n` exceeds [MAX_N].
+///
+/// ```
+/// use pubhubs::short_pseudonym::short_pseudonym;
+///
+/// let lp = "0123456789".repeat(6) + "abcd";
+///
+/// assert_eq!(short_pseudonym(&lp, 0).unwrap(), "01f-6cd");
+/// assert_eq!(short_pseudonym(&lp, 1).unwrap(), "012a-dbcd");
+/// assert_eq!(short_pseudonym(&lp, 14), None);
+/// ```
+pub fn short_pseudonym(local_pseudonym: &str, n: usize) -> Option<String> {
+    if !is_local_pseudonym(local_pseudonym) || n > MAX_N {
+        return None;
+    }
+
+    let prefix_len = n + 2;
+    let prefix = &local_pseudonym[..prefix_len];
+    let suffix = &local_pseudonym[local_pseudonym.len() - prefix_len..];
+
+    Some(format!(
+        "{prefix}{}-{}{suffix}",
+        checkdigit(prefix)?,
+        checkdigit(suffix)?
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkdigit() {
+        assert_eq!(checkdigit("10"), Some('e'));
+        assert_eq!(checkdigit("8"), Some('1'));
+
+        // taking the inverse of the weight for the i-th digit,
+        // we should get 15 times -1
+        let s: String = (0..15u32)
+            .map(|i| {
+                let inv = (1..17).find(|x| (x * (16 - i)) % 17 == 1).unwrap();
+                CHECKDIGIT_ALPHABET[inv as usize] as char
+            })
+            .collect();
+        assert_eq!(checkdigit(&s), Some(CHECKDIGIT_ALPHABET[17 - 15] as char));
+
+        // all ones will give the sum -2 - 3 - 4 - ... - 16
+        assert_eq!(
+            checkdigit(&"1".repeat(15)),
+            Some(CHECKDIGIT_ALPHABET[(1 + 17 * 17 - 17 * 16 / 2) % 17] as char)
+        );
+
+        assert_eq!(checkdigit("h"), None);
+    }
+
+    #[test]
+    fn test_short_pseudonyms() {
+        let lp = "0123456789".repeat(6) + "abcd";
+
+        assert_eq!(
+            (0..=MAX_N)
+                .map(|n| short_pseudonym(&lp, n).unwrap())
+                .collect::<Vec<String>>(),
+            [
+                "01f-6cd",
+                "012a-dbcd",
+                "01231-eabcd",
+                "012344-b9abcd",
+                "0123451-689abcd",
+                "01234568-1789abcd",
+                "012345677-f6789abcd",
+                "012345678e-g56789abcd",
+                "0123456789b-6456789abcd",
+                "012345678900-43456789abcd",
+                "0123456789014-c23456789abcd",
+                "01234567890125-f123456789abcd",
+                "012345678901232-f0123456789abcd",
+                "012345678901234b-790123456789abcd",
+            ]
+        );
+
+        assert_eq!(short_pseudonym(&lp, MAX_N).unwrap().len(), MAX_LEN);
+        assert_eq!(short_pseudonym(&lp.to_uppercase(), 0), None);
+        assert_eq!(short_pseudonym(&lp[1..], 0), None);
+    }
+}
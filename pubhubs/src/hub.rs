@@ -1,5 +1,7 @@
 //! Information about hubs
 use core::cell::OnceCell;
+use std::collections::{BTreeMap, HashMap};
+
 use rand::RngCore as _;
 use regex;
 
@@ -8,16 +10,16 @@ use crate::misc::serde_ext;
 /// Basic details about hub, as provided by PubHubs Central.
 #[derive(serde::Serialize, serde::Deserialize, Debug, Eq, PartialEq, Clone)]
 #[serde(remote = "Self")]
-// We use serde(remote... to check the invariant names.len()>0, see
+// We use serde(remote... to check the invariants (names.len()>0, etc.), see
 //   https://github.com/serde-rs/serde/issues/1220
 pub struct BasicInfo {
     /// The names for this hub.  The first one is the one that's used by default.
     /// Names may be added, but should not be removed.
     names: Vec<Name>,
 
-    /// Short description for this hub.  This is stored centrally to facilitate searching.
-    /// May be changed freely.
-    description: String,
+    /// Short description for this hub, possibly in several languages.  This is stored centrally
+    /// to facilitate searching.  May be changed freely.
+    description: Localized,
 
     /// Hub info endpoint
     /// May be changed freely.
@@ -25,6 +27,27 @@ pub struct BasicInfo {
 
     /// Immutable and unique identifier
     id: Id,
+
+    /// Keywords to facilitate searching, each matching [NAME_REGEX].  May be changed freely.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+
+    /// Where to contact the hub's owner;  an `https`, `http` or `mailto` URL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    contact_url: Option<url::Url>,
+
+    /// Where to find the hub's logo;  an `https` or `http` URL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    logo_url: Option<url::Url>,
+}
+
+impl serde::Serialize for BasicInfo {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        Self::serialize(self, serializer)
+    }
 }
 
 impl<'de> serde::Deserialize<'de> for BasicInfo {
@@ -38,10 +61,283 @@ impl<'de> serde::Deserialize<'de> for BasicInfo {
                 "a hub must have at least one name",
             ));
         }
+
+        if let Some(tag) = unchecked
+            .tags
+            .iter()
+            .find(|tag| !with_name_regex(|r: &regex::Regex| r.is_match(tag)))
+        {
+            return Err(serde::de::Error::custom(format!(
+                "hub tag {tag:?} must be a non-empty string of lower-case alphanumeric characters and underscore"
+            )));
+        }
+
+        if let Some(url) = unchecked.contact_url.as_ref() {
+            if !["https", "http", "mailto"].contains(&url.scheme()) {
+                return Err(serde::de::Error::custom(
+                    "a hub's contact_url must be an https, http or mailto url",
+                ));
+            }
+        }
+
+        if let Some(url) = unchecked.logo_url.as_ref() {
+            if !["https", "http"].contains(&url.scheme()) {
+                return Err(serde::de::Error::custom(
+                    "a hub's logo_url must be an https or http url",
+                ));
+            }
+        }
+
         Ok(unchecked)
     }
 }
 
+impl BasicInfo {
+    /// Creates a hub with a single name, and without optional metadata.
+    pub fn new(name: Name, description: Localized, info_url: url::Url, id: Id) -> Self {
+        BasicInfo {
+            names: vec![name],
+            description,
+            info_url,
+            id,
+            tags: vec![],
+            contact_url: None,
+            logo_url: None,
+        }
+    }
+
+    /// All names of this hub, the default name first.
+    pub fn names(&self) -> &[Name] {
+        &self.names
+    }
+
+    /// The name that's used for this hub by default.
+    pub fn default_name(&self) -> &Name {
+        &self.names[0]
+    }
+
+    pub fn description(&self) -> &Localized {
+        &self.description
+    }
+
+    pub fn info_url(&self) -> &url::Url {
+        &self.info_url
+    }
+
+    pub fn id(&self) -> &Id {
+        &self.id
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn contact_url(&self) -> Option<&url::Url> {
+        self.contact_url.as_ref()
+    }
+
+    pub fn logo_url(&self) -> Option<&url::Url> {
+        self.logo_url.as_ref()
+    }
+}
+
+/// Text in several languages, see [Localized::get].
+///
+/// Deserializes from a plain string, used for all languages, or from a map from language codes
+/// (like `en` and `nl`) to text, with a `default` entry used for all other languages.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Eq, PartialEq, Clone)]
+#[serde(try_from = "LocalizedRepr", into = "LocalizedRepr")]
+pub struct Localized {
+    default: String,
+    translations: BTreeMap<String, String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+enum LocalizedRepr {
+    Plain(String),
+    Map(BTreeMap<String, String>),
+}
+
+/// The key of the fallback text in the map representation of [Localized].
+const DEFAULT_LANG_KEY: &str = "default";
+
+/// Why a [Localized] could not be deserialized.
+#[derive(thiserror::Error, Debug)]
+pub enum LocalizedError {
+    #[error("localized text must have a 'default' entry")]
+    MissingDefault,
+
+    #[error("{0:?} is not a language code;  use two or three lower-case letters, like 'en'")]
+    InvalidLang(String),
+}
+
+impl TryFrom<LocalizedRepr> for Localized {
+    type Error = LocalizedError;
+
+    fn try_from(repr: LocalizedRepr) -> Result<Self, Self::Error> {
+        let mut translations = match repr {
+            LocalizedRepr::Plain(default) => return Ok(default.into()),
+            LocalizedRepr::Map(map) => map,
+        };
+
+        let default = translations
+            .remove(DEFAULT_LANG_KEY)
+            .ok_or(LocalizedError::MissingDefault)?;
+
+        if let Some(lang) = translations.keys().find(|lang| !is_lang(lang)) {
+            return Err(LocalizedError::InvalidLang(lang.clone()));
+        }
+
+        Ok(Localized {
+            default,
+            translations,
+        })
+    }
+}
+
+impl From<Localized> for LocalizedRepr {
+    fn from(localized: Localized) -> Self {
+        if localized.translations.is_empty() {
+            return LocalizedRepr::Plain(localized.default);
+        }
+
+        let mut map = localized.translations;
+        map.insert(DEFAULT_LANG_KEY.to_string(), localized.default);
+        LocalizedRepr::Map(map)
+    }
+}
+
+impl From<String> for Localized {
+    fn from(default: String) -> Self {
+        Localized {
+            default,
+            translations: BTreeMap::new(),
+        }
+    }
+}
+
+/// Whether `lang` is a (primary) language code, i.e. two or three lower-case letters.
+fn is_lang(lang: &str) -> bool {
+    (2..=3).contains(&lang.len()) && lang.bytes().all(|b| b.is_ascii_lowercase())
+}
+
+impl Localized {
+    /// Returns the text for the first of the `preferred` languages available, together with
+    /// that language, or the default text (and [None]) if none is available.
+    ///
+    /// Only the primary language subtag of a preferred language is considered, case-insensitively,
+    /// so `nl-BE` selects the `nl` text.
+    pub fn get(
+        &self,
+        preferred: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> (Option<&str>, &str) {
+        for lang in preferred {
+            let lang = lang.as_ref();
+            let primary = lang.split('-').next().unwrap_or(lang).to_ascii_lowercase();
+
+            if let Some((lang, text)) = self.translations.get_key_value(&primary) {
+                return (Some(lang), text);
+            }
+        }
+
+        (None, &self.default)
+    }
+
+    /// The text used for languages without a translation.
+    pub fn default_text(&self) -> &str {
+        &self.default
+    }
+}
+
+/// The hubs known to PubHubs Central, indexed by each of their names and by [Id].
+///
+/// Deserializes from a list of [BasicInfo]s, rejecting lists in which a name or [Id]
+/// is claimed by more than one hub.
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+#[serde(try_from = "Vec<BasicInfo>")]
+pub struct Hubs {
+    hubs: Vec<BasicInfo>,
+    by_name: HashMap<Name, usize>,
+    by_id: HashMap<Id, usize>,
+}
+
+/// Why a list of [BasicInfo]s could not be turned into [Hubs].
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum HubsError {
+    #[error("the hub name {0} is claimed by more than one hub")]
+    DuplicateName(Name),
+
+    #[error("the hub id {0} is used by more than one hub")]
+    DuplicateId(Id),
+}
+
+impl TryFrom<Vec<BasicInfo>> for Hubs {
+    type Error = HubsError;
+
+    fn try_from(hubs: Vec<BasicInfo>) -> Result<Self, Self::Error> {
+        let mut by_name: HashMap<Name, usize> = HashMap::new();
+        let mut by_id: HashMap<Id, usize> = HashMap::new();
+
+        for (i, hub) in hubs.iter().enumerate() {
+            if by_id.insert(hub.id, i).is_some() {
+                return Err(HubsError::DuplicateId(hub.id));
+            }
+
+            for name in hub.names.iter() {
+                if by_name.insert(name.clone(), i).is_some() {
+                    return Err(HubsError::DuplicateName(name.clone()));
+                }
+            }
+        }
+
+        Ok(Hubs {
+            hubs,
+            by_name,
+            by_id,
+        })
+    }
+}
+
+/// A hub found by [Hubs::resolve].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resolved<'a> {
+    pub hub: &'a BasicInfo,
+
+    /// Whether the hub was found by its [BasicInfo::default_name].  If not, the caller used an
+    /// alias or the hub's [Id], and should be pointed to the default name.
+    pub by_default_name: bool,
+}
+
+impl Hubs {
+    /// Looks up the hub with the given name, which may be any of the hub's names, or [Id].
+    /// Names take precedence over ids.
+    pub fn resolve(&self, name_or_id: &str) -> Option<Resolved<'_>> {
+        if let Ok(name) = name_or_id.parse::<Name>() {
+            if let Some(&i) = self.by_name.get(&name) {
+                let hub = &self.hubs[i];
+
+                return Some(Resolved {
+                    hub,
+                    by_default_name: *hub.default_name() == name,
+                });
+            }
+        }
+
+        let id: Id = name_or_id.parse().ok()?;
+
+        Some(Resolved {
+            hub: &self.hubs[*self.by_id.get(&id)?],
+            by_default_name: false,
+        })
+    }
+
+    /// Iterates over all known hubs.
+    pub fn iter(&self) -> impl Iterator<Item = &BasicInfo> {
+        self.hubs.iter()
+    }
+}
+
 /// The regex pattern for a hub name
 pub const NAME_REGEX: &str = r"^[a-z0-9_]+$";
 
@@ -131,6 +427,12 @@ pub struct Id {
     inner: serde_ext::B64UU<serde_ext::ByteArray<32>>,
 }
 
+impl std::hash::Hash for Id {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        (**self.inner).hash(state)
+    }
+}
+
 impl Id {
     /// Creates a new random hub id
     pub fn random() -> Self {
@@ -142,6 +444,17 @@ impl Id {
             inner: serde_ext::ByteArray::<32>::from(bytes).into(),
         }
     }
+
+    /// The id for a hub migrated from the legacy PubHubs Central, whose id was a uuid.
+    /// The uuid's bytes are followed by zeroes, so the legacy id can be recovered.
+    pub fn from_legacy_uuid(uuid: [u8; 16]) -> Self {
+        let mut bytes: [u8; 32] = [0; 32];
+        bytes[..16].copy_from_slice(&uuid);
+
+        Id {
+            inner: serde_ext::ByteArray::<32>::from(bytes).into(),
+        }
+    }
 }
 
 impl core::str::FromStr for Id {
@@ -191,9 +504,131 @@ mod tests {
             BasicInfo{
                 names: vec!["hub_1".parse().unwrap()],
                 info_url: "https://example.com".parse().unwrap(),
-                description: "some hub".to_string(),
+                description: "some hub".to_string().into(),
                 id: "bLAPDnkcYj8S5hZ8NuH9OFTWKzypLqSakexoRvlZ_aA".parse().unwrap(),
+                tags: vec![],
+                contact_url: None,
+                logo_url: None,
             }
         );
+
+        let info = serde_json::from_str::<BasicInfo>(
+            r#"{"names": ["hub_1"], "info_url": "https://example.com",
+            "description": {"default": "some hub", "nl": "een hub"},
+            "id": "bLAPDnkcYj8S5hZ8NuH9OFTWKzypLqSakexoRvlZ_aA",
+            "tags": ["sports", "tennis"], "contact_url": "mailto:owner@example.com",
+            "logo_url": "https://example.com/logo.png"}"#,
+        )
+        .unwrap();
+        assert_eq!(info.tags(), ["sports", "tennis"]);
+        assert_eq!(
+            serde_json::from_str::<BasicInfo>(&serde_json::to_string(&info).unwrap()).unwrap(),
+            info
+        );
+
+        for (extra, err) in [
+            (r#""tags": ["Not a tag"]"#, "hub tag \"Not a tag\" must be"),
+            (
+                r#""contact_url": "ftp://example.com""#,
+                "a hub's contact_url must be",
+            ),
+            (
+                r#""logo_url": "mailto:owner@example.com""#,
+                "a hub's logo_url must be",
+            ),
+        ] {
+            let json = format!(
+                r#"{{"names": ["hub_1"], "info_url": "https://example.com", "description": "some hub",
+                "id": "bLAPDnkcYj8S5hZ8NuH9OFTWKzypLqSakexoRvlZ_aA", {extra}}}"#
+            );
+            let msg = serde_json::from_str::<BasicInfo>(&json)
+                .unwrap_err()
+                .to_string();
+            assert!(msg.starts_with(err), "{msg:?} does not start with {err:?}");
+        }
+    }
+
+    #[test]
+    fn localized() {
+        let localized: Localized =
+            serde_json::from_str(r#"{"default": "a hub", "nl": "een hub", "de": "ein Hub"}"#)
+                .unwrap();
+
+        assert_eq!(localized.get(["nl"]), (Some("nl"), "een hub"));
+        assert_eq!(
+            localized.get(["fr", "nl-BE", "de"]),
+            (Some("nl"), "een hub")
+        );
+        assert_eq!(localized.get(["DE"]), (Some("de"), "ein Hub"));
+        assert_eq!(localized.get(["fr"]), (None, "a hub"));
+        assert_eq!(localized.get(Vec::<String>::new()), (None, "a hub"));
+
+        let plain: Localized = serde_json::from_str(r#""a hub""#).unwrap();
+        assert_eq!(plain.get(["nl"]), (None, "a hub"));
+        assert_eq!(serde_json::to_string(&plain).unwrap(), r#""a hub""#);
+
+        assert!(
+            serde_json::from_str::<Localized>(r#"{"default": "a hub", "NL": "een hub"}"#)
+                .unwrap_err()
+                .to_string()
+                .starts_with("\"NL\" is not a language code")
+        );
+    }
+
+    #[test]
+    fn hubs_resolve() {
+        let hub = |names: &[&str], id: &str| BasicInfo {
+            names: names.iter().map(|name| name.parse().unwrap()).collect(),
+            info_url: "https://example.com".parse().unwrap(),
+            description: "some hub".to_string().into(),
+            id: id.parse().unwrap(),
+            tags: vec![],
+            contact_url: None,
+            logo_url: None,
+        };
+
+        let id1 = "bLAPDnkcYj8S5hZ8NuH9OFTWKzypLqSakexoRvlZ_aA";
+        let id2 = "aLAPDnkcYj8S5hZ8NuH9OFTWKzypLqSakexoRvlZ_aA";
+
+        let hubs = Hubs::try_from(vec![
+            hub(&["new_name", "old_name"], id1),
+            hub(&["other"], id2),
+        ])
+        .unwrap();
+
+        let resolved = hubs.resolve("new_name").unwrap();
+        assert_eq!(resolved.hub.id().to_string(), id1);
+        assert!(resolved.by_default_name);
+
+        let resolved = hubs.resolve("old_name").unwrap();
+        assert_eq!(resolved.hub.default_name().to_string(), "new_name");
+        assert!(!resolved.by_default_name);
+
+        let resolved = hubs.resolve(id2).unwrap();
+        assert_eq!(resolved.hub.default_name().to_string(), "other");
+        assert!(!resolved.by_default_name);
+
+        assert!(hubs.resolve("unknown").is_none());
+        assert!(hubs.resolve("Not a name").is_none());
+
+        assert_eq!(
+            Hubs::try_from(vec![hub(&["a", "b"], id1), hub(&["b"], id2)]).unwrap_err(),
+            HubsError::DuplicateName("b".parse().unwrap())
+        );
+        assert_eq!(
+            Hubs::try_from(vec![hub(&["a"], id1), hub(&["b"], id1)]).unwrap_err(),
+            HubsError::DuplicateId(id1.parse().unwrap())
+        );
+        assert_eq!(
+            serde_json::from_str::<Hubs>(
+                r#"[{"names": ["a"], "info_url": "https://example.com", "description": "",
+                "id": "bLAPDnkcYj8S5hZ8NuH9OFTWKzypLqSakexoRvlZ_aA"},
+                {"names": ["a"], "info_url": "https://example.com", "description": "",
+                "id": "aLAPDnkcYj8S5hZ8NuH9OFTWKzypLqSakexoRvlZ_aA"}]"#
+            )
+            .unwrap_err()
+            .to_string(),
+            "the hub name a is claimed by more than one hub"
+        );
     }
 }
@@ -1,5 +1,6 @@
 #[cfg(feature = "old")]
 mod old {
+    pub mod account;
     pub mod bar;
     pub mod config;
     pub mod context;
@@ -8,11 +9,16 @@ mod old {
     pub mod data;
     pub mod error;
     pub mod hairy_ext;
+    #[cfg(feature = "bin")] // uses crate::api
+    pub mod hub_secret;
     pub mod middleware;
+    #[cfg(feature = "bin")] // uses crate::hub
+    pub mod migrate;
     pub mod oidc;
     pub mod oidc_handler;
     pub mod policy;
     pub mod pseudonyms;
+    pub mod repseudonymisation;
     pub mod translate;
     pub mod yivi;
     pub mod yivi_proxy;
@@ -47,4 +53,4 @@ pub use common::*;
 
 // only symbols in the root are exported
 #[cfg(feature = "abi")]
-pub use elgamal::abi::*;
+pub use abi::*;
@@ -25,13 +25,152 @@ pub struct JWT<C> {
     phantom: PhantomData<C>,
 }
 
-#[allow(dead_code)] // TODO: remove
+/// The registered claims from RFC7519 that are checked by [JWT::open] according to [Validation].
+///
+/// Other claims are ignored.
+#[derive(Deserialize, Default, Debug, Clone, PartialEq, Eq)]
 pub struct CommonClaims {
-    iat: Option<NumericDate>,
-    nbf: Option<NumericDate>,
-    exp: Option<NumericDate>,
-    iss: Option<String>,
-    sub: Option<String>,
+    pub iat: Option<NumericDate>,
+    pub nbf: Option<NumericDate>,
+    pub exp: Option<NumericDate>,
+    pub iss: Option<String>,
+    pub sub: Option<String>,
+    pub aud: Option<Audience>,
+    pub jti: Option<String>,
+}
+
+/// The value of the `aud` claim:  either a single string, or an array of strings.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Audience {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl Audience {
+    /// Whether `aud` is (among) the audience.
+    pub fn contains(&self, aud: &str) -> bool {
+        match self {
+            Audience::Single(s) => s == aud,
+            Audience::Multiple(v) => v.iter().any(|s| s == aud),
+        }
+    }
+}
+
+/// Specifies which claims [JWT::open] checks, and how.
+///
+/// The time-based claims `exp`, `nbf` and `iat` are always checked when present,
+/// against the time returned by `clock`, allowing for `leeway` seconds of clock skew.
+#[derive(Clone, Debug)]
+pub struct Validation {
+    /// Returns the current time, see [get_current_timestamp].
+    pub clock: fn() -> u64,
+
+    /// Number of seconds of clock skew to allow for when checking `exp`, `nbf` and `iat`.
+    pub leeway: u64,
+
+    /// If set, the `iss` claim must be present and equal this value.
+    pub iss: Option<String>,
+
+    /// If set, the `aud` claim must be present and contain this value.  If not set, the `aud`
+    /// claim must be absent, because RFC7519 requires a JWT to be rejected when its audience
+    /// does not include the party processing it.
+    pub aud: Option<String>,
+
+    /// Whether the `exp` claim must be present.
+    pub require_exp: bool,
+
+    /// Whether the `jti` claim must be present.
+    pub require_jti: bool,
+}
+
+impl Default for Validation {
+    /// Requires `exp`, and allows one minute of clock skew.
+    fn default() -> Self {
+        Self {
+            clock: get_current_timestamp,
+            leeway: 60,
+            iss: None,
+            aud: None,
+            require_exp: true,
+            require_jti: false,
+        }
+    }
+}
+
+impl Validation {
+    /// Checks the given [CommonClaims] against this [Validation].
+    pub fn check(&self, claims: &CommonClaims) -> Result<(), Error> {
+        let now = (self.clock)();
+
+        match &claims.exp {
+            Some(exp) => {
+                if now > exp.timestamp.saturating_add(self.leeway) {
+                    return Err(Error::Expired {
+                        exp: exp.timestamp,
+                        now,
+                    });
+                }
+            }
+            None => {
+                if self.require_exp {
+                    return Err(Error::MissingClaim("exp"));
+                }
+            }
+        }
+
+        if let Some(nbf) = &claims.nbf {
+            if now.saturating_add(self.leeway) < nbf.timestamp {
+                return Err(Error::NotYetValid {
+                    nbf: nbf.timestamp,
+                    now,
+                });
+            }
+        }
+
+        if let Some(iat) = &claims.iat {
+            if now.saturating_add(self.leeway) < iat.timestamp {
+                return Err(Error::IssuedInTheFuture {
+                    iat: iat.timestamp,
+                    now,
+                });
+            }
+        }
+
+        if let Some(expected) = &self.iss {
+            match &claims.iss {
+                None => return Err(Error::MissingClaim("iss")),
+                Some(iss) if iss != expected => {
+                    return Err(Error::UnexpectedIssuer {
+                        got: iss.clone(),
+                        expected: expected.clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        match (&self.aud, &claims.aud) {
+            (None, None) => {}
+            (Some(_), None) => return Err(Error::MissingClaim("aud")),
+            (expected, Some(aud)) => {
+                if !expected
+                    .as_ref()
+                    .is_some_and(|expected| aud.contains(expected))
+                {
+                    return Err(Error::UnexpectedAudience {
+                        expected: expected.clone(),
+                    });
+                }
+            }
+        }
+
+        if self.require_jti && claims.jti.is_none() {
+            return Err(Error::MissingClaim("jti"));
+        }
+
+        Ok(())
+    }
 }
 
 /// Represents the value of the `iat`, `exp`, `nbf` claims.
@@ -56,9 +195,14 @@ pub struct NumericDate {
 impl NumericDate {
     /// Creates a new numeric date from the given `timestamp`, the  number of seconds since the
     /// unix epoch ignoring leap seconds.
-    fn new(timestamp: u64) -> Self {
+    pub fn new(timestamp: u64) -> Self {
         Self { timestamp }
     }
+
+    /// The number of seconds since the unix epoch, ignoring leap seconds.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
 }
 
 impl<'de> Deserialize<'de> for NumericDate {
@@ -111,13 +255,15 @@ impl<C> From<String> for JWT<C> {
 
 impl<C: Serialize> JWT<C> {
     /// Creates JWT from `claims` and [SigningKey] `key`.
-    fn create<SK: SigningKey>(claims: &C, key: &SK) -> Result<JWT<C>, Error> {
+    pub fn create<SK: SigningKey>(claims: &C, key: &SK) -> Result<JWT<C>, Error> {
         let to_be_signed: String = format!(
             "{}.{}",
             Base64UrlUnpadded::encode_string(
-                &serde_json::to_vec(&serde_json::json!({
-                    "alg": SK::ALG,
-                }))
+                &serde_json::to_vec(&Header {
+                    _typ: HeaderType {},
+                    alg: Cow::Borrowed(SK::ALG),
+                    kid: key.key_id().map(Cow::Owned),
+                })
                 .map_err(Error::SerializingHeader)?
             ),
             &Base64UrlUnpadded::encode_string(
@@ -137,16 +283,36 @@ impl<C: Serialize> JWT<C> {
 }
 
 impl<C> JWT<C> {
-    /// Checks the validity of this jwt against the given [VerifyingKey] `key`, and deserializes
-    /// the claims using `seed`.
+    /// Returns the JWT as string.
+    pub fn as_str(&self) -> &str {
+        &self.inner
+    }
+
+    /// Returns the `kid` from the header of this JWT, without checking its signature.
+    /// Used to select the key to [JWT::open] it with, see [Jwks::open].
+    pub fn key_id(&self) -> Result<Option<String>, Error> {
+        let s = &self.inner;
+        let first_dot_pos: usize = s.find('.').ok_or(Error::MissingDot)?;
+
+        let header_vec: Vec<u8> =
+            Base64UrlUnpadded::decode_vec(&s[..first_dot_pos]).map_err(Error::InvalidBase64)?;
+
+        let header: Header =
+            serde_json::from_slice(&header_vec).map_err(Error::DeserializingHeader)?;
+
+        Ok(header.kid.map(Cow::into_owned))
+    }
+
+    /// Checks the validity of this jwt against the given [VerifyingKey] `key`, checks the
+    /// [CommonClaims] according to `validation`, and deserializes the claims using `seed`.
     ///
     /// Since the deserialized claims may borrow from the deserializer the claims are not returned
     /// directly, but instead are passed to a `consumer` function provided by the caller.  Any
     /// value returned by the `consumer` function is returned by `open`.
-    #[allow(dead_code)] // TODO: remove
-    fn open<VK: VerifyingKey, D: for<'de> DeserializeSeed<'de, Value = C>, R>(
+    pub fn open<VK: VerifyingKey, D: for<'de> DeserializeSeed<'de, Value = C>, R>(
         &self,
         key: &VK,
+        validation: &Validation,
         seed: D,
         consumer: impl FnOnce(C) -> R,
     ) -> Result<R, Error> {
@@ -179,8 +345,13 @@ impl<C> JWT<C> {
         }
 
         // decode claims
-        let claims_vec: Vec<u8> =
-            Base64UrlUnpadded::decode_vec(&s[first_dot_pos + 1..]).map_err(Error::InvalidBase64)?;
+        let claims_vec: Vec<u8> = Base64UrlUnpadded::decode_vec(&signed[first_dot_pos + 1..])
+            .map_err(Error::InvalidBase64)?;
+
+        let common_claims: CommonClaims =
+            serde_json::from_slice(&claims_vec).map_err(Error::DeserializingClaims)?;
+
+        validation.check(&common_claims)?;
 
         let mut d = serde_json::Deserializer::from_slice(&claims_vec);
 
@@ -192,9 +363,20 @@ impl<C> JWT<C> {
     }
 }
 
-#[allow(dead_code)] // TODO: remove
+impl<C: serde::de::DeserializeOwned> JWT<C> {
+    /// Like [JWT::open], but simply returns the claims, which do not borrow from the JWT.
+    pub fn open_owned<VK: VerifyingKey>(
+        &self,
+        key: &VK,
+        validation: &Validation,
+    ) -> Result<C, Error> {
+        self.open(key, validation, PhantomData::<C>, |claims| claims)
+    }
+}
+
+/// Why a JWT or [JWE] could not be created or opened.
 #[derive(thiserror::Error, Debug)]
-enum Error {
+pub enum Error {
     #[error("failed to serialize jwt header")]
     SerializingHeader(#[source] serde_json::Error),
 
@@ -221,6 +403,42 @@ enum Error {
 
     #[error("unexpected algorithm; got {got}, but expected {expected}")]
     UnexpectedAlgorithm { got: String, expected: String },
+
+    #[error("jwt expired at {exp} (it is now {now})")]
+    Expired { exp: u64, now: u64 },
+
+    #[error("jwt not valid before {nbf} (it is now {now})")]
+    NotYetValid { nbf: u64, now: u64 },
+
+    #[error("jwt issued in the future, at {iat} (it is now {now})")]
+    IssuedInTheFuture { iat: u64, now: u64 },
+
+    #[error("jwt is missing the {0} claim")]
+    MissingClaim(&'static str),
+
+    #[error("unexpected issuer; got {got}, but expected {expected}")]
+    UnexpectedIssuer { got: String, expected: String },
+
+    #[error("jwt's audience does not contain {expected:?}")]
+    UnexpectedAudience { expected: Option<String> },
+
+    #[error("no (unique) key with id {0:?} in the key set")]
+    UnknownKeyId(Option<String>),
+
+    #[error("not a supported (PEM-encoded) public key")]
+    InvalidPublicKey,
+
+    #[error("jwe should consist of five dot-separated parts, with an empty encrypted key")]
+    MalformedJwe,
+
+    #[error("unexpected content type; got {got:?}, but expected {expected:?}")]
+    UnexpectedContentType {
+        got: Option<String>,
+        expected: Option<String>,
+    },
+
+    #[error("jwe could not be decrypted - wrong key, or tampered with")]
+    Decryption,
 }
 
 /// Signs `claims` using `key` yielding a JWT.
@@ -251,6 +469,10 @@ struct Header<'a> {
 
     #[serde(borrow)]
     alg: Cow<'a, str>,
+
+    /// Identifies the key used to sign the JWT, see [SigningKey::key_id].
+    #[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+    kid: Option<Cow<'a, str>>,
     // Add fields here when needed
 }
 
@@ -290,6 +512,12 @@ pub trait SigningKey: Key {
 
     /// Returns JSON Web Key description of the associated public key.
     fn jwk(&self) -> serde_json::Value;
+
+    /// Returns the `kid` to put in the header of JWTs signed with this key, and in its [jwk](SigningKey::jwk),
+    /// if any.
+    fn key_id(&self) -> Option<String> {
+        None
+    }
 }
 
 /// Represents a key that can be used to verify the signature on a JWT.
@@ -337,6 +565,7 @@ pub trait Key {
 ///     "crv": "Ed25519",
 ///     "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo",
 ///     "use": "sig",
+///     "kid": "kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k",
 /// }));
 /// ```
 impl SigningKey for ed25519_dalek::SigningKey {
@@ -354,14 +583,392 @@ impl SigningKey for ed25519_dalek::SigningKey {
             "x": Base64UrlUnpadded::encode_string(AsRef::<ed25519_dalek::VerifyingKey>::as_ref(self).as_bytes()),
             // parameter "d" must NOT be included, being the private key
             "use": "sig",
+            "kid": self.key_id(),
         })
     }
+
+    /// The JWK thumbprint of the verifying key, see RFC7638.
+    fn key_id(&self) -> Option<String> {
+        // The required members of the JWK in lexicographic order, without whitespace,
+        // see RFC7638, Section 3.2, and RFC8037, Appendix A.3.
+        Some(thumbprint(format!(
+            r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#,
+            Base64UrlUnpadded::encode_string(
+                AsRef::<ed25519_dalek::VerifyingKey>::as_ref(self).as_bytes()
+            )
+        )))
+    }
+}
+
+/// Computes the JWK thumbprint from the canonical form of a JWK, see RFC7638, Section 3.
+fn thumbprint(canonical_jwk: String) -> String {
+    use sha2::Digest as _;
+
+    Base64UrlUnpadded::encode_string(&sha2::Sha256::digest(canonical_jwk.as_bytes()))
 }
 
 impl Key for ed25519_dalek::SigningKey {
     const ALG: &'static str = "EdDSA";
 }
 
+impl VerifyingKey for ed25519_dalek::VerifyingKey {
+    fn is_valid_signature(&self, message: &[u8], signature: Vec<u8>) -> bool {
+        let Ok(signature) = ed25519_dalek::Signature::from_slice(&signature) else {
+            return false;
+        };
+
+        self.verify_strict(message, &signature).is_ok()
+    }
+}
+
+impl Key for ed25519_dalek::VerifyingKey {
+    const ALG: &'static str = "EdDSA";
+}
+
+/// Implements signing JWTs using RSASSA-PKCS1-v1_5 with SHA-256, see RFC7518, Section 3.3.
+///
+/// Mainly used to test verification of JWTs signed by Yivi servers.
+impl SigningKey for rsa::pkcs1v15::SigningKey<sha2::Sha256> {
+    type Signature = Box<[u8]>;
+
+    fn sign(&self, s: &[u8]) -> anyhow::Result<Box<[u8]>> {
+        use rsa::signature::{SignatureEncoding as _, Signer as _};
+
+        Ok(self.try_sign(s)?.to_bytes())
+    }
+
+    fn jwk(&self) -> serde_json::Value {
+        let (n, e) = rsa_public_parts(self.as_ref());
+
+        serde_json::json!({
+            "kty": "RSA",
+            "alg": Self::ALG,
+            "n": n,
+            "e": e,
+            "use": "sig",
+            "kid": self.key_id(),
+        })
+    }
+
+    /// The JWK thumbprint of the verifying key, see RFC7638.
+    fn key_id(&self) -> Option<String> {
+        let (n, e) = rsa_public_parts(self.as_ref());
+
+        Some(thumbprint(format!(
+            r#"{{"e":"{e}","kty":"RSA","n":"{n}"}}"#
+        )))
+    }
+}
+
+/// Returns the base64url encoded modulus and public exponent of `key`, see RFC7518, Section 6.3.1.
+fn rsa_public_parts(key: &rsa::RsaPrivateKey) -> (String, String) {
+    use rsa::traits::PublicKeyParts as _;
+
+    (
+        Base64UrlUnpadded::encode_string(&key.n().to_bytes_be()),
+        Base64UrlUnpadded::encode_string(&key.e().to_bytes_be()),
+    )
+}
+
+impl Key for rsa::pkcs1v15::SigningKey<sha2::Sha256> {
+    const ALG: &'static str = "RS256";
+}
+
+impl VerifyingKey for rsa::pkcs1v15::VerifyingKey<sha2::Sha256> {
+    fn is_valid_signature(&self, message: &[u8], signature: Vec<u8>) -> bool {
+        use rsa::signature::Verifier as _;
+
+        let Ok(signature) = rsa::pkcs1v15::Signature::try_from(signature.as_slice()) else {
+            return false;
+        };
+
+        self.verify(message, &signature).is_ok()
+    }
+}
+
+impl Key for rsa::pkcs1v15::VerifyingKey<sha2::Sha256> {
+    const ALG: &'static str = "RS256";
+}
+
+/// Implements signing JWTs using ECDSA on P-256 with SHA-256, see RFC7518, Section 3.4.
+impl SigningKey for p256::ecdsa::SigningKey {
+    type Signature = Box<[u8]>;
+
+    fn sign(&self, s: &[u8]) -> anyhow::Result<Box<[u8]>> {
+        let signature: p256::ecdsa::Signature = p256::ecdsa::signature::Signer::try_sign(self, s)?;
+
+        // the concatenation of r and s, see RFC7518, Section 3.4.
+        Ok(signature.to_vec().into_boxed_slice())
+    }
+
+    fn jwk(&self) -> serde_json::Value {
+        let (x, y) = p256_coordinates(self.verifying_key());
+
+        serde_json::json!({
+            "kty": "EC",
+            "alg": Self::ALG,
+            "crv": "P-256",
+            "x": x,
+            "y": y,
+            "use": "sig",
+            "kid": self.key_id(),
+        })
+    }
+
+    /// The JWK thumbprint of the verifying key, see RFC7638.
+    fn key_id(&self) -> Option<String> {
+        let (x, y) = p256_coordinates(self.verifying_key());
+
+        Some(thumbprint(format!(
+            r#"{{"crv":"P-256","kty":"EC","x":"{x}","y":"{y}"}}"#
+        )))
+    }
+}
+
+/// Returns the base64url encoded coordinates of `key`, see RFC7518, Section 6.2.1.
+fn p256_coordinates(key: &p256::ecdsa::VerifyingKey) -> (String, String) {
+    let point = key.to_encoded_point(false);
+
+    (
+        Base64UrlUnpadded::encode_string(point.x().expect("uncompressed point to have x")),
+        Base64UrlUnpadded::encode_string(point.y().expect("uncompressed point to have y")),
+    )
+}
+
+impl Key for p256::ecdsa::SigningKey {
+    const ALG: &'static str = "ES256";
+}
+
+impl VerifyingKey for p256::ecdsa::VerifyingKey {
+    fn is_valid_signature(&self, message: &[u8], signature: Vec<u8>) -> bool {
+        use p256::ecdsa::signature::Verifier as _;
+
+        let Ok(signature) = p256::ecdsa::Signature::from_slice(&signature) else {
+            return false;
+        };
+
+        self.verify(message, &signature).is_ok()
+    }
+}
+
+impl Key for p256::ecdsa::VerifyingKey {
+    const ALG: &'static str = "ES256";
+}
+
+/// A JSON Web Key Set, see RFC7517, Section 5, as published by the PubHubs servers
+/// at [crate::api::JWKS_PATH].
+///
+/// Keys of unsupported types are ignored when deserializing.
+#[derive(Debug, Clone, Default)]
+pub struct Jwks {
+    pub keys: Vec<Jwk>,
+}
+
+/// A public key from a [Jwks] that we can verify JWTs with.
+#[derive(Debug, Clone)]
+pub struct Jwk {
+    pub kid: Option<String>,
+    pub key: JwkKey,
+}
+
+/// The supported types of public keys, as found in a [Jwk], or loaded using [JwkKey::from_pem].
+#[derive(Debug, Clone)]
+pub enum JwkKey {
+    Ed25519(ed25519_dalek::VerifyingKey),
+    RS256(rsa::pkcs1v15::VerifyingKey<sha2::Sha256>),
+    ES256(p256::ecdsa::VerifyingKey),
+}
+
+impl JwkKey {
+    /// Loads an RSA or P-256 public key from its PEM encoding, such as a Yivi server's
+    /// `jwt_pubkey`.  RSA keys may be encoded using SPKI ("PUBLIC KEY") or PKCS#1 ("RSA PUBLIC
+    /// KEY"); P-256 keys must be encoded using SPKI.
+    ///
+    /// RSA keys are used with RS256, and P-256 keys with ES256.
+    pub fn from_pem(pem: &str) -> Result<JwkKey, Error> {
+        use p256::pkcs8::DecodePublicKey as _;
+        use rsa::pkcs1::DecodeRsaPublicKey as _;
+
+        if let Ok(key) = rsa::RsaPublicKey::from_public_key_pem(pem)
+            .or_else(|_| rsa::RsaPublicKey::from_pkcs1_pem(pem))
+        {
+            return rsa_verifying_key(key).ok_or(Error::InvalidPublicKey);
+        }
+
+        if let Ok(key) = p256::ecdsa::VerifyingKey::from_public_key_pem(pem) {
+            return Ok(JwkKey::ES256(key));
+        }
+
+        Err(Error::InvalidPublicKey)
+    }
+
+    /// Like [JWT::open], but uses the algorithm belonging to this key.
+    pub fn open<C, D: for<'de> DeserializeSeed<'de, Value = C>, R>(
+        &self,
+        jwt: &JWT<C>,
+        validation: &Validation,
+        seed: D,
+        consumer: impl FnOnce(C) -> R,
+    ) -> Result<R, Error> {
+        match self {
+            JwkKey::Ed25519(key) => jwt.open(key, validation, seed, consumer),
+            JwkKey::RS256(key) => jwt.open(key, validation, seed, consumer),
+            JwkKey::ES256(key) => jwt.open(key, validation, seed, consumer),
+        }
+    }
+
+    /// Like [JwkKey::open], but simply returns the claims, see [JWT::open_owned].
+    pub fn open_owned<C: serde::de::DeserializeOwned>(
+        &self,
+        jwt: &JWT<C>,
+        validation: &Validation,
+    ) -> Result<C, Error> {
+        self.open(jwt, validation, PhantomData::<C>, |claims| claims)
+    }
+}
+
+/// Minimum size of the modulus of RSA keys we accept, in bits, see RFC7518, Section 3.3.
+const MIN_RSA_BITS: usize = 2048;
+
+/// Turns `key` into a [JwkKey::RS256], provided it is not too small.
+fn rsa_verifying_key(key: rsa::RsaPublicKey) -> Option<JwkKey> {
+    use rsa::traits::PublicKeyParts as _;
+
+    if key.size() * 8 < MIN_RSA_BITS {
+        return None;
+    }
+
+    Some(JwkKey::RS256(rsa::pkcs1v15::VerifyingKey::new(key)))
+}
+
+impl Jwks {
+    /// Finds the key with the given `kid`.  If `kid` is `None`, the key set must contain only one
+    /// key.
+    pub fn find(&self, kid: Option<&str>) -> Result<&Jwk, Error> {
+        let mut candidates = self
+            .keys
+            .iter()
+            .filter(|jwk| kid.is_none() || jwk.kid.as_deref() == kid);
+
+        match (candidates.next(), candidates.next()) {
+            (Some(jwk), None) => Ok(jwk),
+            _ => Err(Error::UnknownKeyId(kid.map(str::to_string))),
+        }
+    }
+
+    /// Like [JWT::open], but uses the key from this set with the `kid` from the JWT's header.
+    pub fn open<C, D: for<'de> DeserializeSeed<'de, Value = C>, R>(
+        &self,
+        jwt: &JWT<C>,
+        validation: &Validation,
+        seed: D,
+        consumer: impl FnOnce(C) -> R,
+    ) -> Result<R, Error> {
+        let kid = jwt.key_id()?;
+
+        self.find(kid.as_deref())?
+            .key
+            .open(jwt, validation, seed, consumer)
+    }
+
+    /// Like [Jwks::open], but simply returns the claims, see [JWT::open_owned].
+    pub fn open_owned<C: serde::de::DeserializeOwned>(
+        &self,
+        jwt: &JWT<C>,
+        validation: &Validation,
+    ) -> Result<C, Error> {
+        self.open(jwt, validation, PhantomData::<C>, |claims| claims)
+    }
+}
+
+impl<'de> Deserialize<'de> for Jwks {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct RawJwks {
+            keys: Vec<serde_json::Value>,
+        }
+
+        Ok(Jwks {
+            keys: RawJwks::deserialize(d)?
+                .keys
+                .into_iter()
+                .filter_map(Jwk::from_json)
+                .collect(),
+        })
+    }
+}
+
+impl Jwk {
+    /// Parses a JSON Web Key, returning `None` if it's not supported.
+    fn from_json(value: serde_json::Value) -> Option<Jwk> {
+        #[derive(Deserialize)]
+        struct RawJwk {
+            kty: String,
+            crv: Option<String>,
+            x: Option<String>,
+            y: Option<String>,
+            n: Option<String>,
+            e: Option<String>,
+            alg: Option<String>,
+            #[serde(rename = "use")]
+            use_: Option<String>,
+            kid: Option<String>,
+        }
+
+        let raw: RawJwk = serde_json::from_value(value).ok()?;
+
+        if raw.use_.as_ref().is_some_and(|u| u != "sig") {
+            return None;
+        }
+
+        let alg_is = |expected: &str| raw.alg.as_ref().map_or(true, |alg| alg == expected);
+        let decode = |param: &Option<String>| Base64UrlUnpadded::decode_vec(param.as_ref()?).ok();
+
+        let key = match (raw.kty.as_str(), raw.crv.as_deref()) {
+            ("OKP", Some("Ed25519")) => {
+                if !alg_is(<ed25519_dalek::VerifyingKey as Key>::ALG) {
+                    return None;
+                }
+
+                let x: [u8; 32] = decode(&raw.x)?.try_into().ok()?;
+
+                JwkKey::Ed25519(ed25519_dalek::VerifyingKey::from_bytes(&x).ok()?)
+            }
+            ("RSA", None) => {
+                if !alg_is(<rsa::pkcs1v15::VerifyingKey<sha2::Sha256> as Key>::ALG) {
+                    return None;
+                }
+
+                rsa_verifying_key(
+                    rsa::RsaPublicKey::new(
+                        rsa::BigUint::from_bytes_be(&decode(&raw.n)?),
+                        rsa::BigUint::from_bytes_be(&decode(&raw.e)?),
+                    )
+                    .ok()?,
+                )?
+            }
+            ("EC", Some("P-256")) => {
+                if !alg_is(<p256::ecdsa::VerifyingKey as Key>::ALG) {
+                    return None;
+                }
+
+                let x: [u8; 32] = decode(&raw.x)?.try_into().ok()?;
+                let y: [u8; 32] = decode(&raw.y)?.try_into().ok()?;
+
+                JwkKey::ES256(
+                    p256::ecdsa::VerifyingKey::from_encoded_point(
+                        &p256::EncodedPoint::from_affine_coordinates(&x.into(), &y.into(), false),
+                    )
+                    .ok()?,
+                )
+            }
+            _ => return None,
+        };
+
+        Some(Jwk { kid: raw.kid, key })
+    }
+}
+
 /// Key for SHA256 based HMAC
 pub struct HS256(pub Vec<u8>);
 
@@ -411,6 +1018,317 @@ impl Key for HS256 {
     const ALG: &'static str = "HS256";
 }
 
+/// Wrapper around [String] to indicate it should be interpretted as an encrypted token with
+/// claims `C`, in the compact serialization of RFC7516 (JWE), using direct encryption (`dir`)
+/// with XChaCha20-Poly1305 (`XC20P`).
+///
+/// Use it for tokens with claims that should not be readable by the parties relaying them, such
+/// as the user's browser.  Note that anyone holding the [EncryptionKey] can create such tokens,
+/// so when the recipient needs to know who created the token, use `JWE<JWT<C>>` instead, see
+/// [JWE::create_signed].
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct JWE<C> {
+    inner: String,
+
+    #[serde(skip)]
+    phantom: PhantomData<C>,
+}
+
+impl<C> From<String> for JWE<C> {
+    fn from(s: String) -> Self {
+        Self {
+            inner: s,
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// Symmetric key to create and open [JWE]s with.
+#[derive(Clone)]
+pub struct EncryptionKey {
+    key: chacha20poly1305::Key,
+
+    /// Put in the header of [JWE]s created with this key, so that the right key can be selected,
+    /// see [JWE::key_id].
+    kid: Option<String>,
+}
+
+impl EncryptionKey {
+    pub fn new(key: chacha20poly1305::Key, kid: Option<String>) -> Self {
+        Self { key, kid }
+    }
+
+    /// Generates a random key with the given `kid`.
+    pub fn random(kid: Option<String>) -> Self {
+        use aead::KeyInit as _;
+
+        Self::new(
+            chacha20poly1305::XChaCha20Poly1305::generate_key(&mut aead::OsRng),
+            kid,
+        )
+    }
+
+    pub fn key_id(&self) -> Option<&str> {
+        self.kid.as_deref()
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptionKey")
+            .field("kid", &self.kid)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: Serialize> JWE<C> {
+    /// Creates a JWE by encrypting `claims` using `key`.
+    pub fn create(claims: &C, key: &EncryptionKey) -> Result<JWE<C>, Error> {
+        Self::encrypt(
+            &serde_json::to_vec(claims).map_err(Error::SerializingClaims)?,
+            None,
+            key,
+        )
+    }
+}
+
+impl<C: Serialize> JWE<JWT<C>> {
+    /// Signs the `claims` using `signing_key`, and encrypts the resulting JWT using
+    /// `encryption_key`, yielding a nested JWT, see RFC7519, Section 5.2.  Use
+    /// [JWE::open_signed] to revert.
+    pub fn create_signed<SK: SigningKey>(
+        claims: &C,
+        signing_key: &SK,
+        encryption_key: &EncryptionKey,
+    ) -> Result<Self, Error> {
+        Self::encrypt(
+            JWT::create(claims, signing_key)?.as_str().as_bytes(),
+            Some("JWT"),
+            encryption_key,
+        )
+    }
+}
+
+impl<C> JWE<C> {
+    /// Returns the JWE as string.
+    pub fn as_str(&self) -> &str {
+        &self.inner
+    }
+
+    /// Returns the `kid` from the header of this JWE, without decrypting it.
+    /// Used to select the [EncryptionKey] to open it with.
+    pub fn key_id(&self) -> Result<Option<String>, Error> {
+        let header_part: &str = self.inner.split('.').next().unwrap();
+
+        let header_vec: Vec<u8> =
+            Base64UrlUnpadded::decode_vec(header_part).map_err(Error::InvalidBase64)?;
+
+        let header: JweHeader =
+            serde_json::from_slice(&header_vec).map_err(Error::DeserializingHeader)?;
+
+        Ok(header.kid.map(Cow::into_owned))
+    }
+
+    /// Decrypts this JWE using `key`, checks the [CommonClaims] according to `validation`, and
+    /// deserializes the claims using `seed`, passing them to `consumer`, like [JWT::open].
+    pub fn open<D: for<'de> DeserializeSeed<'de, Value = C>, R>(
+        &self,
+        key: &EncryptionKey,
+        validation: &Validation,
+        seed: D,
+        consumer: impl FnOnce(C) -> R,
+    ) -> Result<R, Error> {
+        let claims_vec = zeroize::Zeroizing::new(self.decrypt(key, None)?);
+
+        let common_claims: CommonClaims =
+            serde_json::from_slice(&claims_vec).map_err(Error::DeserializingClaims)?;
+
+        validation.check(&common_claims)?;
+
+        let mut d = serde_json::Deserializer::from_slice(&claims_vec);
+
+        let claims = seed
+            .deserialize(&mut d)
+            .map_err(Error::DeserializingClaims)?;
+
+        Ok(consumer(claims))
+    }
+
+    fn encrypt(plaintext: &[u8], cty: Option<&str>, key: &EncryptionKey) -> Result<Self, Error> {
+        use aead::{Aead as _, AeadCore as _, KeyInit as _};
+        use chacha20poly1305::XChaCha20Poly1305;
+
+        let header: String = Base64UrlUnpadded::encode_string(
+            &serde_json::to_vec(&JweHeader {
+                alg: Cow::Borrowed(JWE_ALG),
+                enc: Cow::Borrowed(JWE_ENC),
+                kid: key.kid.as_deref().map(Cow::Borrowed),
+                cty: cty.map(Cow::Borrowed),
+            })
+            .map_err(Error::SerializingHeader)?,
+        );
+
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut aead::OsRng);
+        let mut ciphertext = XChaCha20Poly1305::new(&key.key)
+            .encrypt(
+                &nonce,
+                aead::Payload {
+                    msg: plaintext,
+                    // see RFC7516, Section 5.1, step 14
+                    aad: header.as_bytes(),
+                },
+            )
+            .expect("encryption with XChaCha20Poly1305 to be infallible for small plaintexts");
+
+        let tag = ciphertext.split_off(ciphertext.len() - TAG_LEN);
+
+        // the encrypted key is empty for direct encryption, see RFC7516, Section 4.1.1.
+        Ok(JWE::from(format!(
+            "{header}..{}.{}.{}",
+            Base64UrlUnpadded::encode_string(&nonce),
+            Base64UrlUnpadded::encode_string(&ciphertext),
+            Base64UrlUnpadded::encode_string(&tag),
+        )))
+    }
+
+    /// Returns the plaintext of this JWE, provided its header is as expected.
+    fn decrypt(&self, key: &EncryptionKey, expected_cty: Option<&str>) -> Result<Vec<u8>, Error> {
+        use aead::{Aead as _, KeyInit as _};
+        use chacha20poly1305::XChaCha20Poly1305;
+
+        let parts: Vec<&str> = self.inner.split('.').collect();
+
+        let [header_part, encrypted_key, nonce, ciphertext, tag] = parts[..] else {
+            return Err(Error::MalformedJwe);
+        };
+
+        let header_vec: Vec<u8> =
+            Base64UrlUnpadded::decode_vec(header_part).map_err(Error::InvalidBase64)?;
+
+        let header: JweHeader =
+            serde_json::from_slice(&header_vec).map_err(Error::DeserializingHeader)?;
+
+        for (got, expected) in [(&header.alg, JWE_ALG), (&header.enc, JWE_ENC)] {
+            if got != expected {
+                return Err(Error::UnexpectedAlgorithm {
+                    got: got.to_string(),
+                    expected: expected.to_string(),
+                });
+            }
+        }
+
+        if header.cty.as_deref() != expected_cty {
+            return Err(Error::UnexpectedContentType {
+                got: header.cty.map(Cow::into_owned),
+                expected: expected_cty.map(str::to_string),
+            });
+        }
+
+        if header.kid.as_deref() != key.key_id() {
+            return Err(Error::UnknownKeyId(header.kid.map(Cow::into_owned)));
+        }
+
+        if !encrypted_key.is_empty() {
+            return Err(Error::MalformedJwe);
+        }
+
+        let nonce: Vec<u8> = Base64UrlUnpadded::decode_vec(nonce).map_err(Error::InvalidBase64)?;
+
+        if nonce.len() != NONCE_LEN {
+            return Err(Error::MalformedJwe);
+        }
+
+        let mut ciphertext: Vec<u8> =
+            Base64UrlUnpadded::decode_vec(ciphertext).map_err(Error::InvalidBase64)?;
+        ciphertext.extend(Base64UrlUnpadded::decode_vec(tag).map_err(Error::InvalidBase64)?);
+
+        XChaCha20Poly1305::new(&key.key)
+            .decrypt(
+                nonce.as_slice().into(),
+                aead::Payload {
+                    msg: &ciphertext,
+                    aad: header_part.as_bytes(),
+                },
+            )
+            .map_err(|_| Error::Decryption)
+    }
+}
+
+impl<C: serde::de::DeserializeOwned> JWE<C> {
+    /// Like [JWE::open], but simply returns the claims, which do not borrow from the JWE.
+    pub fn open_owned(&self, key: &EncryptionKey, validation: &Validation) -> Result<C, Error> {
+        self.open(key, validation, PhantomData::<C>, |claims| claims)
+    }
+}
+
+impl<C> JWE<JWT<C>> {
+    /// Decrypts this nested JWT using `encryption_key`, and opens the resulting JWT using
+    /// `verifying_key`, see [JWT::open].  Reverse of [JWE::create_signed].
+    pub fn open_signed<VK: VerifyingKey, D: for<'de> DeserializeSeed<'de, Value = C>, R>(
+        &self,
+        encryption_key: &EncryptionKey,
+        verifying_key: &VK,
+        validation: &Validation,
+        seed: D,
+        consumer: impl FnOnce(C) -> R,
+    ) -> Result<R, Error> {
+        let jwt: JWT<C> = String::from_utf8(self.decrypt(encryption_key, Some("JWT"))?)
+            .map_err(|_| Error::MalformedJwe)?
+            .into();
+
+        jwt.open(verifying_key, validation, seed, consumer)
+    }
+}
+
+impl<C: serde::de::DeserializeOwned> JWE<JWT<C>> {
+    /// Like [JWE::open_signed], but simply returns the claims.
+    pub fn open_signed_owned<VK: VerifyingKey>(
+        &self,
+        encryption_key: &EncryptionKey,
+        verifying_key: &VK,
+        validation: &Validation,
+    ) -> Result<C, Error> {
+        self.open_signed(
+            encryption_key,
+            verifying_key,
+            validation,
+            PhantomData::<C>,
+            |claims| claims,
+        )
+    }
+}
+
+/// Value for `alg` in the header of a [JWE]: direct encryption with a shared symmetric key.
+const JWE_ALG: &str = "dir";
+
+/// Value for `enc` in the header of a [JWE], see draft-amringer-jose-chacha.
+const JWE_ENC: &str = "XC20P";
+
+/// Length of the nonce ('initialization vector') of a [JWE].
+const NONCE_LEN: usize = 24;
+
+/// Length of the authentication tag of a [JWE].
+const TAG_LEN: usize = 16;
+
+/// Represents a JWE header.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct JweHeader<'a> {
+    #[serde(borrow)]
+    alg: Cow<'a, str>,
+
+    #[serde(borrow)]
+    enc: Cow<'a, str>,
+
+    #[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+    kid: Option<Cow<'a, str>>,
+
+    /// Set to `JWT` for nested JWTs, see RFC7519, Section 5.2.
+    #[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+    cty: Option<Cow<'a, str>>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -462,11 +1380,453 @@ mod tests {
             serde_json::from_str::<Header>(r#"{"alg":"", "unknown_field": ""}"#)
                 .unwrap_err()
                 .to_string(),
-            "unknown field `unknown_field`, expected `typ` or `alg` at line 1 column 26"
+            "unknown field `unknown_field`, expected one of `typ`, `alg`, `kid` at line 1 column 26"
                 .to_string()
         );
     }
 
+    #[test]
+    fn test_open() {
+        let key = HS256(b"secret".to_vec());
+
+        let jwt = JWT::create(
+            &serde_json::json!({
+                "iat": 1000,
+                "nbf": 1000,
+                "exp": 2000,
+                "iss": "phc",
+                "aud": ["hub", "other_hub"],
+                "jti": "1",
+                "msg": "hello",
+            }),
+            &key,
+        )
+        .unwrap();
+
+        #[derive(Deserialize)]
+        struct Claims {
+            msg: String,
+        }
+        let jwt: JWT<Claims> = JWT::from(jwt.inner);
+
+        let val = Validation {
+            clock: || 1500,
+            iss: Some("phc".to_string()),
+            aud: Some("hub".to_string()),
+            require_jti: true,
+            ..Default::default()
+        };
+
+        assert_eq!(jwt.open_owned(&key, &val).unwrap().msg, "hello");
+
+        assert!(matches!(
+            jwt.open_owned(&HS256(b"other secret".to_vec()), &val),
+            Err(Error::InvalidSignature)
+        ));
+
+        // time-based claims, with leeway
+        for (clock, ok) in [
+            ((|| 939) as fn() -> u64, false),
+            (|| 940, true),
+            (|| 2060, true),
+            (|| 2061, false),
+        ] {
+            assert_eq!(
+                jwt.open_owned(
+                    &key,
+                    &Validation {
+                        clock,
+                        ..val.clone()
+                    }
+                )
+                .is_ok(),
+                ok
+            );
+        }
+        assert!(matches!(
+            jwt.open_owned(
+                &key,
+                &Validation {
+                    clock: || 2061,
+                    ..val.clone()
+                }
+            ),
+            Err(Error::Expired {
+                exp: 2000,
+                now: 2061
+            })
+        ));
+        assert!(matches!(
+            jwt.open_owned(
+                &key,
+                &Validation {
+                    clock: || 939,
+                    ..val.clone()
+                }
+            ),
+            Err(Error::NotYetValid {
+                nbf: 1000,
+                now: 939
+            })
+        ));
+
+        // issuer and audience
+        assert!(matches!(
+            jwt.open_owned(
+                &key,
+                &Validation {
+                    iss: Some("transcryptor".to_string()),
+                    ..val.clone()
+                }
+            ),
+            Err(Error::UnexpectedIssuer { .. })
+        ));
+        assert!(matches!(
+            jwt.open_owned(
+                &key,
+                &Validation {
+                    aud: Some("third_hub".to_string()),
+                    ..val.clone()
+                }
+            ),
+            Err(Error::UnexpectedAudience { .. })
+        ));
+        assert!(matches!(
+            jwt.open_owned(
+                &key,
+                &Validation {
+                    aud: None,
+                    ..val.clone()
+                }
+            ),
+            Err(Error::UnexpectedAudience { expected: None })
+        ));
+    }
+
+    #[test]
+    fn test_jwks() {
+        let sk1 = ed25519_dalek::SigningKey::from_bytes(&[1; 32]);
+        let sk2 = ed25519_dalek::SigningKey::from_bytes(&[2; 32]);
+
+        let jwks: Jwks = serde_json::from_value(serde_json::json!({
+            "keys": [
+                sk1.jwk(),
+                sk2.jwk(),
+                // unsupported and invalid keys are ignored
+                { "kty": "RSA", "n": "AQAB", "e": "AQAB", "kid": "rsa" },
+                { "kty": "OKP", "crv": "X25519", "x": "", "kid": "x25519" },
+            ]
+        }))
+        .unwrap();
+        assert_eq!(jwks.keys.len(), 2);
+
+        #[derive(Serialize, Deserialize)]
+        struct Claims {
+            exp: u64,
+            msg: String,
+        }
+
+        let claims = Claims {
+            exp: get_current_timestamp() + 60,
+            msg: "hello".to_string(),
+        };
+        let jwt1 = JWT::create(&claims, &sk1).unwrap();
+        let jwt2 = JWT::create(&claims, &sk2).unwrap();
+
+        assert_eq!(jwt1.key_id().unwrap(), sk1.key_id());
+        assert_ne!(jwt1.key_id().unwrap(), jwt2.key_id().unwrap());
+
+        let val = Validation::default();
+        assert_eq!(jwks.open_owned(&jwt1, &val).unwrap().msg, "hello");
+        assert_eq!(jwks.open_owned(&jwt2, &val).unwrap().msg, "hello");
+
+        // without kid, the key set must contain exactly one key
+        let jwt_without_kid = JWT::create(&claims, &HS256(vec![])).unwrap();
+        assert_eq!(jwt_without_kid.key_id().unwrap(), None);
+        assert!(matches!(
+            jwks.open_owned(&jwt_without_kid, &val),
+            Err(Error::UnknownKeyId(None))
+        ));
+
+        let jwks1 = Jwks {
+            keys: vec![jwks.keys[0].clone()],
+        };
+        assert!(matches!(
+            jwks1.open_owned(&jwt2, &val),
+            Err(Error::UnknownKeyId(Some(_)))
+        ));
+    }
+
+    #[test]
+    fn test_rs256_and_es256() {
+        use rsa::pkcs1::DecodeRsaPrivateKey as _;
+
+        // the development key of the Yivi server
+        let rsa_sk = rsa::pkcs1v15::SigningKey::<sha2::Sha256>::new(
+            rsa::RsaPrivateKey::from_pkcs1_pem(include_str!("../../../docker_yivi/jwt.priv"))
+                .unwrap(),
+        );
+        let rsa_pk = JwkKey::from_pem(include_str!("../../../docker_yivi/jwt.pub")).unwrap();
+        assert!(matches!(rsa_pk, JwkKey::RS256(_)));
+
+        let ec_sk = p256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+        let ec_pk = {
+            use p256::pkcs8::{EncodePublicKey as _, LineEnding};
+
+            JwkKey::from_pem(
+                &ec_sk
+                    .verifying_key()
+                    .to_public_key_pem(LineEnding::LF)
+                    .unwrap(),
+            )
+            .unwrap()
+        };
+        assert!(matches!(ec_pk, JwkKey::ES256(_)));
+
+        assert!(matches!(
+            JwkKey::from_pem(include_str!("../../../docker_yivi/jwt.priv")),
+            Err(Error::InvalidPublicKey)
+        ));
+
+        #[derive(Serialize, Deserialize)]
+        struct Claims {
+            exp: u64,
+            msg: String,
+        }
+
+        let claims = Claims {
+            exp: get_current_timestamp() + 60,
+            msg: "hello".to_string(),
+        };
+        let val = Validation::default();
+
+        let rsa_jwt = JWT::create(&claims, &rsa_sk).unwrap();
+        let ec_jwt = JWT::create(&claims, &ec_sk).unwrap();
+
+        assert_eq!(rsa_pk.open_owned(&rsa_jwt, &val).unwrap().msg, "hello");
+        assert_eq!(ec_pk.open_owned(&ec_jwt, &val).unwrap().msg, "hello");
+
+        assert!(matches!(
+            rsa_pk.open_owned(&ec_jwt, &val),
+            Err(Error::UnexpectedAlgorithm { .. })
+        ));
+        assert!(matches!(
+            ec_pk.open_owned(&rsa_jwt, &val),
+            Err(Error::UnexpectedAlgorithm { .. })
+        ));
+
+        // tampering with the claims invalidates the signature
+        for jwt in [&rsa_jwt, &ec_jwt] {
+            let (header, rest) = jwt.as_str().split_once('.').unwrap();
+            let (_, signature) = rest.split_once('.').unwrap();
+            let tampered: JWT<Claims> = format!(
+                "{header}.{}.{signature}",
+                Base64UrlUnpadded::encode_string(br#"{"exp":99999999999,"msg":"bye"}"#)
+            )
+            .into();
+
+            assert!(matches!(
+                rsa_pk.open_owned(&tampered, &val),
+                Err(Error::InvalidSignature | Error::UnexpectedAlgorithm { .. })
+            ));
+            assert!(matches!(
+                ec_pk.open_owned(&tampered, &val),
+                Err(Error::InvalidSignature | Error::UnexpectedAlgorithm { .. })
+            ));
+        }
+
+        // keys can be found in a key set by their thumbprint
+        let jwks: Jwks = serde_json::from_value(serde_json::json!({
+            "keys": [ rsa_sk.jwk(), ec_sk.jwk() ]
+        }))
+        .unwrap();
+        assert_eq!(jwks.keys.len(), 2);
+        assert_ne!(rsa_sk.key_id(), ec_sk.key_id());
+
+        assert_eq!(jwks.open_owned(&rsa_jwt, &val).unwrap().msg, "hello");
+        assert_eq!(jwks.open_owned(&ec_jwt, &val).unwrap().msg, "hello");
+    }
+
+    #[test]
+    fn test_jwe() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        struct Claims {
+            exp: u64,
+            secret: String,
+        }
+
+        let claims = Claims {
+            exp: get_current_timestamp() + 60,
+            secret: "pseudonym".to_string(),
+        };
+        let val = Validation::default();
+
+        let key = EncryptionKey::random(Some("key1".to_string()));
+        let other_key = EncryptionKey::random(Some("key1".to_string()));
+        let key2 = EncryptionKey::random(Some("key2".to_string()));
+
+        let jwe = JWE::create(&claims, &key).unwrap();
+        assert_eq!(jwe.as_str().split('.').count(), 5);
+        let ciphertext =
+            Base64UrlUnpadded::decode_vec(jwe.as_str().split('.').nth(3).unwrap()).unwrap();
+        assert!(!ciphertext.windows(9).any(|w| w == b"pseudonym"));
+        assert_eq!(jwe.key_id().unwrap(), Some("key1".to_string()));
+        assert!(!format!("{key:?}").contains(&format!("{:?}", key.key)));
+
+        assert_eq!(jwe.open_owned(&key, &val).unwrap(), claims);
+        assert!(matches!(
+            jwe.open_owned(&other_key, &val),
+            Err(Error::Decryption)
+        ));
+        assert!(matches!(
+            jwe.open_owned(&key2, &val),
+            Err(Error::UnknownKeyId(Some(kid))) if kid == "key1"
+        ));
+
+        // the header is authenticated
+        let (header, rest) = jwe.as_str().split_once('.').unwrap();
+        let mut header: serde_json::Value =
+            serde_json::from_slice(&Base64UrlUnpadded::decode_vec(header).unwrap()).unwrap();
+        header["kid"] = "key2".into();
+        let tampered: JWE<Claims> = format!(
+            "{}.{rest}",
+            Base64UrlUnpadded::encode_string(&serde_json::to_vec(&header).unwrap())
+        )
+        .into();
+        assert!(matches!(
+            tampered.open_owned(&key2, &val),
+            Err(Error::Decryption)
+        ));
+
+        let jwe_without_encrypted_key: JWE<Claims> = jwe.as_str().replacen("..", ".", 1).into();
+        assert!(matches!(
+            jwe_without_encrypted_key.open_owned(&key, &val),
+            Err(Error::MalformedJwe)
+        ));
+
+        // expiry is checked
+        let expired = JWE::create(
+            &Claims {
+                exp: get_current_timestamp() - 3600,
+                ..claims
+            },
+            &key,
+        )
+        .unwrap();
+        assert!(matches!(
+            expired.open_owned(&key, &val),
+            Err(Error::Expired { .. })
+        ));
+    }
+
+    #[test]
+    fn test_signed_jwe() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        struct Claims {
+            exp: u64,
+            secret: String,
+        }
+
+        let claims = Claims {
+            exp: get_current_timestamp() + 60,
+            secret: "pseudonym".to_string(),
+        };
+        let val = Validation::default();
+
+        let ek = EncryptionKey::random(None);
+        let sk = ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng);
+        let other_sk = ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng);
+
+        let jwe = JWE::create_signed(&claims, &sk, &ek).unwrap();
+        assert_eq!(jwe.key_id().unwrap(), None);
+
+        assert_eq!(
+            jwe.open_signed_owned(&ek, &sk.verifying_key(), &val)
+                .unwrap(),
+            claims
+        );
+        assert!(matches!(
+            jwe.open_signed_owned(&ek, &other_sk.verifying_key(), &val),
+            Err(Error::InvalidSignature)
+        ));
+
+        // a nested jwt can't be opened as a plain jwe, and vice versa
+        let nested: JWE<Claims> = jwe.as_str().to_string().into();
+        assert!(matches!(
+            nested.open_owned(&ek, &val),
+            Err(Error::UnexpectedContentType { .. })
+        ));
+        let plain: JWE<JWT<Claims>> = JWE::create(&claims, &ek)
+            .unwrap()
+            .as_str()
+            .to_string()
+            .into();
+        assert!(matches!(
+            plain.open_signed_owned(&ek, &sk.verifying_key(), &val),
+            Err(Error::UnexpectedContentType { .. })
+        ));
+    }
+
+    #[test]
+    fn test_validation_missing_claims() {
+        let val = Validation {
+            clock: || 0,
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            val.check(&CommonClaims::default()),
+            Err(Error::MissingClaim("exp"))
+        ));
+
+        let claims = CommonClaims {
+            exp: Some(NumericDate::new(10)),
+            ..Default::default()
+        };
+        assert!(val.check(&claims).is_ok());
+
+        for (val, missing) in [
+            (
+                Validation {
+                    iss: Some("phc".to_string()),
+                    ..val.clone()
+                },
+                "iss",
+            ),
+            (
+                Validation {
+                    aud: Some("hub".to_string()),
+                    ..val.clone()
+                },
+                "aud",
+            ),
+            (
+                Validation {
+                    require_jti: true,
+                    ..val.clone()
+                },
+                "jti",
+            ),
+        ] {
+            assert!(matches!(val.check(&claims), Err(Error::MissingClaim(m)) if m == missing));
+        }
+
+        assert!(matches!(
+            val.check(&CommonClaims {
+                iat: Some(NumericDate::new(61)),
+                ..claims.clone()
+            }),
+            Err(Error::IssuedInTheFuture { iat: 61, now: 0 })
+        ));
+
+        assert_eq!(
+            serde_json::from_str::<CommonClaims>(r#"{"aud": "hub", "other": 1}"#)
+                .unwrap()
+                .aud,
+            Some(Audience::Single("hub".to_string()))
+        );
+    }
+
     #[test]
     fn test_numericdate() {
         assert!(NumericDate::deserialize(serde_json::json!(0u64)).is_ok());
@@ -1,4 +1,6 @@
 pub mod fmt_ext;
 pub mod jwt;
+pub mod sealed_box;
+pub mod secret;
 pub mod serde_ext;
 pub mod task;
@@ -0,0 +1,165 @@
+//! Anonymous public key encryption to the holder of an ed25519 signing key.
+//!
+//! The recipient's [ed25519_dalek::VerifyingKey] is converted to an X25519 public key,
+//! with which an ephemeral X25519 key agrees on a shared secret, from which in turn the key for
+//! [XChaCha20Poly1305] is derived.  Only the holder of the corresponding
+//! [ed25519_dalek::SigningKey] can [unseal] the result.
+//!
+//! This way secrets can be delivered to a hub that advertises its verifying key
+//! (see [crate::api::hub::InfoResp]) without the hub needing a separate encryption key.
+
+use aead::{Aead as _, AeadCore as _, KeyInit as _};
+use chacha20poly1305::XChaCha20Poly1305;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use rand::RngCore as _;
+use sha2::Digest as _;
+use zeroize::Zeroize as _;
+
+/// Length of the ephemeral X25519 public key at the start of a sealed box.
+const EPHEMERAL_KEY_LEN: usize = 32;
+
+/// Length of the nonce following the ephemeral public key in a sealed box.
+const NONCE_LEN: usize = 24;
+
+/// Why a sealed box could not be created or opened.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    #[error("the recipient's key is of low order")]
+    LowOrderKey,
+
+    #[error("sealed box is too short")]
+    TooShort,
+
+    #[error(
+        "sealed box could not be decrypted - wrong key, wrong associated data, or tampered with"
+    )]
+    Decryption,
+}
+
+/// Encrypts `plaintext` for the holder of the signing key belonging to `recipient`,
+/// authenticating (but not encrypting) the associated data `aad` too.  Use [unseal] to revert.
+///
+/// The result consists of the ephemeral X25519 public key, the nonce, and the ciphertext.
+pub fn seal(
+    plaintext: &[u8],
+    recipient: &ed25519_dalek::VerifyingKey,
+    aad: impl AsRef<[u8]>,
+) -> Result<Vec<u8>, Error> {
+    let recipient = recipient.to_montgomery();
+
+    let mut ephemeral_secret = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut ephemeral_secret);
+    let ephemeral = MontgomeryPoint::mul_base_clamped(ephemeral_secret);
+    let shared = recipient.mul_clamped(ephemeral_secret);
+    ephemeral_secret.zeroize();
+
+    let cipher = derive_cipher(shared, &ephemeral, &recipient)?;
+
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut aead::OsRng);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            aead::Payload {
+                msg: plaintext,
+                aad: aad.as_ref(),
+            },
+        )
+        .expect("encryption with XChaCha20Poly1305 to be infallible for small plaintexts");
+
+    let mut result = Vec::with_capacity(EPHEMERAL_KEY_LEN + NONCE_LEN + ciphertext.len());
+    result.extend_from_slice(ephemeral.as_bytes());
+    result.extend_from_slice(&nonce);
+    result.extend_from_slice(&ciphertext);
+
+    Ok(result)
+}
+
+/// Reverse of [seal], using the `recipient`'s signing key.
+pub fn unseal(
+    sealed: &[u8],
+    recipient: &ed25519_dalek::SigningKey,
+    aad: impl AsRef<[u8]>,
+) -> Result<Vec<u8>, Error> {
+    if sealed.len() < EPHEMERAL_KEY_LEN + NONCE_LEN {
+        return Err(Error::TooShort);
+    }
+
+    let (ephemeral, rest) = sealed.split_at(EPHEMERAL_KEY_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let ephemeral = MontgomeryPoint(ephemeral.try_into().unwrap());
+
+    let mut secret = recipient.to_scalar_bytes();
+    let shared = ephemeral.mul_clamped(secret);
+    secret.zeroize();
+
+    let cipher = derive_cipher(
+        shared,
+        &ephemeral,
+        &recipient.verifying_key().to_montgomery(),
+    )?;
+
+    cipher
+        .decrypt(
+            nonce.into(),
+            aead::Payload {
+                msg: ciphertext,
+                aad: aad.as_ref(),
+            },
+        )
+        .map_err(|_| Error::Decryption)
+}
+
+/// Derives the symmetric cipher from the X25519 `shared` secret, binding it to both public keys.
+fn derive_cipher(
+    mut shared: MontgomeryPoint,
+    ephemeral: &MontgomeryPoint,
+    recipient: &MontgomeryPoint,
+) -> Result<XChaCha20Poly1305, Error> {
+    // An all-zero shared secret means one of the keys was of low order, see RFC7748, section 6.1.
+    if shared == MontgomeryPoint([0u8; 32]) {
+        return Err(Error::LowOrderKey);
+    }
+
+    let mut key = sha2::Sha256::new()
+        .chain_update(b"pubhubs sealed box v1")
+        .chain_update(shared.as_bytes())
+        .chain_update(ephemeral.as_bytes())
+        .chain_update(recipient.as_bytes())
+        .finalize();
+    shared.0.zeroize();
+
+    let cipher = XChaCha20Poly1305::new(&key);
+    key.zeroize();
+
+    Ok(cipher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seal_and_unseal() {
+        let sk = ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng);
+        let other_sk = ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng);
+
+        let sealed = seal(b"hub secret", &sk.verifying_key(), b"hub").unwrap();
+        assert_eq!(sealed.len(), EPHEMERAL_KEY_LEN + NONCE_LEN + 10 + 16);
+        assert_eq!(unseal(&sealed, &sk, b"hub").unwrap(), b"hub secret");
+
+        assert_eq!(unseal(&sealed, &other_sk, b"hub"), Err(Error::Decryption));
+        assert_eq!(unseal(&sealed, &sk, b"other hub"), Err(Error::Decryption));
+        assert_eq!(unseal(&sealed[..40], &sk, b"hub"), Err(Error::TooShort));
+
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(unseal(&tampered, &sk, b"hub"), Err(Error::Decryption));
+
+        // sealing is randomized
+        assert_ne!(
+            sealed,
+            seal(b"hub secret", &sk.verifying_key(), b"hub").unwrap()
+        );
+    }
+}
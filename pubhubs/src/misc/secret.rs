@@ -0,0 +1,80 @@
+//! Tools for handling secret material
+use subtle::ConstantTimeEq as _;
+use zeroize::Zeroize as _;
+
+/// Secret bytes, like a key or a seed, that are zeroized on drop, are compared in constant time,
+/// and are never printed.
+///
+/// Share using [std::sync::Arc] instead of cloning.
+pub struct SecretBytes {
+    inner: Vec<u8>,
+}
+
+impl SecretBytes {
+    /// Takes ownership of `bytes`, without copying them.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self { inner: bytes }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.inner
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl From<String> for SecretBytes {
+    fn from(s: String) -> Self {
+        Self::new(s.into_bytes())
+    }
+}
+
+impl AsRef<[u8]> for SecretBytes {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        self.inner.zeroize();
+    }
+}
+
+impl zeroize::ZeroizeOnDrop for SecretBytes {}
+
+/// Only reveals the length of the secret.
+impl PartialEq for SecretBytes {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner.ct_eq(&other.inner).into()
+    }
+}
+
+impl Eq for SecretBytes {}
+
+/// Does not reveal the secret.
+impl std::fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretBytes").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_bytes() {
+        let secret = SecretBytes::from("very secret".to_string());
+
+        assert_eq!(secret, SecretBytes::new(b"very secret".to_vec()));
+        assert_ne!(secret, SecretBytes::new(b"very secreT".to_vec()));
+        assert_ne!(secret, SecretBytes::new(b"very".to_vec()));
+        assert!(!format!("{secret:?}").contains("secret\""));
+        assert!(!format!("{secret:?}").contains("very"));
+    }
+}
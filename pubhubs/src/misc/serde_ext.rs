@@ -450,7 +450,9 @@ where
         assert_eq!(buf.as_ptr(), slice_ptr);
         buf.truncate(slice_len);
 
-        T::deserialize(ByteBufDeserializer::new(buf))
+        // NB. We pass borrowed bytes, because some types, like [ed25519_dalek::VerifyingKey],
+        //     only implement [serde::de::Visitor::visit_borrowed_bytes].
+        T::deserialize(serde::de::value::BorrowedBytesDeserializer::new(&buf))
     }
 }
 
@@ -525,6 +527,14 @@ impl<const N: usize> From<[u8; N]> for ByteArray<N> {
     }
 }
 
+impl<const N: usize> std::ops::Deref for ByteArray<N> {
+    type Target = [u8; N];
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
 impl<const N: usize> Serialize for ByteArray<N> {
     fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
         s.serialize_bytes(&self.inner)
@@ -562,6 +572,210 @@ impl<'de, const N: usize> Deserialize<'de> for ByteArray<N> {
     }
 }
 
+/// A secret, like a private key, from a configuration file, that is never printed, and that can
+/// be specified in one of the following ways.
+///
+/// ```yaml
+/// jwt_key: 9d61b19d...                 # inline
+/// jwt_key: { file: keys/jwt_key }      # in a file, relative to the configuration's working directory
+/// jwt_key: { env: PUBHUBS_JWT_KEY }    # in an environmental variable
+/// ```
+///
+/// The secret (with surrounding whitespace removed) is deserialized as `T` from a string, so `T` is
+/// typically a [B16] or [B64].  Secrets from files and environmental variables are only available
+/// after [Secret::load] has been called, which [crate::servers::Config::load_from_path] takes care of.
+#[derive(Clone)]
+pub struct Secret<T> {
+    source: SecretSource,
+    value: Option<T>,
+}
+
+/// Where the value of a [Secret] comes from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SecretSource {
+    Inline,
+    File(std::path::PathBuf),
+    Env(String),
+}
+
+impl<T> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            source: SecretSource::Inline,
+            value: Some(value),
+        }
+    }
+
+    pub fn source(&self) -> &SecretSource {
+        &self.source
+    }
+
+    /// Returns the value of this secret.
+    ///
+    /// # Panics
+    /// When the secret comes from a file or environmental variable and [Secret::load] has not
+    /// been called successfully.
+    pub fn value(&self) -> &T {
+        self.value
+            .as_ref()
+            .expect("secret to be loaded before being used - see Secret::load")
+    }
+
+    pub fn into_value(self) -> T {
+        self.value
+            .expect("secret to be loaded before being used - see Secret::load")
+    }
+}
+
+impl<T: serde::de::DeserializeOwned> Secret<T> {
+    /// Retrieves the value of this secret from its file or environmental variable, if it has not
+    /// been retrieved already.  Relative paths are interpretted with respect to `wd`.
+    ///
+    /// Warns when the secret's file is readable by others than its owner.
+    pub fn load(&mut self, wd: &std::path::Path) -> anyhow::Result<()> {
+        use anyhow::Context as _;
+
+        if self.value.is_some() {
+            return Ok(());
+        }
+
+        let text = zeroize::Zeroizing::new(match &self.source {
+            SecretSource::Inline => unreachable!("inline secrets are loaded when deserialized"),
+            SecretSource::File(path) => {
+                let path = wd.join(path);
+
+                let text = std::fs::read_to_string(&path)
+                    .with_context(|| format!("could not read secret from {}", path.display()))?;
+
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt as _;
+
+                    let mode = std::fs::metadata(&path)?.permissions().mode();
+
+                    if mode & 0o077 != 0 {
+                        log::warn!(
+                            "the secret in {} is accessible by others than its owner (mode {:o}); consider running `chmod go-rwx {}`",
+                            path.display(),
+                            mode & 0o777,
+                            path.display()
+                        );
+                    }
+                }
+
+                text
+            }
+            SecretSource::Env(var) => std::env::var(var).with_context(|| {
+                format!("could not read secret from environmental variable {var}")
+            })?,
+        });
+
+        self.value = Some(
+            T::deserialize(text.trim().into_deserializer())
+                .map_err(|err: serde::de::value::Error| anyhow::anyhow!("{err}"))
+                .with_context(|| format!("invalid secret from {:?}", self.source))?,
+        );
+
+        Ok(())
+    }
+}
+
+impl<T> std::ops::Deref for Secret<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value()
+    }
+}
+
+impl<T> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// Does not reveal the secret.
+impl<T> std::fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Secret")
+            .field("source", &self.source)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Does not reveal the secret.
+impl<T> Serialize for Secret<T> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str("<redacted>")
+    }
+}
+
+impl<'de, T: serde::de::DeserializeOwned> Deserialize<'de> for Secret<T> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        d.deserialize_any(SecretVisitor::<T> {
+            phantom: PhantomData,
+        })
+    }
+}
+
+struct SecretVisitor<T> {
+    phantom: PhantomData<T>,
+}
+
+impl<T> SecretVisitor<T> {
+    const SOURCES: &'static [&'static str] = &["file", "env"];
+
+    fn external<E: serde::de::Error>(source: &str, arg: String) -> Result<Secret<T>, E> {
+        let source = match source {
+            "file" => SecretSource::File(arg.into()),
+            "env" => SecretSource::Env(arg),
+            _ => return Err(E::unknown_variant(source, Self::SOURCES)),
+        };
+
+        Ok(Secret {
+            source,
+            value: None,
+        })
+    }
+}
+
+impl<'de, T: serde::de::DeserializeOwned> serde::de::Visitor<'de> for SecretVisitor<T> {
+    type Value = Secret<T>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a secret, or a map with a 'file' or 'env' key")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        T::deserialize(v.trim().into_deserializer())
+            .map(Secret::new)
+            .map_err(|err: serde::de::value::Error| E::custom(format!("invalid secret: {err}")))
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        use serde::de::Error as _;
+
+        let Some((source, arg)) = map.next_entry::<String, String>()? else {
+            return Err(A::Error::invalid_length(0, &self));
+        };
+
+        if map.next_key::<serde::de::IgnoredAny>()?.is_some() {
+            return Err(A::Error::invalid_length(2, &self));
+        }
+
+        Self::external(&source, arg)
+    }
+
+    /// Used for YAML tags, like `!file path`.
+    fn visit_enum<A: serde::de::EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        use serde::de::VariantAccess as _;
+
+        let (source, variant): (String, _) = data.variant()?;
+
+        Self::external(&source, variant.newtype_variant()?)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -574,6 +788,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn verifying_key_roundtrip() {
+        let vk: B16<ed25519_dalek::VerifyingKey> = ed25519_dalek::SigningKey::from_bytes(&[1; 32])
+            .verifying_key()
+            .into();
+
+        let json = serde_json::to_string(&vk).unwrap();
+        assert_eq!(
+            *serde_json::from_str::<B16<ed25519_dalek::VerifyingKey>>(&json).unwrap(),
+            *vk
+        );
+    }
+
+    #[test]
+    fn secret() {
+        type Key = Secret<B16<serde_bytes::ByteBuf>>;
+
+        let key: Key = serde_yaml::from_str("' 00ff '").unwrap();
+        assert_eq!(key.source(), &SecretSource::Inline);
+        assert_eq!(key.as_slice(), &[0, 255]);
+
+        assert!(!format!("{key:?}").contains("00ff"));
+        assert_eq!(serde_json::to_string(&key).unwrap(), "\"<redacted>\"");
+
+        assert!(serde_yaml::from_str::<Key>("not hex").is_err());
+        assert!(serde_yaml::from_str::<Key>("{ url: http://example.com }").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("key"), "0102\n").unwrap();
+
+        for yaml in ["{ file: key }", "!file key"] {
+            let mut key: Key = serde_yaml::from_str(yaml).unwrap();
+            assert_eq!(key.source(), &SecretSource::File("key".into()));
+            key.load(dir.path()).unwrap();
+            assert_eq!(key.as_slice(), &[1, 2]);
+        }
+
+        let mut missing: Key = serde_yaml::from_str("{ file: missing }").unwrap();
+        assert!(missing.load(dir.path()).is_err());
+
+        std::env::set_var("PUBHUBS_TEST_SERDE_EXT_SECRET", "0304");
+        let mut key: Key = serde_yaml::from_str("{ env: PUBHUBS_TEST_SERDE_EXT_SECRET }").unwrap();
+        key.load(dir.path()).unwrap();
+        assert_eq!(key.as_slice(), &[3, 4]);
+    }
+
     #[test]
     fn byte_array_deserialization() {
         assert_eq!(
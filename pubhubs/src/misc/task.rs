@@ -1,9 +1,16 @@
 //! Tools for dealing with tokio tasks.
 
+use std::collections::HashMap;
 use std::future::Future;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use rand::Rng as _;
 use tokio::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 /// Options for [retry].
+#[derive(Clone, Debug)]
 pub struct RetryOptions {
     /// Wait this amount of time after the first `Ok(None)` is returned.
     pub initial_wait_time: Duration,
@@ -11,8 +18,22 @@ pub struct RetryOptions {
     /// Increase the wait time by this factor each time `Ok(None)` is returned.
     pub backoff_factor: f32,
 
+    /// Never wait longer than this between two tries.
+    pub max_wait_time: Duration,
+
+    /// Randomly shorten each wait time by up to this fraction (between 0 and 1) of it,
+    /// so that servers that were restarted at the same time do not retry in lockstep.
+    pub jitter: f32,
+
     /// Try `max_retries+1` number of times to get a non-`Ok(None)` answer.
     pub max_retries: Option<usize>,
+
+    /// Give up when no non-`Ok(None)` answer has been obtained within this time.
+    /// A try that is underway when the deadline passes is abandoned.
+    pub deadline: Option<Duration>,
+
+    /// Give up when this token is cancelled, abandoning a try that is underway.
+    pub cancellation_token: Option<CancellationToken>,
 }
 
 impl Default for RetryOptions {
@@ -20,9 +41,73 @@ impl Default for RetryOptions {
         Self {
             initial_wait_time: Duration::from_millis(100),
             backoff_factor: 2f32,
+            max_wait_time: Duration::from_secs(10),
+            jitter: 0.5,
             max_retries: None,
+            deadline: Some(Duration::from_secs(5 * 60)),
+            cancellation_token: None,
+        }
+    }
+}
+
+/// How a call to [RetryOptions::retry] ended.  Counted by [retry_metrics].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetryOutcome {
+    /// `Ok(Some(value))` was returned
+    Ok,
+
+    /// `Err(err)` was returned
+    Err,
+
+    /// [RetryOptions::max_retries] was reached
+    Exhausted,
+
+    /// [RetryOptions::deadline] passed
+    DeadlineExceeded,
+
+    /// [RetryOptions::cancellation_token] was cancelled
+    Cancelled,
+
+    /// Gave up (for one of the reasons above) while the [CircuitBreaker] for the peer was open,
+    /// so that the last try was skipped
+    CircuitOpen,
+}
+
+impl RetryOutcome {
+    /// Value of the `outcome` label in [retry_metrics].
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RetryOutcome::Ok => "ok",
+            RetryOutcome::Err => "err",
+            RetryOutcome::Exhausted => "exhausted",
+            RetryOutcome::DeadlineExceeded => "deadline_exceeded",
+            RetryOutcome::Cancelled => "cancelled",
+            RetryOutcome::CircuitOpen => "circuit_open",
         }
     }
+
+    /// Increments the counter for this outcome in [retry_metrics].
+    pub fn record(self) {
+        retry_metrics().with_label_values(&[self.as_str()]).inc();
+    }
+}
+
+/// Counts the [RetryOutcome]s of all retries by the `outcome` label.
+///
+/// Register it with a [prometheus::Registry] to expose it.
+pub fn retry_metrics() -> &'static prometheus::IntCounterVec {
+    static RETRY_OUTCOMES: OnceLock<prometheus::IntCounterVec> = OnceLock::new();
+
+    RETRY_OUTCOMES.get_or_init(|| {
+        prometheus::IntCounterVec::new(
+            prometheus::Opts::new(
+                "retry_outcomes_total",
+                "Number of retried operations by how they ended",
+            ),
+            &["outcome"],
+        )
+        .expect("retry_outcomes_total to be a valid metric")
+    })
 }
 
 impl RetryOptions {
@@ -30,35 +115,99 @@ impl RetryOptions {
         &self,
         f: impl Fn() -> Fut,
     ) -> Result<Option<T>, E> {
+        let (res, outcome) = self.retry_with_outcome(f).await;
+        outcome.record();
+        res
+    }
+
+    /// Like [RetryOptions::retry], but returns the [RetryOutcome] instead of recording it.
+    pub async fn retry_with_outcome<T, E, Fut: Future<Output = Result<Option<T>, E>>>(
+        &self,
+        f: impl Fn() -> Fut,
+    ) -> (Result<Option<T>, E>, RetryOutcome) {
         let mut retries_left: usize = self.max_retries.unwrap_or(usize::MAX);
         let mut wait_time: Duration = self.initial_wait_time;
         let backoff_factor = self.backoff_factor;
 
+        let deadline = self
+            .deadline
+            .map(|deadline| tokio::time::Instant::now() + deadline);
+
         loop {
-            let res = f().await;
+            let res = match self.interruptible(f(), deadline).await {
+                Ok(res) => res,
+                Err(outcome) => return (Ok(None), outcome),
+            };
 
             // return when res is Ok(Some(v)) or Err(ec)
             match res.as_ref() {
                 Ok(None) => {}
-                _ => return res,
+                Ok(Some(_)) => return (res, RetryOutcome::Ok),
+                Err(_) => return (res, RetryOutcome::Err),
             };
 
             if retries_left == 0 {
-                return Ok(None);
+                return (Ok(None), RetryOutcome::Exhausted);
             }
 
             retries_left -= 1;
 
-            tokio::time::sleep(wait_time).await;
-            wait_time = wait_time.mul_f32(backoff_factor);
+            if let Err(outcome) = self
+                .interruptible(tokio::time::sleep(self.jittered(wait_time)), deadline)
+                .await
+            {
+                return (Ok(None), outcome);
+            }
+
+            wait_time = wait_time.mul_f32(backoff_factor).min(self.max_wait_time);
         }
     }
+
+    /// Runs `fut`, unless the `deadline` passes or the [RetryOptions::cancellation_token] is
+    /// cancelled first.
+    async fn interruptible<F: Future>(
+        &self,
+        fut: F,
+        deadline: Option<tokio::time::Instant>,
+    ) -> Result<F::Output, RetryOutcome> {
+        tokio::select! {
+            res = fut => Ok(res),
+            _ = sleep_until_option(deadline) => Err(RetryOutcome::DeadlineExceeded),
+            _ = cancelled_option(self.cancellation_token.as_ref()) => Err(RetryOutcome::Cancelled),
+        }
+    }
+
+    /// Randomly shortens `wait_time` according to [RetryOptions::jitter].
+    fn jittered(&self, wait_time: Duration) -> Duration {
+        let jitter = self.jitter.clamp(0f32, 1f32);
+
+        if jitter == 0f32 {
+            return wait_time;
+        }
+
+        wait_time.mul_f32(1f32 - jitter * rand::thread_rng().gen::<f32>())
+    }
+}
+
+async fn sleep_until_option(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn cancelled_option(token: Option<&CancellationToken>) {
+    match token {
+        Some(token) => token.cancelled().await,
+        None => std::future::pending().await,
+    }
 }
 
 /// Calls the given function `f` until it no longer returns `Ok(None)`, and returns the last result
 /// which is thus either an `Ok(Some(value))` or an `Err(err)`.
 ///
-/// When a maximal number of retries is reached (see [RetryOptions::max_retries]), `Ok(None)` is returned.
+/// When a maximal number of retries is reached (see [RetryOptions::max_retries]), or the
+/// deadline has passed (see [RetryOptions::deadline]), `Ok(None)` is returned.
 ///
 /// See [RetryOptions::retry] for more options.
 pub async fn retry<T, E, Fut: Future<Output = Result<Option<T>, E>>>(
@@ -66,3 +215,276 @@ pub async fn retry<T, E, Fut: Future<Output = Result<Option<T>, E>>>(
 ) -> Result<Option<T>, E> {
     RetryOptions::default().retry(f).await
 }
+
+/// Keeps track of failures per peer, and stops us from contacting a peer for a while (by being
+/// 'open') after a number of consecutive failures.
+///
+/// After [CircuitBreaker::open_for] has passed, one attempt is allowed again ('half-open').
+/// If it succeeds, the peer is contacted as usual again; if it fails, the circuit opens again.
+/// Until the outcome of this attempt is [recorded](CircuitBreaker::record), all other attempts
+/// are refused, unless the attempt takes longer than [CircuitBreaker::open_for], in which case
+/// it is presumed lost and another attempt is allowed.
+pub struct CircuitBreaker {
+    /// Open the circuit after this many consecutive failures.
+    pub failure_threshold: u32,
+
+    /// Keep the circuit open for this amount of time.
+    pub open_for: Duration,
+
+    peers: Mutex<HashMap<String, PeerState>>,
+}
+
+/// State of the circuit to a peer.  Peers whose circuit is closed without any recent failures
+/// are not tracked.
+enum PeerState {
+    Closed {
+        consecutive_failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    HalfOpen {
+        probe_in_flight: bool,
+        since: Instant,
+    },
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(10, Duration::from_secs(30))
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, open_for: Duration) -> Self {
+        Self {
+            failure_threshold,
+            open_for,
+            peers: Default::default(),
+        }
+    }
+
+    /// Returns whether `peer` may be contacted.  If so, the caller must [record](Self::record)
+    /// the result of contacting `peer`.
+    pub fn allows(&self, peer: &str) -> bool {
+        let mut peers = self.peers.lock().unwrap();
+
+        let Some(state) = peers.get_mut(peer) else {
+            return true;
+        };
+
+        let now = Instant::now();
+
+        match *state {
+            PeerState::Closed { .. } => true,
+            PeerState::Open { until } if now < until => false,
+            PeerState::HalfOpen {
+                probe_in_flight: true,
+                since,
+            } if now < since + self.open_for => false,
+            PeerState::Open { .. } | PeerState::HalfOpen { .. } => {
+                // half-open: allow one attempt, and open again if that fails
+                *state = PeerState::HalfOpen {
+                    probe_in_flight: true,
+                    since: now,
+                };
+                true
+            }
+        }
+    }
+
+    /// Records the result of contacting `peer`.
+    pub fn record(&self, peer: &str, success: bool) {
+        let mut peers = self.peers.lock().unwrap();
+
+        if success {
+            peers.remove(peer);
+            return;
+        }
+
+        let state = peers.entry(peer.to_string()).or_insert(PeerState::Closed {
+            consecutive_failures: 0,
+        });
+
+        match state {
+            PeerState::Closed {
+                consecutive_failures,
+            } => {
+                *consecutive_failures += 1;
+
+                if *consecutive_failures < self.failure_threshold {
+                    return;
+                }
+
+                log::warn!(
+                    "failed to contact {peer} {consecutive_failures} times in a row; not trying again for {:?}",
+                    self.open_for
+                );
+            }
+            PeerState::HalfOpen { .. } => {
+                log::warn!(
+                    "failed to contact {peer} again; not trying again for {:?}",
+                    self.open_for
+                );
+            }
+            // a failure of an attempt made before the circuit opened
+            PeerState::Open { .. } => return,
+        }
+
+        *state = PeerState::Open {
+            until: Instant::now() + self.open_for,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn fast() -> RetryOptions {
+        RetryOptions {
+            initial_wait_time: Duration::from_millis(1),
+            max_wait_time: Duration::from_millis(5),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_outcomes() {
+        let tries = AtomicUsize::new(0);
+        let count = || async {
+            let n = tries.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, ()>((n >= 3).then_some(n))
+        };
+
+        assert_eq!(
+            fast().retry_with_outcome(count).await,
+            (Ok(Some(3)), RetryOutcome::Ok)
+        );
+
+        tries.store(0, Ordering::SeqCst);
+        assert_eq!(
+            RetryOptions {
+                max_retries: Some(1),
+                ..fast()
+            }
+            .retry_with_outcome(count)
+            .await,
+            (Ok(None), RetryOutcome::Exhausted)
+        );
+        assert_eq!(tries.load(Ordering::SeqCst), 2);
+
+        assert_eq!(
+            fast()
+                .retry_with_outcome(|| async { Err::<Option<()>, _>("no") })
+                .await,
+            (Err("no"), RetryOutcome::Err)
+        );
+
+        let never = || async { Ok::<Option<()>, ()>(None) };
+
+        assert_eq!(
+            RetryOptions {
+                deadline: Some(Duration::from_millis(20)),
+                ..fast()
+            }
+            .retry_with_outcome(never)
+            .await,
+            (Ok(None), RetryOutcome::DeadlineExceeded)
+        );
+
+        // a try that hangs is abandoned at the deadline
+        assert_eq!(
+            RetryOptions {
+                deadline: Some(Duration::from_millis(20)),
+                ..fast()
+            }
+            .retry_with_outcome(std::future::pending::<Result<Option<()>, ()>>)
+            .await,
+            (Ok(None), RetryOutcome::DeadlineExceeded)
+        );
+
+        let token = CancellationToken::new();
+        let options = RetryOptions {
+            cancellation_token: Some(token.clone()),
+            deadline: None,
+            ..fast()
+        };
+        let (res, ()) = tokio::join!(options.retry_with_outcome(never), async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            token.cancel();
+        });
+        assert_eq!(res, (Ok(None), RetryOutcome::Cancelled));
+    }
+
+    #[test]
+    fn jitter() {
+        let options = RetryOptions {
+            jitter: 0.5,
+            ..Default::default()
+        };
+        let wait_time = Duration::from_secs(1);
+
+        for _ in 0..100 {
+            let jittered = options.jittered(wait_time);
+            assert!(jittered <= wait_time);
+            assert!(jittered >= wait_time / 2);
+        }
+
+        let options = RetryOptions {
+            jitter: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(options.jittered(wait_time), wait_time);
+    }
+
+    #[test]
+    fn circuit_breaker() {
+        let cb = CircuitBreaker::new(2, Duration::from_millis(50));
+
+        assert!(cb.allows("a"));
+        cb.record("a", false);
+        assert!(cb.allows("a"));
+        cb.record("a", false);
+        assert!(!cb.allows("a"));
+        assert!(cb.allows("b"));
+
+        std::thread::sleep(Duration::from_millis(60));
+
+        // half-open: only one attempt is allowed, and if it fails the circuit opens again
+        assert!(cb.allows("a"));
+        assert!(!cb.allows("a"));
+        cb.record("a", false);
+        assert!(!cb.allows("a"));
+
+        std::thread::sleep(Duration::from_millis(60));
+
+        assert!(cb.allows("a"));
+        cb.record("a", true);
+        cb.record("a", false);
+        assert!(cb.allows("a"));
+        assert!(cb.allows("a"));
+    }
+
+    #[test]
+    fn circuit_breaker_lost_probe() {
+        let cb = CircuitBreaker::new(1, Duration::from_millis(50));
+
+        cb.record("a", false);
+        assert!(!cb.allows("a"));
+
+        std::thread::sleep(Duration::from_millis(60));
+
+        // the result of this probe is never recorded
+        assert!(cb.allows("a"));
+        assert!(!cb.allows("a"));
+
+        std::thread::sleep(Duration::from_millis(60));
+
+        assert!(cb.allows("a"));
+        cb.record("a", true);
+        assert!(cb.allows("a"));
+    }
+}
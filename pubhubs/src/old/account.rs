@@ -0,0 +1,465 @@
+//! Endpoints for users to download all their data, and to delete their account, authenticated
+//! by the `PHAccount` session cookie.
+//!
+//! ```text
+//! GET  /account/export           -> all data on the user, as JSON
+//! POST /account/delete           -> { "confirmation": "..." }
+//! POST /account/delete/confirm   <- { "confirmation": "..." }
+//! ```
+//!
+//! An account is only deleted when the confirmation obtained from `/account/delete` is sent back
+//! within [CONFIRMATION_VALIDITY] seconds.  Since the session cookie is not stored, it can't be
+//! revoked, but it is useless once the account is gone:  a new account gets a new id.
+//!
+//! The hubs are told about the deletion, see [notify_hubs], so that they can remove the account
+//! of the user's local pseudonym.  Hubs that could not be told are tried again when PubHubs
+//! Central restarts, see [notify_hubs_of_pending_deletions].
+use crate::context::Main;
+use crate::cookie::HttpResponseBuilderExt as _;
+use crate::data::{
+    BarState, DataCommands, Hub, HubHandle, PendingAccountDeletion, User, UserExport,
+};
+use crate::elgamal::Encoding as _;
+use crate::error::AnyhowExt as _;
+use crate::error::TranslatedError;
+use actix_web::web::Data;
+use anyhow::{anyhow, Context as _, Result};
+use chrono::Utc;
+use hmac::{Hmac, Mac as _};
+use rand::distributions::{Alphanumeric, DistString as _};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Number of seconds a confirmation for deleting an account is valid.
+pub const CONFIRMATION_VALIDITY: i64 = 10 * 60;
+
+/// Number of seconds a logout token sent to a hub is valid.
+const LOGOUT_TOKEN_VALIDITY: u64 = 10 * 60;
+
+/// The only event in the logout tokens sent by [notify_hubs], telling the hub that the user
+/// deleted their account.  It also makes sure a logout token can't be mistaken for an id token.
+pub const ACCOUNT_DELETED_EVENT: &str = "https://pubhubs.net/event/account-deleted";
+
+/// Path of the endpoint of the hub's `pseudonyms.AccountDeletion` module (see
+/// `pubhubs_hub/modules/pseudonyms.py`) that [notify_hubs] sends logout tokens to.
+pub const HUB_ACCOUNT_DELETED_PATH: &str = "/_synapse/client/pubhubs/account_deleted";
+
+pub mod reason {
+    pub const INVALID_CONFIRMATION: &str =
+        "Forbidden - invalid confirmation;  obtain one via POST /account/delete";
+    pub const EXPIRED_CONFIRMATION: &str =
+        "Forbidden - expired confirmation;  obtain a new one via POST /account/delete";
+}
+
+/// Proof that the user asked to delete their account, see [delete].
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct Confirmation {
+    pub confirmation: String,
+}
+
+pub async fn export(
+    req: actix_web::HttpRequest,
+    context: Data<Main>,
+) -> Result<actix_web::HttpResponse, TranslatedError> {
+    export_anyhow(&req, &context)
+        .await
+        .into_translated_error(&req)
+}
+
+async fn export_anyhow(
+    req: &actix_web::HttpRequest,
+    context: &Main,
+) -> Result<actix_web::HttpResponse> {
+    let user_id = match crate::bar::get_user_id(req, context) {
+        Ok(user_id) => user_id,
+        Err(err_resp) => return Ok(err_resp),
+    };
+
+    let Some(user) = get_user(context, user_id.clone()).await? else {
+        return Ok(crate::bar::unknown_user());
+    };
+
+    let bar_state: BarState = {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        context
+            .db_tx
+            .send(DataCommands::GetBarState {
+                resp: tx,
+                id: user_id,
+            })
+            .await?;
+        rx.await??
+    };
+
+    Ok(actix_web::HttpResponse::Ok()
+        .insert_header(actix_web::http::header::ContentDisposition {
+            disposition: actix_web::http::header::DispositionType::Attachment,
+            parameters: vec![actix_web::http::header::DispositionParam::Filename(
+                "pubhubs-account.json".to_string(),
+            )],
+        })
+        .json(UserExport::new(user, bar_state)))
+}
+
+/// Returns a [Confirmation] to be sent to [delete_confirm] to actually delete the account.
+pub async fn delete(
+    req: actix_web::HttpRequest,
+    context: Data<Main>,
+) -> Result<actix_web::HttpResponse, TranslatedError> {
+    delete_anyhow(&req, &context)
+        .await
+        .into_translated_error(&req)
+}
+
+async fn delete_anyhow(
+    req: &actix_web::HttpRequest,
+    context: &Main,
+) -> Result<actix_web::HttpResponse> {
+    let user_id = match crate::bar::get_user_id(req, context) {
+        Ok(user_id) => user_id,
+        Err(err_resp) => return Ok(err_resp),
+    };
+
+    if get_user(context, user_id.clone()).await?.is_none() {
+        return Ok(crate::bar::unknown_user());
+    }
+
+    Ok(actix_web::HttpResponse::Ok().json(Confirmation {
+        confirmation: create_confirmation(
+            &user_id,
+            Utc::now().timestamp() + CONFIRMATION_VALIDITY,
+            &context.cookie_secret,
+        )?,
+    }))
+}
+
+/// Deletes the account of the user, when the [Confirmation] is in order, and removes the session
+/// cookie.
+pub async fn delete_confirm(
+    req: actix_web::HttpRequest,
+    context: Data<Main>,
+    body: actix_web::web::Json<Confirmation>,
+) -> Result<actix_web::HttpResponse, TranslatedError> {
+    delete_confirm_anyhow(&req, context, body.into_inner())
+        .await
+        .into_translated_error(&req)
+}
+
+async fn delete_confirm_anyhow(
+    req: &actix_web::HttpRequest,
+    context: Data<Main>,
+    body: Confirmation,
+) -> Result<actix_web::HttpResponse> {
+    let user_id = match crate::bar::get_user_id(req, &context) {
+        Ok(user_id) => user_id,
+        Err(err_resp) => return Ok(err_resp),
+    };
+
+    if let Err(reason) = check_confirmation(&body.confirmation, &user_id, &context.cookie_secret) {
+        return Ok(actix_web::HttpResponse::Forbidden().reason(reason).finish());
+    }
+
+    let Some(user) = get_user(&context, user_id.clone()).await? else {
+        return Ok(crate::bar::unknown_user());
+    };
+
+    // The user's pseudonyms for the hubs can't be computed after the user is gone, so they're
+    // recorded together with the deletion, see [PendingAccountDeletion].
+    let subs: Vec<(crate::data::Hubid, String)> = {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        context
+            .db_tx
+            .send(DataCommands::AllHubs { resp: tx })
+            .await?;
+        rx.await??
+            .iter()
+            .map(|hub| Ok((hub.id, sub_for(&context, &user, hub)?)))
+            .collect::<Result<_>>()?
+    };
+
+    let (user, pending): (User, Vec<PendingAccountDeletion>) = {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        context
+            .db_tx
+            .send(DataCommands::DeleteUser {
+                resp: tx,
+                id: user_id,
+                subs,
+            })
+            .await?;
+        match rx.await? {
+            Ok(result) => result,
+            Err(err) if crate::data::no_result(&err) => return Ok(crate::bar::unknown_user()),
+            Err(err) => return Err(err),
+        }
+    };
+
+    log::info!("user {} deleted their account", user.external_id);
+
+    // awc's futures are not Send, so we can't use tokio::spawn
+    actix_web::rt::spawn(notify_hubs(context, pending));
+
+    Ok(actix_web::HttpResponse::NoContent()
+        .remove_session_cookie()
+        .finish())
+}
+
+/// Returns the active user with the given id, or `None` if there is none, for example because
+/// the user deleted their account, but still presents a session cookie.
+async fn get_user(context: &Main, id: String) -> Result<Option<User>> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    context
+        .db_tx
+        .send(DataCommands::GetUserById { resp: tx, id })
+        .await?;
+    match rx.await? {
+        Ok(user) => Ok(Some(user)),
+        Err(err) if crate::data::no_result(&err) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Creates a confirmation for deleting the account of `user_id`, valid until the unix timestamp
+/// `until`:  `until` followed by a MAC on `user_id` and `until`.
+fn create_confirmation(user_id: &str, until: i64, cookie_secret: &str) -> Result<String> {
+    let tag = confirmation_mac(user_id, until, cookie_secret)?
+        .finalize()
+        .into_bytes();
+
+    Ok(format!("{until}.{}", base16ct::lower::encode_string(&tag)))
+}
+
+/// Checks that `confirmation` was created by [create_confirmation] for `user_id`, and is still
+/// valid, returning the reason it's not otherwise.
+fn check_confirmation(
+    confirmation: &str,
+    user_id: &str,
+    cookie_secret: &str,
+) -> Result<(), &'static str> {
+    let Some((until, tag)) = confirmation.split_once('.') else {
+        return Err(reason::INVALID_CONFIRMATION);
+    };
+
+    let (Ok(until), Ok(tag)) = (until.parse::<i64>(), base16ct::mixed::decode_vec(tag)) else {
+        return Err(reason::INVALID_CONFIRMATION);
+    };
+
+    confirmation_mac(user_id, until, cookie_secret)
+        .map_err(|_| reason::INVALID_CONFIRMATION)?
+        .verify_slice(&tag)
+        .map_err(|_| reason::INVALID_CONFIRMATION)?;
+
+    if Utc::now().timestamp() > until {
+        return Err(reason::EXPIRED_CONFIRMATION);
+    }
+
+    Ok(())
+}
+
+fn confirmation_mac(user_id: &str, until: i64, cookie_secret: &str) -> Result<HmacSha256> {
+    // derive a separate key, so that a confirmation can't be mistaken for a session cookie
+    let key = crate::crypto::derive_secret("account-deletion", cookie_secret.as_bytes());
+
+    let mut mac = HmacSha256::new_from_slice(&key).context("creating MAC for confirmation")?;
+    mac.update(format!("{user_id}.{until}").as_bytes());
+    Ok(mac)
+}
+
+/// Returns the `sub` of `user` in the id tokens issued to `hub`:  the user's encrypted local
+/// pseudonym for `hub`, hex-encoded.
+fn sub_for(context: &Main, user: &User, hub: &Hub) -> Result<String> {
+    Ok(context
+        .pep
+        .convert_to_local_pseudonym(
+            crate::elgamal::Triple::from_hex(&user.pseudonym)
+                .ok_or_else(|| anyhow!("invalid pseudonym"))?,
+            hub,
+        )?
+        .to_hex())
+}
+
+/// Tells the hubs that users deleted their account, by sending a logout token for the user's
+/// local pseudonym to the [HUB_ACCOUNT_DELETED_PATH] endpoint on each hub's redirect uri's host.
+///
+/// The logout token is like the one of Section 2.4 of OpenID Connect Back-Channel Logout 1.0,
+/// but with [ACCOUNT_DELETED_EVENT] as event.  Synapse's own back-channel logout endpoint can't
+/// be used, because it only ends the user's sessions, and requires a session id.
+///
+/// Hubs that can't be reached are retried for a while.  The [PendingAccountDeletion]s of the
+/// hubs that were told are removed;  the others remain, and are tried again by
+/// [notify_hubs_of_pending_deletions].
+async fn notify_hubs(context: Data<Main>, pending: Vec<PendingAccountDeletion>) {
+    let client = awc::Client::default();
+
+    futures::future::join_all(pending.iter().map(|pending| async {
+        match notify_hub_of(&context, &client, pending).await {
+            Ok(()) => {
+                if let Err(err) = remove_pending(&context, pending.id).await {
+                    log::error!(
+                        "could not remove pending account deletion {}: {err:#}",
+                        pending.id
+                    );
+                }
+            }
+            Err(err) => log::warn!(
+                "could not notify hub {} of account deletion {};  trying again when PubHubs Central restarts: {err:#}",
+                pending.hub,
+                pending.id
+            ),
+        }
+    }))
+    .await;
+}
+
+/// Tells the hubs about all account deletions they have yet to be told about, see [notify_hubs].
+/// Run when PubHubs Central starts.
+pub async fn notify_hubs_of_pending_deletions(context: Data<Main>) -> Result<()> {
+    let pending: Vec<PendingAccountDeletion> = {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        context
+            .db_tx
+            .send(DataCommands::PendingAccountDeletions { resp: tx })
+            .await?;
+        rx.await??
+    };
+
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    log::info!(
+        "notifying hubs of {} earlier account deletion(s)",
+        pending.len()
+    );
+
+    notify_hubs(context, pending).await;
+
+    Ok(())
+}
+
+async fn remove_pending(context: &Main, id: i64) -> Result<()> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    context
+        .db_tx
+        .send(DataCommands::RemovePendingAccountDeletion { resp: tx, id })
+        .await?;
+    rx.await?
+}
+
+async fn notify_hub_of(
+    context: &Main,
+    client: &awc::Client,
+    pending: &PendingAccountDeletion,
+) -> Result<()> {
+    let hub: Hub = {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        context
+            .db_tx
+            .send(DataCommands::GetHub {
+                resp: tx,
+                handle: HubHandle::Id(pending.hub),
+            })
+            .await?;
+        match rx.await? {
+            Ok(hub) => hub,
+            Err(err) if crate::data::no_result(&err) => {
+                log::info!(
+                    "not notifying hub {} of account deletion {}: the hub is gone",
+                    pending.hub,
+                    pending.id
+                );
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        }
+    };
+
+    let endpoint = url::Url::parse(&hub.oidc_redirect_uri)
+        .and_then(|uri| uri.join(HUB_ACCOUNT_DELETED_PATH))
+        .with_context(|| format!("invalid redirect uri {:?}", hub.oidc_redirect_uri))?;
+
+    let client_id: String = hub.oidc_credentials(&context.oidc).client_id.into();
+    let now = crate::jwt::get_current_timestamp();
+
+    let logout_token = crate::jwt::sign(
+        &serde_json::json!({
+            "iss": context.url.for_hub.as_str(),
+            // the same 'sub' as in the id_tokens issued to this hub
+            "sub": pending.sub,
+            "aud": client_id,
+            "iat": now,
+            "exp": now + LOGOUT_TOKEN_VALIDITY,
+            "jti": Alphanumeric.sample_string(&mut rand::thread_rng(), 22),
+            "events": {
+                ACCOUNT_DELETED_EVENT: {},
+            },
+        }),
+        &context.id_token_key,
+    )?;
+
+    crate::misc::task::RetryOptions {
+        deadline: Some(std::time::Duration::from_secs(LOGOUT_TOKEN_VALIDITY)),
+        ..Default::default()
+    }
+    .retry(|| async {
+        match client
+            .post(endpoint.as_str())
+            .send_form(&[("logout_token", &logout_token)])
+            .await
+        {
+            Ok(resp) if resp.status().is_success() => Ok(Some(())),
+            Ok(resp) if resp.status().is_server_error() => {
+                log::debug!("{endpoint} responded with {};  retrying", resp.status());
+                Ok(None)
+            }
+            Ok(resp) => Err(anyhow!("{endpoint} responded with {}", resp.status())),
+            Err(err) => {
+                log::debug!("could not reach {endpoint}: {err};  retrying");
+                Ok(None)
+            }
+        }
+    })
+    .await?
+    .ok_or_else(|| anyhow!("gave up on {endpoint}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn confirmations() {
+        let until = Utc::now().timestamp() + CONFIRMATION_VALIDITY;
+        let confirmation = create_confirmation("user", until, "secret").unwrap();
+
+        assert_eq!(check_confirmation(&confirmation, "user", "secret"), Ok(()));
+        assert_eq!(
+            check_confirmation(&confirmation, "other user", "secret"),
+            Err(reason::INVALID_CONFIRMATION)
+        );
+        assert_eq!(
+            check_confirmation(&confirmation, "user", "other secret"),
+            Err(reason::INVALID_CONFIRMATION)
+        );
+        for invalid in ["", ".", "x.00", &confirmation.replacen('.', ",", 1)] {
+            assert_eq!(
+                check_confirmation(invalid, "user", "secret"),
+                Err(reason::INVALID_CONFIRMATION)
+            );
+        }
+
+        // the validity can't be extended
+        let (_, tag) = confirmation.split_once('.').unwrap();
+        assert_eq!(
+            check_confirmation(&format!("{}.{tag}", until + 1), "user", "secret"),
+            Err(reason::INVALID_CONFIRMATION)
+        );
+
+        let expired = create_confirmation("user", Utc::now().timestamp() - 1, "secret").unwrap();
+        assert_eq!(
+            check_confirmation(&expired, "user", "secret"),
+            Err(reason::EXPIRED_CONFIRMATION)
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use crate::cookie::HttpRequestCookieExt as _;
+use crate::cookie::HttpResponseBuilderExt as _;
 use crate::error::AnyhowExt as _;
 use crate::error::TranslatedError;
 use actix_web::http::header::{Header as _, TryIntoHeaderValue as _};
@@ -19,6 +20,7 @@ pub mod reason {
                 "Bad Request - 'If-Match: *' is not supported here; you must send the ETag of the old (and still current) state.";
     pub const MISSING_COOKIE: &str = "Forbidden - missing PHAccount cookie";
     pub const INVALID_COOKIE: &str = "Forbidden - invalid PHAccount cookie";
+    pub const UNKNOWN_USER: &str = "Forbidden - the account of this PHAccount cookie was deleted";
 }
 
 pub async fn get_state(
@@ -48,7 +50,11 @@ async fn get_state_anyhow(
                 id: user_id,
             })
             .await?;
-        bs_rx.await??
+        match bs_rx.await? {
+            Ok(bar_state) => bar_state,
+            Err(err) if crate::data::no_result(&err) => return Ok(unknown_user()),
+            Err(err) => return Err(err),
+        }
     };
 
     Ok(actix_web::HttpResponse::Ok()
@@ -211,9 +217,11 @@ async fn get_hubs_anyhow(
         .json(SerializeHubs(hubs)))
 }
 
-fn get_user_id(
+/// Returns the user id from the `PHAccount` cookie, or the response to send when there's no
+/// (valid) such cookie.
+pub(crate) fn get_user_id(
     req: &actix_web::HttpRequest,
-    context: &actix_web::web::Data<crate::context::Main>,
+    context: &crate::context::Main,
 ) -> Result<String, actix_web::HttpResponse> {
     let user_id = req.user_id_from_cookie(&context.cookie_secret);
 
@@ -233,3 +241,12 @@ fn get_user_id(
 
     Ok(user_id.unwrap())
 }
+
+/// Response to a request with a valid `PHAccount` cookie of a user that no longer exists,
+/// removing the cookie.
+pub(crate) fn unknown_user() -> actix_web::HttpResponse {
+    actix_web::HttpResponse::Forbidden()
+        .reason(reason::UNKNOWN_USER)
+        .remove_session_cookie()
+        .finish()
+}
@@ -1,5 +1,5 @@
 use actix_web::http::header::{HeaderMap, HeaderValue};
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use expry::BytecodeVec;
 use hairy::hairy_compile_html;
 
@@ -8,9 +8,6 @@ use std::io::Read;
 use std::path::PathBuf;
 use std::sync::Arc;
 
-#[cfg(feature = "old")]
-use std::ops::Deref as _;
-
 use base64ct::{Base64, Encoding as _};
 use http::header::AUTHORIZATION;
 use prometheus::{CounterVec, HistogramOpts, HistogramVec, Opts, Registry};
@@ -36,6 +33,8 @@ pub struct Main {
     pub metrics_key: String,
 
     pub db_tx: Sender<DataCommands>,
+    /// see [config::File::backup_directory]
+    pub backup_directory: Option<PathBuf>,
 
     pub translations: crate::translate::AllTranslations,
     pub hair: BytecodeVec,
@@ -123,19 +122,43 @@ impl Main {
             .register(Box::new(database_req_histogram.clone()))
             .context("Expected to register database_req_histogram")?;
 
+        // the outcomes of retries, e.g. when querying the new servers
+        registry
+            .register(Box::new(crate::misc::task::retry_metrics().clone()))
+            .context("Expected to register retry_outcomes_total")?;
+
         // Make a database actor mpsc channel
         let (db_tx, db_rx) = mpsc::channel(1_000);
 
-        if let Some(ref path) = config.database_location {
+        if let Some(ref url) = config.database_url {
+            if config.database_location.is_some() {
+                bail!("both database_url and database_location are set;  choose one");
+            }
+
+            crate::data::make_postgres_database_manager(url, db_rx, database_req_histogram)
+                .await
+                .context("setting up the PostgreSQL database failed")?;
+        } else if let Some(ref path) = config.database_location {
             crate::data::make_database_manager(
                 config.interpret_path(path),
                 db_rx,
                 database_req_histogram,
             );
         } else {
+            if !cfg!(test) {
+                log::warn!(
+                    "NO DATABASE CONFIGURED:  using an in-memory database, so ALL DATA IS LOST \
+                    when PubHubs Central stops;  set database_location (or database_url)"
+                );
+            }
             crate::data::make_in_memory_database_manager(db_rx, database_req_histogram);
         }
 
+        let backup_directory = config
+            .backup_directory
+            .as_ref()
+            .map(|dir| config.interpret_path(dir));
+
         let path_interpreter = config.path_interpreter();
 
         crate::policy::initialize_latest_policy(
@@ -192,13 +215,16 @@ impl Main {
         ))
         .context("loading translations failed")?;
 
-        let oidc_secret = having_debug_default(
+        let oidc_secret: crate::misc::secret::SecretBytes = having_debug_default(
             config.oidc_secret,
             crate::misc::serde_ext::B64::new(serde_bytes::ByteBuf::from(
                 b"default_oidc_secret".to_vec(),
             )),
             "oidc_secret",
-        )?;
+        )?
+        .into_inner()
+        .into_vec()
+        .into();
 
         let well_known_openid_configuration : bytes::Bytes = serde_json::to_string_pretty(&serde_json::json!({
             "issuer": url.for_hub.as_str(),
@@ -222,9 +248,11 @@ impl Main {
 
         // Compute contents of /.well-known/jwks.json,
         let id_token_key: ed25519_dalek::SigningKey = {
-            let id_token_private_key: [u8; 32] =
-                crate::crypto::derive_secret("id-token-key", &oidc_secret).into();
-            ed25519_dalek::SigningKey::from_bytes(&id_token_private_key)
+            let mut id_token_private_key: [u8; 32] =
+                crate::crypto::derive_secret("id-token-key", oidc_secret.as_bytes()).into();
+            let id_token_key = ed25519_dalek::SigningKey::from_bytes(&id_token_private_key);
+            zeroize::Zeroize::zeroize(&mut id_token_private_key);
+            id_token_key
         };
 
         let well_known_jwks_json: bytes::Bytes =
@@ -239,7 +267,7 @@ impl Main {
             .into();
 
         Ok(Arc::new_cyclic(|wp: &std::sync::Weak<context::Main>| {
-            let oidc = oidc::new(oidc_handler::Handler::new(wp), oidc_secret.deref());
+            let oidc = oidc::new(oidc_handler::Handler::new(wp), oidc_secret.as_bytes());
 
             Self {
                 url,
@@ -255,6 +283,7 @@ impl Main {
                 hair,
                 translations,
                 db_tx,
+                backup_directory,
                 oidc,
                 registry,
                 http_req_histogram,
@@ -302,8 +331,8 @@ impl Main {
                 .send(crate::data::DataCommands::GetUserById { resp: tx, id })
                 .await
                 .unwrap();
-            let user = rx.await.unwrap().unwrap();
-            return user.administrator;
+            // the user might have deleted their account since the cookie was issued
+            return matches!(rx.await.unwrap(), Ok(user) if user.administrator);
         }
         false
     }
@@ -319,7 +348,7 @@ pub struct Yivi {
     pub requestor: String,
     pub requestor_hmac_key: crate::jwt::HS256,
     pub server_issuer: String,
-    pub server_key: jsonwebtoken::DecodingKey,
+    pub server_key: crate::jwt::JwkKey,
 }
 
 impl Yivi {
@@ -334,12 +363,13 @@ impl Yivi {
         // the configuration file's location
         let server_key_file = pi(&server_key_file);
 
-        let mut buff: Vec<u8> = vec![];
+        let mut buff = String::new();
 
         let mut f = std::fs::File::open(server_key_file)?;
-        f.read_to_end(&mut buff)?;
+        f.read_to_string(&mut buff)?;
 
-        let server_key = jsonwebtoken::DecodingKey::from_rsa_pem(&buff)?;
+        let server_key = crate::jwt::JwkKey::from_pem(&buff)
+            .context("expected a PEM-encoded RSA or P-256 public key as yivi server key")?;
 
         Ok(Self {
             client_api_url: config
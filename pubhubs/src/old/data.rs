@@ -20,6 +20,11 @@ use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::sync::oneshot;
 use uuid::Uuid;
 
+mod postgres;
+mod readers;
+pub use postgres::Postgres;
+use readers::Readers;
+
 /// Trait for a database migration.  Implemented by `str` for simple SQL-statements.
 ///
 /// See [MIGRATIONS] for more details.
@@ -111,7 +116,7 @@ fn migrate_database(db: &mut Connection, do_migrations: DoMigrations) -> Result<
 /// be interpretted as the index of the next migration to be performed.
 ///
 /// Since a blank database starts with `user_version=0`, migration number 0 will be performed first.
-const MIGRATIONS: [&dyn Migration; 12] = [
+const MIGRATIONS: [&dyn Migration; 14] = [
     &"
         -- Commented out, for efficiency,
         --   because it's dropped later on anyhow 
@@ -199,7 +204,31 @@ const MIGRATIONS: [&dyn Migration; 12] = [
     &"ALTER TABLE hub RENAME COLUMN redirection_uri TO oidc_redirect_uri;", // #8
     &("adding client_uri to hub", migration_add_client_uri), // #9
     &"ALTER TABLE user ADD registration_date TEXT DEFAULT 'older card' NOT NULL;", //#10
-    &("add external id to user",migration_add_user_external_id)//#11
+    &("add external id to user",migration_add_user_external_id),//#11
+    &"
+        ALTER TABLE hub ADD previous_decryption_id TEXT;
+        ALTER TABLE hub ADD previous_decryption_id_valid_until INTEGER;
+
+        CREATE TABLE hub_decryption_id_rotation (
+            id INTEGER PRIMARY KEY,
+            hub TEXT NOT NULL,
+            previous_decryption_id TEXT NOT NULL,
+            decryption_id TEXT NOT NULL,
+            rotated_at INTEGER NOT NULL,
+            previous_decryption_id_valid_until INTEGER NOT NULL,
+            rotated_by TEXT NOT NULL
+        );
+
+        CREATE INDEX idx_hub_decryption_id_rotation_hub ON hub_decryption_id_rotation (hub);
+    ", //#12
+    &"
+        CREATE TABLE pending_account_deletion (
+            id INTEGER PRIMARY KEY,
+            hub TEXT NOT NULL,
+            sub TEXT NOT NULL,
+            deleted_at INTEGER NOT NULL
+        );
+    ", //#13
 ];
 
 /// Adds `client_uri` field to hub, with as initial value the `oidc_redirect_uri` but with path,
@@ -224,17 +253,24 @@ fn migration_add_client_uri(tx: &rusqlite::Transaction) -> Result<()> {
     for row_result in rows {
         let (id, oidc_redirect_uri) = row_result?;
 
-        let mut uri = url::Url::parse(&oidc_redirect_uri)?;
-        uri.set_path("");
-        uri.set_query(None);
-        uri.set_fragment(None);
-
-        update_stmt.execute(rusqlite::named_params! { ":id": id, ":uri": uri.to_string()})?;
+        update_stmt.execute(
+            rusqlite::named_params! { ":id": id, ":uri": client_uri_for(&oidc_redirect_uri)?},
+        )?;
     }
 
     Ok(())
 }
 
+/// The initial `client_uri` of a hub, see [migration_add_client_uri].
+fn client_uri_for(oidc_redirect_uri: &str) -> Result<String> {
+    let mut uri = url::Url::parse(oidc_redirect_uri)?;
+    uri.set_path("");
+    uri.set_query(None);
+    uri.set_fragment(None);
+
+    Ok(uri.to_string())
+}
+
 fn migration_add_user_external_id(tx: &rusqlite::Transaction) -> Result<()> {
     tx.execute(
         "ALTER TABLE user ADD external_id TEXT DEFAULT '' NOT NULL;",
@@ -293,6 +329,13 @@ pub enum DataCommands {
         oidc_redirect_uri: String,
         client_uri: String,
     },
+    /// See [rotate_decryption_id].
+    RotateHubDecryptionId {
+        resp: oneshot::Sender<Result<Hub>>,
+        id: Hubid,
+        grace_period: chrono::Duration,
+        rotated_by: String,
+    },
     AllUsers {
         resp: oneshot::Sender<Result<Vec<User>>>,
     },
@@ -313,6 +356,21 @@ pub enum DataCommands {
         resp: oneshot::Sender<Result<User>>,
         id: String,
     },
+    /// See [delete_user].
+    DeleteUser {
+        resp: oneshot::Sender<Result<(User, Vec<PendingAccountDeletion>)>>,
+        id: String,
+        subs: Vec<(Hubid, String)>,
+    },
+    /// All [PendingAccountDeletion]s, oldest first.
+    PendingAccountDeletions {
+        resp: oneshot::Sender<Result<Vec<PendingAccountDeletion>>>,
+    },
+    /// Removes the [PendingAccountDeletion] with the given id, once the hub has been told.
+    RemovePendingAccountDeletion {
+        resp: oneshot::Sender<Result<()>>,
+        id: i64,
+    },
     GetBarState {
         resp: oneshot::Sender<Result<BarState>>,
         id: String,
@@ -335,10 +393,38 @@ pub enum DataCommands {
     GetLatestPolicy {
         resp: oneshot::Sender<Result<Option<Policy>>>,
     },
+    /// Writes a consistent copy of the database to the file `target`, see [backup].
+    Backup {
+        resp: oneshot::Sender<Result<()>>,
+        target: std::path::PathBuf,
+    },
     // #[cfg(test)] // does not work for the binary
     Terminate {},
 }
 
+impl DataCommands {
+    /// Whether this command only reads from the database, and may thus be handled concurrently
+    /// with other commands, see [make_database_manager].
+    fn is_read(&self) -> bool {
+        matches!(
+            self,
+            DataCommands::AllHubs { .. }
+                | DataCommands::GetHub { .. }
+                | DataCommands::GetHubid { .. }
+                | DataCommands::AllUsers { .. }
+                | DataCommands::GetUser { .. }
+                | DataCommands::GetUserById { .. }
+                | DataCommands::GetBarState { .. }
+                | DataCommands::GetLatestPolicy { .. }
+        )
+    }
+}
+
+/// Handles the [DataCommands] sent over `rx` using the SQLite database at `path`.
+///
+/// Commands that write are handled one by one on a single connection, while commands that only
+/// read are handled concurrently on a pool of read-only connections, see [DataCommands::is_read].
+/// For this, the database is put in WAL mode.
 pub fn make_database_manager<P: AsRef<Path>>(
     path: P,
     rx: Receiver<DataCommands>,
@@ -347,12 +433,36 @@ pub fn make_database_manager<P: AsRef<Path>>(
     // can't pass reference to new thread
     let path = path.as_ref().to_path_buf();
 
+    let (writes_tx, writes_rx) = tokio::sync::mpsc::channel(1_000);
+
     tokio::spawn(async move {
-        let manager = get_manager(path).expect("A database connection");
-        handle_command(rx, manager, database_req_histogram).await
+        let manager = get_manager(&path).expect("A database connection");
+
+        let journal_mode: String = manager
+            .pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))
+            .expect("to be able to set the journal mode");
+        assert_eq!(
+            journal_mode, "wal",
+            "expected the database to be put in WAL mode"
+        );
+
+        let readers =
+            Readers::open(&path, read_connections()).expect("Read-only database connections");
+
+        tokio::spawn(readers.dispatch(rx, writes_tx, database_req_histogram.clone()));
+
+        handle_command(writes_rx, manager, database_req_histogram).await
     });
 }
 
+/// The number of read-only connections used by [make_database_manager].
+fn read_connections() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+        .clamp(2, 16)
+}
+
 pub fn make_in_memory_database_manager(
     rx: Receiver<DataCommands>,
     database_req_histogram: HistogramVec,
@@ -364,9 +474,222 @@ pub fn make_in_memory_database_manager(
     });
 }
 
+/// Like [make_database_manager], but uses the PostgreSQL database at `url`, see [Postgres].
+///
+/// Returns an error when the database cannot be reached, or migrated.
+pub async fn make_postgres_database_manager(
+    url: &str,
+    rx: Receiver<DataCommands>,
+    database_req_histogram: HistogramVec,
+) -> Result<()> {
+    let manager = Postgres::connect(url).await?;
+
+    tokio::spawn(async move { handle_command(rx, manager, database_req_histogram).await });
+
+    Ok(())
+}
+
+/// Where the database actor stores its data:  either a SQLite [Connection], or [Postgres].
+///
+/// The methods correspond to the [DataCommands], and are named after the functions
+/// implementing them for SQLite.  When there's no result, they return
+/// [rusqlite::Error::QueryReturnedNoRows] (see [no_result]), also for PostgreSQL.
+trait Storage {
+    async fn get_all_hubs(&mut self) -> Result<Vec<Hub>>;
+
+    async fn create_hub(
+        &mut self,
+        name: &str,
+        description: &str,
+        oidc_redirect_uri: &str,
+        client_uri: &str,
+    ) -> Result<Hubid>;
+
+    async fn get_hub(&mut self, handle: HubHandle) -> Result<Hub>;
+
+    async fn get_hubid(&mut self, name: &str) -> Result<Option<Hubid>>;
+
+    async fn update_hub_details(
+        &mut self,
+        id: Hubid,
+        name: &str,
+        description: &str,
+        oidc_redirect_uri: &str,
+        client_uri: &str,
+    ) -> Result<Hub>;
+
+    /// See [rotate_decryption_id].
+    async fn rotate_decryption_id(
+        &mut self,
+        id: Hubid,
+        grace_period: chrono::Duration,
+        rotated_by: &str,
+    ) -> Result<Hub>;
+
+    async fn get_all_users(&mut self) -> Result<Vec<User>>;
+
+    async fn create_user(
+        &mut self,
+        email: &str,
+        telephone: &str,
+        registration_date: &str,
+        pep: &PepContext,
+        is_admin: bool,
+    ) -> Result<User>;
+
+    async fn get_user(&mut self, email: &str, telephone: &str) -> Result<User>;
+
+    async fn get_user_by_id(&mut self, id: String) -> Result<User>;
+
+    /// See [delete_user].
+    async fn delete_user(
+        &mut self,
+        id: String,
+        subs: &[(Hubid, String)],
+    ) -> Result<(User, Vec<PendingAccountDeletion>)>;
+
+    async fn get_pending_account_deletions(&mut self) -> Result<Vec<PendingAccountDeletion>>;
+
+    async fn remove_pending_account_deletion(&mut self, id: i64) -> Result<()>;
+
+    async fn get_bar_state(&mut self, id: String) -> Result<BarState>;
+
+    /// See [DataCommands::UpdateBarState].
+    async fn update_bar_state(
+        &mut self,
+        id: String,
+        old_etag: String,
+        state: bytes::Bytes,
+    ) -> Result<Option<String>>;
+
+    async fn create_new_policy(
+        &mut self,
+        content: &str,
+        highlights: Vec<String>,
+        version: u32,
+    ) -> Result<Policy>;
+
+    async fn get_latest_policy(&mut self) -> Result<Option<Policy>>;
+
+    async fn backup(&mut self, target: &Path) -> Result<()>;
+}
+
+impl Storage for Connection {
+    async fn get_all_hubs(&mut self) -> Result<Vec<Hub>> {
+        get_all_hubs(self)
+    }
+
+    async fn create_hub(
+        &mut self,
+        name: &str,
+        description: &str,
+        oidc_redirect_uri: &str,
+        client_uri: &str,
+    ) -> Result<Hubid> {
+        create_hub(self, name, description, oidc_redirect_uri, client_uri)
+    }
+
+    async fn get_hub(&mut self, handle: HubHandle) -> Result<Hub> {
+        get_hub(self, handle)
+    }
+
+    async fn get_hubid(&mut self, name: &str) -> Result<Option<Hubid>> {
+        get_hubid(self, name)
+    }
+
+    async fn update_hub_details(
+        &mut self,
+        id: Hubid,
+        name: &str,
+        description: &str,
+        oidc_redirect_uri: &str,
+        client_uri: &str,
+    ) -> Result<Hub> {
+        update_hub_details(self, id, name, description, oidc_redirect_uri, client_uri)
+    }
+
+    async fn rotate_decryption_id(
+        &mut self,
+        id: Hubid,
+        grace_period: chrono::Duration,
+        rotated_by: &str,
+    ) -> Result<Hub> {
+        rotate_decryption_id(self, id, grace_period, rotated_by)
+    }
+
+    async fn get_all_users(&mut self) -> Result<Vec<User>> {
+        get_all_users(self)
+    }
+
+    async fn create_user(
+        &mut self,
+        email: &str,
+        telephone: &str,
+        registration_date: &str,
+        pep: &PepContext,
+        is_admin: bool,
+    ) -> Result<User> {
+        create_user(self, email, telephone, registration_date, pep, is_admin)
+    }
+
+    async fn get_user(&mut self, email: &str, telephone: &str) -> Result<User> {
+        get_user(self, email, telephone)
+    }
+
+    async fn get_user_by_id(&mut self, id: String) -> Result<User> {
+        get_user_by_id(self, id)
+    }
+
+    async fn delete_user(
+        &mut self,
+        id: String,
+        subs: &[(Hubid, String)],
+    ) -> Result<(User, Vec<PendingAccountDeletion>)> {
+        delete_user(self, id, subs)
+    }
+
+    async fn get_pending_account_deletions(&mut self) -> Result<Vec<PendingAccountDeletion>> {
+        get_pending_account_deletions(self)
+    }
+
+    async fn remove_pending_account_deletion(&mut self, id: i64) -> Result<()> {
+        remove_pending_account_deletion(self, id)
+    }
+
+    async fn get_bar_state(&mut self, id: String) -> Result<BarState> {
+        get_bar_state(self, id)
+    }
+
+    async fn update_bar_state(
+        &mut self,
+        id: String,
+        old_etag: String,
+        state: bytes::Bytes,
+    ) -> Result<Option<String>> {
+        update_bar_state(self, id, old_etag, state)
+    }
+
+    async fn create_new_policy(
+        &mut self,
+        content: &str,
+        highlights: Vec<String>,
+        version: u32,
+    ) -> Result<Policy> {
+        create_new_policy(self, content, highlights, version)
+    }
+
+    async fn get_latest_policy(&mut self) -> Result<Option<Policy>> {
+        get_latest_policy(self)
+    }
+
+    async fn backup(&mut self, target: &Path) -> Result<()> {
+        backup(self, target)
+    }
+}
+
 async fn handle_command(
     mut rx: Receiver<DataCommands>,
-    mut manager: Connection,
+    mut manager: impl Storage,
     database_req_histogram: HistogramVec,
 ) {
     while let Some(cmd) = rx.recv().await {
@@ -375,7 +698,7 @@ async fn handle_command(
             .start_timer();
         match cmd {
             DataCommands::AllHubs { resp } => {
-                let hubs = get_all_hubs(&manager);
+                let hubs = manager.get_all_hubs().await;
                 resp.send(hubs).expect("To use our channel");
             }
             DataCommands::CreateHub {
@@ -385,21 +708,19 @@ async fn handle_command(
                 client_uri,
                 resp,
             } => {
-                resp.send(create_hub(
-                    &manager,
-                    &name,
-                    &description,
-                    &oidc_redirect_uri,
-                    &client_uri,
-                ))
+                resp.send(
+                    manager
+                        .create_hub(&name, &description, &oidc_redirect_uri, &client_uri)
+                        .await,
+                )
                 .expect("To use our channel");
             }
             DataCommands::GetHub { resp, handle } => {
-                resp.send(get_hub(&manager, handle))
+                resp.send(manager.get_hub(handle).await)
                     .expect("To use our channel");
             }
             DataCommands::GetHubid { resp, name } => {
-                resp.send(get_hubid(&manager, &name))
+                resp.send(manager.get_hubid(&name).await)
                     .expect("To use our channel");
             }
             DataCommands::UpdateHub {
@@ -410,18 +731,34 @@ async fn handle_command(
                 oidc_redirect_uri,
                 client_uri,
             } => {
-                resp.send(update_hub_details(
-                    &manager,
-                    id,
-                    &name,
-                    &description,
-                    &oidc_redirect_uri,
-                    &client_uri,
-                ))
+                resp.send(
+                    manager
+                        .update_hub_details(
+                            id,
+                            &name,
+                            &description,
+                            &oidc_redirect_uri,
+                            &client_uri,
+                        )
+                        .await,
+                )
+                .expect("To use our channel");
+            }
+            DataCommands::RotateHubDecryptionId {
+                resp,
+                id,
+                grace_period,
+                rotated_by,
+            } => {
+                resp.send(
+                    manager
+                        .rotate_decryption_id(id, grace_period, &rotated_by)
+                        .await,
+                )
                 .expect("To use our channel");
             }
             DataCommands::AllUsers { resp } => {
-                let users = get_all_users(&manager);
+                let users = manager.get_all_users().await;
                 resp.send(users).expect("To use our channel");
             }
             DataCommands::CreateUser {
@@ -432,14 +769,11 @@ async fn handle_command(
                 config,
                 is_admin,
             } => {
-                resp.send(create_user(
-                    &manager,
-                    &email,
-                    &telephone,
-                    &registration_date,
-                    &config,
-                    is_admin,
-                ))
+                resp.send(
+                    manager
+                        .create_user(&email, &telephone, &registration_date, &config, is_admin)
+                        .await,
+                )
                 .expect("To use our channel");
             }
             DataCommands::GetUser {
@@ -447,14 +781,23 @@ async fn handle_command(
                 email,
                 telephone,
             } => {
-                resp.send(get_user(&manager, &email, &telephone))
+                resp.send(manager.get_user(&email, &telephone).await)
                     .expect("To use our channel");
             }
             DataCommands::GetUserById { resp, id } => resp
-                .send(get_user_by_id(&manager, id))
+                .send(manager.get_user_by_id(id).await)
+                .expect("Trying to use the data channel"),
+            DataCommands::DeleteUser { resp, id, subs } => resp
+                .send(manager.delete_user(id, &subs).await)
+                .expect("Trying to use the data channel"),
+            DataCommands::PendingAccountDeletions { resp } => resp
+                .send(manager.get_pending_account_deletions().await)
+                .expect("Trying to use the data channel"),
+            DataCommands::RemovePendingAccountDeletion { resp, id } => resp
+                .send(manager.remove_pending_account_deletion(id).await)
                 .expect("Trying to use the data channel"),
             DataCommands::GetBarState { resp, id } => resp
-                .send(get_bar_state(&manager, id))
+                .send(manager.get_bar_state(id).await)
                 .expect("Trying to use the data channel"),
             DataCommands::UpdateBarState {
                 resp,
@@ -462,7 +805,7 @@ async fn handle_command(
                 old_etag,
                 state,
             } => resp
-                .send(update_bar_state(&manager, id, old_etag, state))
+                .send(manager.update_bar_state(id, old_etag, state).await)
                 .expect("Trying to use the data channel"),
             DataCommands::CreatePolicy {
                 resp,
@@ -470,15 +813,17 @@ async fn handle_command(
                 highlights,
                 version,
             } => resp
-                .send(create_new_policy(
-                    &mut manager,
-                    &content,
-                    highlights,
-                    version,
-                ))
+                .send(
+                    manager
+                        .create_new_policy(&content, highlights, version)
+                        .await,
+                )
                 .expect("Expected to use the data channel"),
             DataCommands::GetLatestPolicy { resp } => resp
-                .send(get_latest_policy(&manager))
+                .send(manager.get_latest_policy().await)
+                .expect("Expected to use the data channel"),
+            DataCommands::Backup { resp, target } => resp
+                .send(manager.backup(&target).await)
                 .expect("Expected to use the data channel"),
             //#[cfg(test)]
             DataCommands::Terminate {} => break,
@@ -494,6 +839,116 @@ pub fn get_manager<P: AsRef<Path>>(path: P) -> Result<Connection> {
     Ok(manager)
 }
 
+/// Writes a copy of the database `db` to the new file `target` using SQLite's online backup
+/// API, see <https://www.sqlite.org/backup.html>.
+///
+/// The copy is first written to `target` with `.partial` appended, so that an interrupted backup
+/// is not mistaken for a complete one.
+pub fn backup(db: &Connection, target: &Path) -> Result<()> {
+    ensure!(
+        !target.exists(),
+        "{} already exists;  not overwriting it",
+        target.display()
+    );
+
+    let partial = with_suffix(target, ".partial");
+
+    // create_new, so that we don't overwrite a file an earlier, concurrent backup is writing to
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&partial)
+        .with_context(|| format!("could not create {}", partial.display()))?;
+
+    let result = (|| -> Result<()> {
+        let mut dst = Connection::open(&partial)?;
+
+        // all pages at once, since the writes wait for us anyway
+        match rusqlite::backup::Backup::new(db, &mut dst)?.step(-1)? {
+            rusqlite::backup::StepResult::Done => {}
+            result => bail!("backup did not complete: {result:?}"),
+        }
+
+        dst.close().map_err(|(_, err)| err)?;
+
+        std::fs::rename(&partial, target)?;
+
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+
+    result.with_context(|| format!("backing up the database to {}", target.display()))
+}
+
+/// Replaces the database at `database` by the copy at `backup`, made by [backup].
+///
+/// PubHubs Central must not be running.  The backup must be of a database whose schema is not
+/// newer than the schema this version of PubHubs Central migrates to;  an older schema is
+/// migrated when PubHubs Central starts.  The database that is replaced, if any, is moved aside
+/// to `database` with `.before-restore` appended, which is returned.
+pub fn restore(backup: &Path, database: &Path) -> Result<Option<std::path::PathBuf>> {
+    let check = || -> Result<()> {
+        let db = Connection::open_with_flags(backup, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+        let integrity: String = db.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+        ensure!(integrity == "ok", "integrity check failed: {integrity}");
+
+        let version = schema_version(&db)?;
+        ensure!(version > 0, "not a PubHubs Central database");
+        ensure!(
+            version <= MIGRATIONS.len(),
+            "its schema version {version} is newer than the version {} of this PubHubs Central",
+            MIGRATIONS.len()
+        );
+
+        Ok(())
+    };
+
+    check().with_context(|| format!("not restoring {}", backup.display()))?;
+
+    let restoring = with_suffix(database, ".restoring");
+    std::fs::copy(backup, &restoring)
+        .with_context(|| format!("copying {} to {}", backup.display(), restoring.display()))?;
+    std::fs::File::open(&restoring)?.sync_all()?;
+
+    let mut moved_aside = None;
+
+    if database.exists() {
+        // move the contents of the write-ahead log into the database file itself
+        Connection::open(database)?
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .with_context(|| format!("checkpointing {}", database.display()))?;
+
+        let before_restore = with_suffix(database, ".before-restore");
+        ensure!(
+            !before_restore.exists(),
+            "{} already exists;  please move it out of the way",
+            before_restore.display()
+        );
+
+        std::fs::rename(database, &before_restore)?;
+        moved_aside = Some(before_restore);
+    }
+
+    for suffix in ["-wal", "-shm"] {
+        let _ = std::fs::remove_file(with_suffix(database, suffix));
+    }
+
+    std::fs::rename(&restoring, database)?;
+
+    Ok(moved_aside)
+}
+
+/// Returns `path` with `suffix` appended to its file name.
+fn with_suffix(path: &Path, suffix: &str) -> std::path::PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
 /// Specify which migrations to perform.
 pub enum DoMigrations {
     All,
@@ -612,6 +1067,9 @@ pub struct Hub {
     // the hub's local decryption key (aka the 'Hub secret') so that it
     // can be changed when the Hub secret is compromised.
     pub decryption_id: Hubid,
+    // The decryption_id before the last rotation of the Hub secret, which is still in use
+    // during a grace period, see [rotate_decryption_id].
+    pub previous_decryption_id: Option<PreviousDecryptionId>,
     pub name: String,
     pub description: String,
     pub oidc_redirect_uri: String,
@@ -619,6 +1077,20 @@ pub struct Hub {
     pub active: bool,
 }
 
+/// A hub's previous [Hub::decryption_id], together with the moment (as unix timestamp) until
+/// which its local decryption key remains in use.
+#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct PreviousDecryptionId {
+    pub decryption_id: Hubid,
+    pub valid_until: i64,
+}
+
+impl PreviousDecryptionId {
+    pub fn is_valid(&self) -> bool {
+        chrono::Utc::now().timestamp() < self.valid_until
+    }
+}
+
 /// Represents a (decryption) id of a Hub,
 /// a uuid which is formatted as lower-case hex with hyphens, e.g.:
 ///   936da01f-9abd-4d9d-80c7-02af85c822a8
@@ -762,9 +1234,25 @@ impl HubHandle {
 }
 
 impl Hub {
-    /// DO NOT CHANGE lest you'll change all local decryption keys
+    /// The decryption id of the local decryption key that is currently in use:  the
+    /// [previous decryption id](Hub::previous_decryption_id) during the grace period after a
+    /// rotation of the Hub secret, so that the hub can keep decrypting until it has installed its
+    /// new key, and [Hub::decryption_id] otherwise.
+    pub fn decryption_id_in_use(&self) -> Hubid {
+        match self.previous_decryption_id {
+            Some(previous) if previous.is_valid() => previous.decryption_id,
+            _ => self.decryption_id,
+        }
+    }
+
+    /// The context for the local decryption key currently in use, see [Hub::decryption_id_in_use].
     pub fn decryption_context(&self) -> String {
-        format!("Hub decryption key #{}", self.decryption_id)
+        Self::decryption_context_for(&self.decryption_id_in_use())
+    }
+
+    /// DO NOT CHANGE lest you'll change all local decryption keys
+    pub fn decryption_context_for(decryption_id: &Hubid) -> String {
+        format!("Hub decryption key #{}", decryption_id)
     }
 
     /// DO NOT CHANGE lest you'll change all local pseudonyms.
@@ -835,9 +1323,18 @@ pub fn create_hub(
 }
 
 fn map_hub(row: &Row) -> rusqlite::Result<Hub> {
+    let previous_decryption_id: Option<Hubid> = row.get(7)?;
+    let previous_decryption_id_valid_until: Option<i64> = row.get(8)?;
+
     Ok(Hub {
         id: row.get(0)?,
         decryption_id: row.get(1)?,
+        previous_decryption_id: previous_decryption_id
+            .zip(previous_decryption_id_valid_until)
+            .map(|(decryption_id, valid_until)| PreviousDecryptionId {
+                decryption_id,
+                valid_until,
+            }),
         name: row.get(2)?,
         description: row.get(3)?,
         oidc_redirect_uri: row.get(4)?,
@@ -849,7 +1346,8 @@ fn map_hub(row: &Row) -> rusqlite::Result<Hub> {
 /// Get a hub by id if it's active.
 pub fn get_hub(db: &Connection, handle: HubHandle) -> Result<Hub> {
     let query = format!(
-        "SELECT id, decryption_id, name, description, oidc_redirect_uri, client_uri, active FROM hub
+        "SELECT id, decryption_id, name, description, oidc_redirect_uri, client_uri, active,
+            previous_decryption_id, previous_decryption_id_valid_until FROM hub
         WHERE active = TRUE AND {} = ?1",
         handle.column_name()
     );
@@ -874,7 +1372,8 @@ pub fn get_hubid(db: &Connection, name: &str) -> Result<Option<Hubid>> {
 /// List all hubs
 pub fn get_all_hubs(db: &Connection) -> Result<Vec<Hub>> {
     let mut stmt = db.prepare(
-        "SELECT id, decryption_id, name, description, oidc_redirect_uri, client_uri, active FROM hub WHERE active = TRUE",
+        "SELECT id, decryption_id, name, description, oidc_redirect_uri, client_uri, active,
+            previous_decryption_id, previous_decryption_id_valid_until FROM hub WHERE active = TRUE",
     )?;
     let result: Result<Vec<Hub>, rusqlite::Error> = stmt.query_map([], map_hub)?.collect();
     Ok(result?)
@@ -924,6 +1423,80 @@ pub fn update_hub_details(
     get_hub(db, HubHandle::Id(id))
 }
 
+/// Gives the active hub with the given `id` a new [Hub::decryption_id], and thus a new local
+/// decryption key (the 'Hub secret'), for example when the old one leaked.
+///
+/// The decryption key currently in use remains in use for `grace_period`, giving the hub time to
+/// install its new key.  When the old key leaked, use a grace period of zero.
+///
+/// The rotation is recorded in the `hub_decryption_id_rotation` table, with `rotated_by`
+/// describing who performed it.
+pub fn rotate_decryption_id(
+    db: &mut Connection,
+    id: Hubid,
+    grace_period: chrono::Duration,
+    rotated_by: &str,
+) -> Result<Hub> {
+    ensure!(
+        grace_period >= chrono::Duration::zero(),
+        "grace period must not be negative"
+    );
+
+    let tx = db.transaction()?;
+
+    let hub = get_hub(&tx, HubHandle::Id(id))?;
+
+    let previous_decryption_id = hub.decryption_id_in_use();
+    let decryption_id = Hubid::new();
+    let now = chrono::Utc::now().timestamp();
+    let valid_until = now + grace_period.num_seconds();
+
+    let rows_changed = tx.execute(
+        "UPDATE hub
+        SET decryption_id = :decryption_id, previous_decryption_id = :previous_decryption_id,
+            previous_decryption_id_valid_until = :valid_until
+        WHERE active = TRUE AND id = :id",
+        rusqlite::named_params! {
+            ":decryption_id": decryption_id,
+            ":previous_decryption_id": previous_decryption_id,
+            ":valid_until": valid_until,
+            ":id": id,
+        },
+    )?;
+
+    ensure!(
+        rows_changed == 1,
+        "expected to change 1 database row on hub decryption id rotation"
+    );
+
+    tx.execute(
+        "INSERT INTO hub_decryption_id_rotation (hub, previous_decryption_id, decryption_id,
+            rotated_at, previous_decryption_id_valid_until, rotated_by)
+        VALUES (:hub, :previous_decryption_id, :decryption_id, :rotated_at, :valid_until, :rotated_by)",
+        rusqlite::named_params! {
+            ":hub": id,
+            ":previous_decryption_id": previous_decryption_id,
+            ":decryption_id": decryption_id,
+            ":rotated_at": now,
+            ":valid_until": valid_until,
+            ":rotated_by": rotated_by,
+        },
+    )?;
+
+    let hub = get_hub(&tx, HubHandle::Id(id))?;
+
+    tx.commit()?;
+
+    log::info!(
+        "{rotated_by} rotated the decryption id of hub {} ({}); the previous decryption key remains in use for {} seconds",
+        hub.id,
+        hub.name,
+        grace_period.num_seconds()
+    );
+
+    Ok(hub)
+}
+
 #[derive(PartialEq, Eq, Serialize)]
 pub struct User {
     pub external_id: String,
@@ -942,6 +1515,66 @@ pub struct BarState {
     pub state_etag: String,
 }
 
+/// A hub that has yet to be told that a user deleted their account, see [crate::account].
+///
+/// Recorded together with the deletion of the user, and removed once the hub acknowledged it,
+/// so that the hub is told even when PubHubs Central is restarted in between.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct PendingAccountDeletion {
+    pub id: i64,
+    pub hub: Hubid,
+
+    /// The `sub` of the user in the id tokens issued to `hub`:  the hex-encoded encrypted local
+    /// pseudonym of the user for `hub`
+    pub sub: String,
+
+    /// Unix timestamp of the deletion of the user
+    pub deleted_at: i64,
+}
+
+/// Everything PubHubs Central stores about an active user, as exported to the user (see
+/// [crate::account]) or to the new servers (see [crate::migrate]).
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct UserExport {
+    pub external_id: String,
+    pub email: String,
+    pub telephone: String,
+
+    /// Hex-encoded encrypted global pseudonym, see [crate::elgamal::Triple]
+    pub pseudonym: String,
+    pub registration_date: String,
+    pub administrator: bool,
+
+    /// State of the global client's side bar, see [crate::bar]
+    pub bar_state: crate::misc::serde_ext::B64UU,
+    pub bar_state_etag: String,
+}
+
+impl UserExport {
+    pub fn new(user: User, bar_state: BarState) -> Self {
+        let User {
+            external_id,
+            email,
+            telephone,
+            pseudonym,
+            registration_date,
+            active: _,
+            administrator,
+        } = user;
+
+        UserExport {
+            external_id,
+            email,
+            telephone,
+            pseudonym,
+            registration_date,
+            administrator,
+            bar_state: serde_bytes::ByteBuf::from(bar_state.state).into(),
+            bar_state_etag: bar_state.state_etag,
+        }
+    }
+}
+
 impl Debug for User {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("User")
@@ -1029,12 +1662,7 @@ pub fn update_bar_state(
     old_etag: String,
     state: bytes::Bytes,
 ) -> Result<Option<String>> {
-    let new_etag: String = base16ct::lower::encode_string(
-        sha2::Sha256::new()
-            .chain_update(&state)
-            .finalize()
-            .as_slice(),
-    );
+    let new_etag: String = bar_state_etag(&state);
 
     match db.execute(
         "UPDATE user SET bar_state = :bar_state, bar_state_etag = :new_etag WHERE external_id = :id AND bar_state_etag = :old_etag;",
@@ -1050,6 +1678,16 @@ pub fn update_bar_state(
     }
 }
 
+/// The etag of the given bar state:  its hex-encoded SHA-256 hash.
+fn bar_state_etag(state: &[u8]) -> String {
+    base16ct::lower::encode_string(
+        sha2::Sha256::new()
+            .chain_update(state)
+            .finalize()
+            .as_slice(),
+    )
+}
+
 pub fn get_all_users(db: &Connection) -> Result<Vec<User>> {
     let mut stmt =
         db.prepare("SELECT external_id, email, telephone, pseudonym, registration_date, active, administrator FROM user WHERE active = TRUE")?;
@@ -1057,16 +1695,59 @@ pub fn get_all_users(db: &Connection) -> Result<Vec<User>> {
     Ok(result?)
 }
 
-//Will be used later
-#[allow(dead_code)]
-pub fn delete_user(db: &Connection, email: &str, telephone: &str) -> Result<usize> {
-    let result = db.execute(
-        "UPDATE user
-        SET active = FALSE
-        WHERE active = TRUE AND email = ?1 and telephone = ?2",
-        [email, telephone],
+/// Erases the active user with the given external id, including their bar state, and returns
+/// the user as it was just before.
+///
+/// Unlike hubs, users are not merely marked inactive, because they should be able to have their
+/// personal data removed.
+///
+/// In the same transaction, a [PendingAccountDeletion] is recorded for each hub and `sub` in
+/// `subs`, which are returned too.
+pub fn delete_user(
+    db: &mut Connection,
+    id: String,
+    subs: &[(Hubid, String)],
+) -> Result<(User, Vec<PendingAccountDeletion>)> {
+    let tx = db.transaction()?;
+
+    let user = tx.query_row(
+        "DELETE FROM user WHERE active = TRUE AND external_id = ?1
+        RETURNING external_id, email, telephone, pseudonym, registration_date, active, administrator",
+        [id],
+        map_user,
     )?;
-    Ok(result)
+
+    let deleted_at = chrono::Utc::now().timestamp();
+    let mut pending = Vec::with_capacity(subs.len());
+
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO pending_account_deletion (hub, sub, deleted_at) VALUES (?1, ?2, ?3)
+            RETURNING id, hub, sub, deleted_at",
+        )?;
+
+        for (hub, sub) in subs {
+            pending
+                .push(stmt.query_row(params![hub, sub, deleted_at], map_pending_account_deletion)?);
+        }
+    }
+
+    tx.commit()?;
+
+    Ok((user, pending))
+}
+
+pub fn get_pending_account_deletions(db: &Connection) -> Result<Vec<PendingAccountDeletion>> {
+    let mut stmt =
+        db.prepare("SELECT id, hub, sub, deleted_at FROM pending_account_deletion ORDER BY id")?;
+    let result: Result<Vec<PendingAccountDeletion>, rusqlite::Error> =
+        stmt.query_map([], map_pending_account_deletion)?.collect();
+    Ok(result?)
+}
+
+pub fn remove_pending_account_deletion(db: &Connection, id: i64) -> Result<()> {
+    db.execute("DELETE FROM pending_account_deletion WHERE id = ?1", [id])?;
+    Ok(())
 }
 
 //Will be used later
@@ -1093,6 +1774,15 @@ fn map_user(row: &Row) -> Result<User, rusqlite::Error> {
     })
 }
 
+fn map_pending_account_deletion(row: &Row) -> Result<PendingAccountDeletion, rusqlite::Error> {
+    Ok(PendingAccountDeletion {
+        id: row.get(0)?,
+        hub: row.get(1)?,
+        sub: row.get(2)?,
+        deleted_at: row.get(3)?,
+    })
+}
+
 fn map_bar_state(row: &Row) -> Result<BarState, rusqlite::Error> {
     Ok(BarState {
         state: row.get(0)?,
@@ -1207,7 +1897,8 @@ mod tests {
 
         let hub = pool
             .query_row(
-                "SELECT id, decryption_id, name, description, oidc_redirect_uri, client_uri,active FROM hub WHERE name = ?1",
+                "SELECT id, decryption_id, name, description, oidc_redirect_uri, client_uri, active,
+                    previous_decryption_id, previous_decryption_id_valid_until FROM hub WHERE name = ?1",
                 [name1],
                 map_hub,
             )
@@ -1240,6 +1931,69 @@ mod tests {
         compare_error("UNIQUE constraint failed: hub.name", update_result);
     }
 
+    #[test]
+    fn can_rotate_decryption_id() {
+        let mut pool = set_up();
+        let hubid = create_hub(&pool, "hub", "description", "/callback", "client").unwrap();
+        let hub = get_hub(&pool, HubHandle::Id(hubid)).unwrap();
+        assert_eq!(hub.previous_decryption_id, None);
+        assert_eq!(hub.decryption_id_in_use(), hub.decryption_id);
+
+        // the original decryption id stays in use during the grace period
+        let rotated =
+            rotate_decryption_id(&mut pool, hubid, chrono::Duration::hours(1), "admin").unwrap();
+        assert_ne!(rotated.decryption_id, hub.decryption_id);
+        assert_eq!(rotated.decryption_id_in_use(), hub.decryption_id);
+        assert_eq!(rotated.decryption_context(), hub.decryption_context());
+
+        // ... and when rotated once more, the original decryption id remains in use
+        let rotated_again =
+            rotate_decryption_id(&mut pool, hubid, chrono::Duration::zero(), "admin").unwrap();
+        assert_eq!(
+            rotated_again.previous_decryption_id.unwrap().decryption_id,
+            hub.decryption_id
+        );
+        assert_eq!(
+            rotated_again.decryption_id_in_use(),
+            rotated_again.decryption_id
+        );
+        assert_eq!(
+            rotated_again.decryption_context(),
+            format!("Hub decryption key #{}", rotated_again.decryption_id)
+        );
+
+        let rotations: Vec<(String, String, String)> = pool
+            .prepare(
+                "SELECT previous_decryption_id, decryption_id, rotated_by
+                FROM hub_decryption_id_rotation WHERE hub = ?1 ORDER BY id",
+            )
+            .unwrap()
+            .query_map([hubid], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            rotations,
+            [
+                (
+                    hub.decryption_id.to_string(),
+                    rotated.decryption_id.to_string(),
+                    "admin".to_string()
+                ),
+                (
+                    hub.decryption_id.to_string(),
+                    rotated_again.decryption_id.to_string(),
+                    "admin".to_string()
+                ),
+            ]
+        );
+
+        assert!(
+            rotate_decryption_id(&mut pool, Hubid::new(), chrono::Duration::zero(), "admin")
+                .is_err()
+        );
+    }
+
     #[test]
     fn can_create_user_and_mail_telephone_needs_to_be_unique() {
         let pool = set_up();
@@ -1293,7 +2047,7 @@ mod tests {
 
     #[test]
     fn can_delete_user() {
-        let pool = set_up();
+        let mut pool = set_up();
         let mail = "mail1";
         let tel = "tel1";
         let date = "today";
@@ -1302,20 +2056,39 @@ mod tests {
         let user = get_user(&pool, mail, tel).unwrap();
         assert!(user.active);
 
-        delete_user(&pool, mail, tel);
+        let hub = Hubid::new();
+        let subs = vec![(hub, "sub".to_string())];
+
+        let (deleted, pending) = delete_user(&mut pool, user.external_id.clone(), &subs).unwrap();
+        assert_eq!(deleted, user);
+        assert_eq!(pending.len(), 1);
+        assert_eq!((pending[0].hub, pending[0].sub.as_str()), (hub, "sub"));
         let user_result = get_user(&pool, mail, tel);
         match user_result {
             Err(e) if no_result(&e) => assert!(true),
             _ => assert!(false),
         }
+        assert!(no_result(
+            &delete_user(&mut pool, user.external_id.clone(), &subs).unwrap_err()
+        ));
 
-        let user = pool.query_row(
-                "SELECT external_id, email, telephone, pseudonym, registration_date, active, administrator FROM user WHERE email = ?1 AND telephone = ?2",
-                [mail, tel],
-                map_user,
+        // the failed deletion recorded nothing
+        assert_eq!(get_pending_account_deletions(&pool).unwrap(), pending);
+        remove_pending_account_deletion(&pool, pending[0].id).unwrap();
+        assert!(get_pending_account_deletions(&pool).unwrap().is_empty());
+
+        // nothing of the user remains
+        let count: usize = pool
+            .query_row(
+                "SELECT COUNT(*) FROM user WHERE email = ?1 OR external_id = ?2",
+                [mail, &user.external_id],
+                |row| row.get(0),
             )
             .unwrap();
-        assert!(!user.active)
+        assert_eq!(count, 0);
+
+        // and so the user may register again
+        create_user(&pool, mail, tel, date, config, false).unwrap();
     }
 
     #[test]
@@ -1397,4 +2170,56 @@ mod tests {
         )?;
         Ok(result)
     }
+
+    #[test]
+    fn backup_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = dir.path().join("pubhubs.db");
+        let backup_path = dir.path().join("backup.db");
+
+        let db = get_manager(&database).unwrap();
+        db.pragma_update(None, "journal_mode", "WAL").unwrap();
+        create_hub(&db, "hub", "", "", "").unwrap();
+
+        backup(&db, &backup_path).unwrap();
+        assert!(backup(&db, &backup_path).is_err());
+        assert!(!with_suffix(&backup_path, ".partial").exists());
+
+        create_hub(&db, "hub2", "", "", "").unwrap();
+        drop(db);
+
+        let moved_aside = restore(&backup_path, &database).unwrap().unwrap();
+
+        let db = get_manager(&database).unwrap();
+        let hubs = get_all_hubs(&db).unwrap();
+        assert_eq!(hubs.len(), 1);
+        assert_eq!(hubs[0].name, "hub");
+        drop(db);
+
+        // the previous database is kept, including what was still in its write-ahead log
+        let db = Connection::open(&moved_aside).unwrap();
+        assert_eq!(get_all_hubs(&db).unwrap().len(), 2);
+        drop(db);
+
+        // refuses to move aside the database again
+        assert!(restore(&backup_path, &database).is_err());
+        std::fs::remove_file(&moved_aside).unwrap();
+
+        // refuses backups from newer versions, and other files
+        let newer = dir.path().join("newer.db");
+        std::fs::copy(&backup_path, &newer).unwrap();
+        Connection::open(&newer)
+            .unwrap()
+            .pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+            .unwrap();
+        assert!(restore(&newer, &database).is_err());
+
+        let other = dir.path().join("other.db");
+        Connection::open(&other)
+            .unwrap()
+            .execute_batch("CREATE TABLE t (x INTEGER);")
+            .unwrap();
+        assert!(restore(&other, &database).is_err());
+        assert!(restore(&backup_path, &database).is_ok());
+    }
 }
@@ -0,0 +1,239 @@
+//! Read-only SQLite connections that handle the [DataCommands] that only read (see
+//! [DataCommands::is_read]), so that these need not queue behind writes.
+//!
+//! This requires the database to be in WAL mode, in which readers and the (single) writer do
+//! not block each other, see <https://www.sqlite.org/wal.html>.
+use super::*;
+
+use rusqlite::OpenFlags;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
+
+/// A pool of read-only connections to a SQLite database.
+#[derive(Clone)]
+pub(super) struct Readers {
+    connections: Arc<Mutex<Vec<Connection>>>,
+
+    /// one permit per connection in `connections`
+    available: Arc<Semaphore>,
+}
+
+impl Readers {
+    /// Opens `count` read-only connections to the (already migrated) database at `path`.
+    pub(super) fn open(path: &Path, count: usize) -> Result<Self> {
+        let connections = (0..count)
+            .map(|_| {
+                Connection::open_with_flags(
+                    path,
+                    OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+                )
+            })
+            .collect::<Result<Vec<Connection>, _>>()
+            .context("opening read-only database connections")?;
+
+        Ok(Readers {
+            connections: Arc::new(Mutex::new(connections)),
+            available: Arc::new(Semaphore::new(count)),
+        })
+    }
+
+    /// Handles the read commands from `rx` concurrently, and forwards the other commands to
+    /// `writer`, which handles them one by one.
+    ///
+    /// A read sent after a write was answered sees that write, but a read might not see a write
+    /// sent just before it that is still being handled.
+    pub(super) async fn dispatch(
+        self,
+        mut rx: Receiver<DataCommands>,
+        writer: Sender<DataCommands>,
+        database_req_histogram: HistogramVec,
+    ) {
+        while let Some(cmd) = rx.recv().await {
+            if !cmd.is_read() {
+                let terminate = matches!(cmd, DataCommands::Terminate {});
+
+                if writer.send(cmd).await.is_err() {
+                    log::error!("database writer stopped;  no longer handling database commands");
+                    break;
+                }
+
+                if terminate {
+                    break;
+                }
+
+                continue;
+            }
+
+            // timed here, because the writer only times the commands it handles
+            let timer = database_req_histogram
+                .with_label_values(&[cmd.as_ref()])
+                .start_timer();
+            let readers = self.clone();
+
+            tokio::spawn(async move {
+                readers.handle(cmd).await;
+                timer.stop_and_record();
+            });
+        }
+    }
+
+    /// Handles the read command `cmd` on one of the connections, waiting for one to become
+    /// available if all are in use.
+    async fn handle(&self, cmd: DataCommands) {
+        let permit = self
+            .available
+            .acquire()
+            .await
+            .expect("the semaphore never to be closed");
+
+        let db = self
+            .connections
+            .lock()
+            .unwrap()
+            .pop()
+            .expect("a connection for each permit");
+
+        match tokio::task::spawn_blocking(move || {
+            handle_read(&db, cmd);
+            db
+        })
+        .await
+        {
+            Ok(db) => self.connections.lock().unwrap().push(db),
+            Err(err) => {
+                // the connection is lost, and so should its permit be
+                log::error!("read-only database connection lost: {err}");
+                permit.forget();
+            }
+        }
+    }
+}
+
+/// Handles a command for which [DataCommands::is_read] holds.
+///
+/// The requester might no longer be waiting for the response (e.g. when its http request
+/// was cancelled) which is why failing to send it is ignored.
+fn handle_read(db: &Connection, cmd: DataCommands) {
+    match cmd {
+        DataCommands::AllHubs { resp } => {
+            let _ = resp.send(get_all_hubs(db));
+        }
+        DataCommands::GetHub { resp, handle } => {
+            let _ = resp.send(get_hub(db, handle));
+        }
+        DataCommands::GetHubid { resp, name } => {
+            let _ = resp.send(get_hubid(db, &name));
+        }
+        DataCommands::AllUsers { resp } => {
+            let _ = resp.send(get_all_users(db));
+        }
+        DataCommands::GetUser {
+            resp,
+            email,
+            telephone,
+        } => {
+            let _ = resp.send(get_user(db, &email, &telephone));
+        }
+        DataCommands::GetUserById { resp, id } => {
+            let _ = resp.send(get_user_by_id(db, id));
+        }
+        DataCommands::GetBarState { resp, id } => {
+            let _ = resp.send(get_bar_state(db, id));
+        }
+        DataCommands::GetLatestPolicy { resp } => {
+            let _ = resp.send(get_latest_policy(db));
+        }
+        cmd => panic!("{} is not a read command", cmd.as_ref()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::HistogramOpts;
+    use tokio::sync::mpsc;
+
+    async fn request<T>(
+        db: &Sender<DataCommands>,
+        cmd: impl FnOnce(oneshot::Sender<T>) -> DataCommands,
+    ) -> oneshot::Receiver<T> {
+        let (tx, rx) = oneshot::channel();
+        db.send(cmd(tx)).await.unwrap();
+        rx
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn reads_do_not_wait_for_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db.sqlite");
+
+        let database_req_histogram = HistogramVec::new(
+            HistogramOpts::new("database_request_duration_seconds", "test"),
+            &["command"],
+        )
+        .unwrap();
+        let (db, db_rx) = mpsc::channel(1_000);
+        make_database_manager(&path, db_rx, database_req_histogram.clone());
+
+        let user = request(&db, |resp| DataCommands::CreateUser {
+            resp,
+            email: "email".to_string(),
+            telephone: "telephone".to_string(),
+            registration_date: "today".to_string(),
+            config: PepContext::test_config(),
+            is_admin: false,
+        })
+        .await
+        .await
+        .unwrap()
+        .unwrap();
+
+        // block the writer
+        let blocker = Connection::open(&path).unwrap();
+        blocker.execute_batch("BEGIN IMMEDIATE").unwrap();
+
+        let update = request(&db, |resp| DataCommands::UpdateBarState {
+            resp,
+            id: user.external_id.clone(),
+            old_etag: bar_state_etag(b""),
+            state: bytes::Bytes::from_static(b"state"),
+        })
+        .await;
+
+        let get = |db| {
+            request(db, |resp| DataCommands::GetBarState {
+                resp,
+                id: user.external_id.clone(),
+            })
+        };
+
+        let bar_state = tokio::time::timeout(std::time::Duration::from_secs(2), get(&db).await)
+            .await
+            .expect("the read not to wait for the blocked write")
+            .unwrap()
+            .unwrap();
+        assert_eq!(bar_state.state, b"");
+
+        blocker.execute_batch("COMMIT").unwrap();
+
+        let etag = update.await.unwrap().unwrap().unwrap();
+
+        // reads see the writes that were answered before them
+        let bar_state = get(&db).await.await.unwrap().unwrap();
+        assert_eq!(bar_state.state, b"state");
+        assert_eq!(bar_state.state_etag, etag);
+
+        // the duration of a read is recorded just after its response has been sent
+        tokio::time::timeout(std::time::Duration::from_secs(2), async {
+            while database_req_histogram
+                .with_label_values(&["GetBarState"])
+                .get_sample_count()
+                < 2
+            {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("both reads to be timed");
+    }
+}
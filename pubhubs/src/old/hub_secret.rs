@@ -0,0 +1,117 @@
+//! Delivers a hub its local decryption keys (the 'Hub secret'), so that hub operators need not
+//! copy them from the admin pages into their configuration by hand.
+//!
+//! The hub authenticates using its OpenID Connect client credentials, and points to its info
+//! endpoint (see [crate::api::hub::Info]), which must be on the same origin as its registered
+//! OpenID Connect redirect uri.  The keys are sealed (see [crate::misc::sealed_box]) to the
+//! verifying key advertised there, so that only the holder of the hub's signing key can read
+//! them.  See [crate::api::phc::hub::DecryptionKey] for the details, and
+//! `pubhubs tools fetch-hub-secret` for the client.
+use crate::api::phc::hub::{DecryptionKeyReq, DecryptionKeyResp};
+use crate::data::{DataCommands, Hub, HubHandle};
+use crate::elgamal::Encoding as _;
+use crate::misc::sealed_box;
+use crate::servers::api;
+use subtle::ConstantTimeEq as _;
+
+pub async fn post(
+    context: actix_web::web::Data<crate::context::Main>,
+    body: actix_web::web::Json<DecryptionKeyReq>,
+) -> api::Result<DecryptionKeyResp> {
+    let req = body.into_inner();
+
+    let hub: Hub = api::return_if_ec!(get_hub(&context, &req.hub).await);
+
+    let creds = hub.oidc_credentials(&context.oidc);
+    let password_ok: bool = creds
+        .password
+        .as_bytes()
+        .ct_eq(req.client_password.as_bytes())
+        .into();
+    if creds.client_id.as_ref() != req.client_id || !password_ok {
+        log::warn!(
+            "someone tried to obtain the decryption key of hub {} ({}) using invalid client credentials",
+            hub.id,
+            hub.name
+        );
+        return api::err(api::ErrorCode::NotAuthenticated);
+    }
+
+    match url::Url::parse(&hub.oidc_redirect_uri) {
+        Ok(redirect_uri) if redirect_uri.origin() == req.info_url.origin() => {}
+        _ => {
+            log::warn!(
+                "hub {} ({}) requested its decryption key using info url {} whose origin differs from its redirect uri {}",
+                hub.id,
+                hub.name,
+                req.info_url,
+                hub.oidc_redirect_uri
+            );
+            return api::err(api::ErrorCode::BadRequest);
+        }
+    }
+
+    let info = api::return_if_ec!(api::query::<api::hub::Info>(&req.info_url, &())
+        .await
+        .into_server_result());
+
+    let keys = zeroize::Zeroizing::new(
+        context
+            .pep
+            .make_local_decryption_keys(&hub)
+            .iter()
+            .map(|key| key.to_hex())
+            .collect::<Vec<String>>()
+            .join(","),
+    );
+
+    let sealed_keys = match sealed_box::seal(keys.as_bytes(), &info.verifying_key, &hub.name) {
+        Ok(sealed_keys) => sealed_keys,
+        Err(err) => {
+            log::warn!(
+                "could not seal decryption key of hub {} ({}) to the key advertised at {}: {err}",
+                hub.id,
+                hub.name,
+                req.info_url,
+            );
+            return api::err(api::ErrorCode::BadRequest);
+        }
+    };
+
+    log::info!(
+        "delivered the decryption key of hub {} ({}) sealed to verifying key {} advertised at {}",
+        hub.id,
+        hub.name,
+        info.verifying_key,
+        req.info_url
+    );
+
+    api::ok(DecryptionKeyResp {
+        sealed_keys: serde_bytes::ByteBuf::from(sealed_keys).into(),
+    })
+}
+
+/// Gets the active hub with the given name.
+async fn get_hub(context: &crate::context::Main, name: &str) -> api::Result<Hub> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    context
+        .db_tx
+        .send(DataCommands::GetHub {
+            resp: tx,
+            handle: HubHandle::Name(name.to_string()),
+        })
+        .await
+        .expect("To use our channel");
+
+    match rx.await.expect("To use our channel") {
+        Ok(hub) => api::ok(hub),
+        Err(err) if crate::data::no_result(&err) => {
+            log::warn!("someone tried to obtain the decryption key of unknown hub {name}");
+            api::err(api::ErrorCode::NotAuthenticated)
+        }
+        Err(err) => {
+            log::error!("could not retrieve hub {name}: {err:?}");
+            api::err(api::ErrorCode::InternalError)
+        }
+    }
+}
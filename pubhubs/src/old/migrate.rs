@@ -0,0 +1,358 @@
+//! Migrates the data of the legacy PubHubs Central (see [crate::data]) to the formats used by the
+//! new servers, see `pubhubs tools migrate-legacy`.
+//!
+//! Hubs are converted to [hub::BasicInfo]s for the `phc.hubs` configuration.  The new servers do
+//! not store users, policies or hub secrets yet, so these are exported as is, to be imported once
+//! they do.  The [MigratedHubSecret]s are needed to reproduce the hubs' decryption keys.
+//! Anything that could not be mapped is listed in the [Report].
+use crate::data::{self, Hub, Hubid, PreviousDecryptionId, UserExport};
+use crate::elgamal::Encoding as _;
+use crate::hub;
+
+use anyhow::{Context as _, Result};
+use rusqlite::Connection;
+use std::collections::HashSet;
+
+/// The result of [migrate].
+#[derive(Debug)]
+pub struct Migration {
+    pub hubs: Vec<hub::BasicInfo>,
+    pub hub_secrets: Vec<MigratedHubSecret>,
+    pub users: Vec<UserExport>,
+    pub policy: Option<MigratedPolicy>,
+    pub report: Report,
+}
+
+/// The decryption ids of a migrated hub, from which PubHubs Central derives the hub's local
+/// decryption key(s), see [Hub::decryption_context_for].
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct MigratedHubSecret {
+    pub hub: hub::Name,
+    pub decryption_id: Hubid,
+
+    /// Only present during the grace period after the last rotation of the hub's secret.
+    pub previous_decryption_id: Option<PreviousDecryptionId>,
+}
+
+/// The latest policy of the legacy PubHubs Central.
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct MigratedPolicy {
+    pub content: String,
+    pub version: u32,
+    pub highlights: Vec<String>,
+}
+
+/// What happened during a [migrate], to reconcile the legacy and the migrated data.
+#[derive(Default, Debug)]
+pub struct Report {
+    /// Changes made to data that was nevertheless migrated, like renamed hubs.
+    pub notes: Vec<String>,
+
+    /// Data that could not be migrated.
+    pub unmapped: Vec<String>,
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for note in self.notes.iter() {
+            writeln!(f, "note:     {note}")?;
+        }
+
+        for unmapped in self.unmapped.iter() {
+            writeln!(f, "UNMAPPED: {unmapped}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads hubs, users and the latest policy from the legacy database `db`.
+///
+/// The info url of a hub is taken to be `info_path` relative to the origin of the hub's OpenID
+/// Connect redirect uri.
+pub fn migrate(db: &Connection, info_path: &str) -> Result<Migration> {
+    let mut report = Report::default();
+
+    let (hubs, hub_secrets) = migrate_hubs(db, info_path, &mut report)?;
+    let users = migrate_users(db, &mut report)?;
+
+    let policy = data::get_latest_policy(db)
+        .context("reading the latest policy")?
+        .map(|policy| MigratedPolicy {
+            content: policy.content,
+            version: policy.version,
+            highlights: policy.highlights,
+        });
+
+    if policy.is_none() {
+        report
+            .notes
+            .push("there is no policy to migrate".to_string());
+    }
+
+    Ok(Migration {
+        hubs,
+        hub_secrets,
+        users,
+        policy,
+        report,
+    })
+}
+
+fn migrate_hubs(
+    db: &Connection,
+    info_path: &str,
+    report: &mut Report,
+) -> Result<(Vec<hub::BasicInfo>, Vec<MigratedHubSecret>)> {
+    let mut names: HashSet<hub::Name> = HashSet::new();
+    let mut result: Vec<hub::BasicInfo> = Vec::new();
+    let mut secrets: Vec<MigratedHubSecret> = Vec::new();
+
+    for legacy in data::get_all_hubs(db).context("reading hubs")? {
+        let Some(info) = migrate_hub(&legacy, info_path, report) else {
+            continue;
+        };
+
+        if !names.insert(info.default_name().clone()) {
+            report.unmapped.push(format!(
+                "hub {:?} ({}): its name {} is already used by another hub",
+                legacy.name,
+                legacy.id,
+                info.default_name()
+            ));
+            continue;
+        }
+
+        secrets.push(MigratedHubSecret {
+            hub: info.default_name().clone(),
+            decryption_id: legacy.decryption_id,
+            previous_decryption_id: legacy
+                .previous_decryption_id
+                .filter(PreviousDecryptionId::is_valid),
+        });
+        result.push(info);
+    }
+
+    let inactive = count_inactive(db, "hub")?;
+    if inactive > 0 {
+        report
+            .notes
+            .push(format!("skipped {inactive} inactive hub(s)"));
+    }
+
+    Ok((result, secrets))
+}
+
+fn migrate_hub(legacy: &Hub, info_path: &str, report: &mut Report) -> Option<hub::BasicInfo> {
+    let Some(name) = legacy_name_to_name(&legacy.name) else {
+        report.unmapped.push(format!(
+            "hub {:?} ({}): its name contains no usable characters",
+            legacy.name, legacy.id
+        ));
+        return None;
+    };
+
+    if name.to_string() != legacy.name {
+        report.notes.push(format!(
+            "hub {:?} ({}) is renamed to {name}",
+            legacy.name, legacy.id
+        ));
+    }
+
+    let info_url = match url::Url::parse(&legacy.oidc_redirect_uri)
+        .and_then(|uri| url::Url::parse(&uri.origin().ascii_serialization())?.join(info_path))
+    {
+        Ok(info_url) if info_url.scheme() == "https" || info_url.scheme() == "http" => info_url,
+        _ => {
+            report.unmapped.push(format!(
+                "hub {:?} ({}): could not derive an info url from its redirect uri {:?}",
+                legacy.name, legacy.id, legacy.oidc_redirect_uri
+            ));
+            return None;
+        }
+    };
+
+    Some(hub::BasicInfo::new(
+        name,
+        legacy.description.clone().into(),
+        info_url,
+        hub::Id::from_legacy_uuid(legacy_uuid(&legacy.id)),
+    ))
+}
+
+/// Turns a legacy hub name, which could be any string, into a [hub::Name], by lower-casing it,
+/// and replacing runs of other characters than `a-z` and `0-9` by an underscore.
+fn legacy_name_to_name(legacy: &str) -> Option<hub::Name> {
+    let mut name = String::with_capacity(legacy.len());
+
+    for c in legacy.chars().map(|c| c.to_ascii_lowercase()) {
+        if c.is_ascii_lowercase() || c.is_ascii_digit() {
+            name.push(c);
+        } else if !name.is_empty() && !name.ends_with('_') {
+            name.push('_');
+        }
+    }
+
+    while name.ends_with('_') {
+        name.pop();
+    }
+
+    name.parse().ok()
+}
+
+fn legacy_uuid(hubid: &Hubid) -> [u8; 16] {
+    uuid::Uuid::parse_str(hubid.as_str())
+        .expect("a hub id to be a valid uuid")
+        .into_bytes()
+}
+
+fn migrate_users(db: &Connection, report: &mut Report) -> Result<Vec<UserExport>> {
+    let mut result: Vec<UserExport> = Vec::new();
+
+    for user in data::get_all_users(db).context("reading users")? {
+        if crate::elgamal::Triple::from_hex(&user.pseudonym).is_none() {
+            report
+                .unmapped
+                .push(format!("user {}: invalid pseudonym", user.external_id));
+            continue;
+        }
+
+        let bar_state = data::get_bar_state(db, user.external_id.clone())
+            .with_context(|| format!("reading bar state of user {}", user.external_id))?;
+
+        result.push(UserExport::new(user, bar_state));
+    }
+
+    let inactive = count_inactive(db, "user")?;
+    if inactive > 0 {
+        report
+            .notes
+            .push(format!("skipped {inactive} inactive user(s)"));
+    }
+
+    Ok(result)
+}
+
+/// Counts the rows of `table` that have `active = FALSE`.
+fn count_inactive(db: &Connection, table: &'static str) -> Result<usize> {
+    db.query_row(
+        &format!("SELECT COUNT(*) FROM {table} WHERE active = FALSE"),
+        [],
+        |row| row.get(0),
+    )
+    .with_context(|| format!("counting inactive rows of {table}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{get_connection_memory, DoMigrations};
+    use crate::pseudonyms::PepContext;
+
+    #[test]
+    fn legacy_names() {
+        assert_eq!(
+            legacy_name_to_name("Organisation Hub").unwrap().to_string(),
+            "organisation_hub"
+        );
+        assert_eq!(
+            legacy_name_to_name(" -- Hub #2!").unwrap().to_string(),
+            "hub_2"
+        );
+        assert_eq!(
+            legacy_name_to_name("testhub").unwrap().to_string(),
+            "testhub"
+        );
+        assert!(legacy_name_to_name("ü ~").is_none());
+    }
+
+    #[test]
+    fn migrate_legacy_database() {
+        let mut db = get_connection_memory(DoMigrations::All).unwrap();
+        let pep = PepContext::test_config();
+
+        let id = data::create_hub(
+            &db,
+            "Test Hub",
+            "some hub",
+            "https://hub.example.com/_synapse/client/oidc/callback",
+            "https://client.example.com",
+        )
+        .unwrap();
+        let rotated =
+            data::rotate_decryption_id(&mut db, id, chrono::Duration::days(1), "admin").unwrap();
+        data::create_hub(&db, "test hub", "same name", "https://example.com", "").unwrap();
+        data::create_hub(&db, "bad uri", "", "not a url", "").unwrap();
+        let inactive = data::create_hub(&db, "inactive", "", "https://example.com", "").unwrap();
+        data::delete_hub(&db, inactive).unwrap();
+
+        let user =
+            data::create_user(&db, "a@example.com", "0612345678", "today", &pep, true).unwrap();
+        data::create_user(&db, "b@example.com", "0612345678", "today", &pep, false).unwrap();
+        db.execute(
+            "UPDATE user SET pseudonym = 'invalid' WHERE email = 'b@example.com'",
+            [],
+        )
+        .unwrap();
+        let etag = data::update_bar_state(
+            &db,
+            user.external_id.clone(),
+            data::get_bar_state(&db, user.external_id.clone())
+                .unwrap()
+                .state_etag,
+            bytes::Bytes::from_static(b"state"),
+        )
+        .unwrap()
+        .unwrap();
+
+        let migration = migrate(&db, ".ph/info").unwrap();
+
+        assert_eq!(migration.hubs.len(), 1);
+        let hub = &migration.hubs[0];
+        assert_eq!(hub.default_name().to_string(), "test_hub");
+        assert_eq!(hub.description().default_text(), "some hub");
+        assert_eq!(hub.info_url().as_str(), "https://hub.example.com/.ph/info");
+        assert_eq!(*hub.id(), hub::Id::from_legacy_uuid(legacy_uuid(&id)));
+
+        assert_eq!(
+            migration.hub_secrets,
+            vec![MigratedHubSecret {
+                hub: hub.default_name().clone(),
+                decryption_id: rotated.decryption_id,
+                previous_decryption_id: rotated.previous_decryption_id,
+            }]
+        );
+        assert!(rotated.previous_decryption_id.is_some());
+
+        assert_eq!(
+            migration.users,
+            vec![UserExport {
+                external_id: user.external_id.clone(),
+                email: "a@example.com".to_string(),
+                telephone: "0612345678".to_string(),
+                pseudonym: user.pseudonym.clone(),
+                registration_date: "today".to_string(),
+                administrator: true,
+                bar_state: serde_bytes::ByteBuf::from(b"state".to_vec()).into(),
+                bar_state_etag: etag,
+            }]
+        );
+
+        assert!(migration.policy.is_none());
+
+        let report = migration.report.to_string();
+        for expected in [
+            "note:     hub \"Test Hub\"",
+            "note:     skipped 1 inactive hub(s)",
+            "UNMAPPED: hub \"test hub\"",
+            "UNMAPPED: hub \"bad uri\"",
+            "UNMAPPED: user",
+        ] {
+            assert!(report.contains(expected), "{expected:?} not in {report}");
+        }
+        assert_eq!(migration.report.unmapped.len(), 3);
+
+        // the migrated hubs are accepted by the new servers
+        hub::Hubs::try_from(migration.hubs).unwrap();
+    }
+}
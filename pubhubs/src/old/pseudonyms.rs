@@ -1,18 +1,22 @@
 use crate::config::having_debug_default;
 use crate::data::Hub;
 use crate::elgamal::{self, Encoding as _};
+use crate::factors::{self, FactorType};
+use crate::misc::secret::SecretBytes;
 use anyhow::{Context as _, Result};
 use curve25519_dalek::scalar::Scalar;
-use hmac::digest::Update as _;
-use sha2::Digest as _;
 use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use zeroize::Zeroize as _;
 
 /// A convenience struct that can be used to share needed configuration around.
+///
+/// The secrets are shared between clones, and are zeroized when the last clone is dropped.
 #[derive(Clone)]
 pub struct PepContext {
     global_public_key: elgamal::PublicKey,
-    global_secret_key: elgamal::PrivateKey,
-    factor_secret: String,
+    global_secret_key: Arc<elgamal::PrivateKey>,
+    factor_secret: Arc<SecretBytes>,
 }
 
 impl Debug for PepContext {
@@ -23,23 +27,16 @@ impl Debug for PepContext {
     }
 }
 
-enum FactorType {
-    Pseudonym,
-    Decryption,
-}
-
-impl FactorType {
-    fn libpepcli_repr(&self) -> &'static str {
-        // https://gitlab.science.ru.nl/bernardg/libpep-cpp/-/blob/65b1f346e0edb8a6606b32e8df7b0c23f8832cec/src/libpep.cpp#L44
-        match self {
-            Self::Pseudonym => "pseudonym",
-            Self::Decryption => "decryption",
-        }
-    }
-}
-
 impl PepContext {
     pub fn from_config(config: crate::config::Pep) -> Result<Self> {
+        let mut global_secret_key_hex = having_debug_default(
+            config.global_secret_key,
+            "b6c57e69b093a34fa2546bc59c7940fd304d91e9c68602d237989d74f1172908",
+            "pep.global_secret_key",
+        )?;
+        let global_secret_key = elgamal::PrivateKey::from_hex(&global_secret_key_hex);
+        global_secret_key_hex.zeroize();
+
         Ok(PepContext {
             global_public_key: elgamal::PublicKey::from_hex(&having_debug_default(
                 config.global_public_key,
@@ -47,29 +44,27 @@ impl PepContext {
                 "pep.global_public_key",
             )?)
             .context("invalid global_public_key")?,
-            global_secret_key: elgamal::PrivateKey::from_hex(&having_debug_default(
-                config.global_secret_key,
-                "b6c57e69b093a34fa2546bc59c7940fd304d91e9c68602d237989d74f1172908",
-                "pep.global_secret_key",
-            )?)
-            .context("invalid global_secret_key")?,
-            factor_secret: having_debug_default(
-                config.factor_secret,
-                "default_factor_secret",
-                "pep.factor_secret",
-            )?,
+            global_secret_key: Arc::new(global_secret_key.context("invalid global_secret_key")?),
+            factor_secret: Arc::new(
+                having_debug_default(
+                    config.factor_secret,
+                    "default_factor_secret",
+                    "pep.factor_secret",
+                )?
+                .into(),
+            ),
         })
     }
 
+    /// Derives factors like libpep-cpp does, see [factors::Version::V0], so that existing
+    /// local pseudonyms remain unchanged.
     fn libpepcli_factor(&self, typ: FactorType, context: &str) -> Scalar {
-        // https://gitlab.science.ru.nl/bernardg/libpep-cpp/-/blob/65b1f346e0edb8a6606b32e8df7b0c23f8832cec/src/libpep.cpp#L37
-        let h = sha2::Sha512::new()
-            .chain(typ.libpepcli_repr())
-            .chain("|")
-            .chain(&self.factor_secret)
-            .chain("|")
-            .chain(context);
-        Scalar::from_hash(h)
+        factors::derive(
+            factors::Version::V0,
+            typ,
+            self.factor_secret.as_bytes(),
+            context,
+        )
     }
 
     /// Generate the (polymorphic and encrypted) pseudonym for a user.
@@ -77,13 +72,34 @@ impl PepContext {
         self.global_public_key.encrypt_random()
     }
 
-    /// Generates the private decryption key for the specified hub
+    /// Generates the private decryption key for the specified hub that is currently in use,
+    /// see [Hub::decryption_id_in_use].
     pub fn make_local_decryption_key(&self, hub: &Hub) -> Result<elgamal::PrivateKey> {
-        Ok(
-            (self.libpepcli_factor(FactorType::Decryption, &hub.decryption_context())
-                * self.global_secret_key.as_scalar())
-            .into(),
-        )
+        Ok(self.make_local_decryption_key_for_context(&hub.decryption_context()))
+    }
+
+    /// Generates the private decryption keys the specified hub should be able to use:  the key for
+    /// its newest [Hub::decryption_id] first, followed by the key that is still in use during the
+    /// grace period after a rotation, if any.
+    pub fn make_local_decryption_keys(&self, hub: &Hub) -> Vec<elgamal::PrivateKey> {
+        let mut keys =
+            vec![
+                self.make_local_decryption_key_for_context(&Hub::decryption_context_for(
+                    &hub.decryption_id,
+                )),
+            ];
+
+        if hub.decryption_id_in_use() != hub.decryption_id {
+            keys.push(self.make_local_decryption_key_for_context(&hub.decryption_context()));
+        }
+
+        keys
+    }
+
+    fn make_local_decryption_key_for_context(&self, context: &str) -> elgamal::PrivateKey {
+        (self.libpepcli_factor(FactorType::Decryption, context)
+            * self.global_secret_key.as_scalar())
+        .into()
     }
 
     /// Turns the given polymorphic pseudonym into an encrypted local pseudonym for `hub`.
@@ -93,11 +109,66 @@ impl PepContext {
         hub: &Hub,
     ) -> Result<elgamal::Triple> {
         // https://gitlab.science.ru.nl/bernardg/libpep-cpp/-/blob/5d5e57c7b410c77824e46ed95e104c5f50cb6057/src/libpep.cpp#L51
-        let s = self.libpepcli_factor(FactorType::Pseudonym, &hub.pseudonymisation_context());
+        let s = self.libpepcli_factor(
+            FactorType::Pseudonymisation,
+            &hub.pseudonymisation_context(),
+        );
         let k = self.libpepcli_factor(FactorType::Decryption, &hub.decryption_context());
 
         Ok(pseudonym.rsk_with_s(&s).and_k(&k))
     }
+
+    /// Turns the given triple encrypting [elgamal::data] for the global public key into a triple
+    /// encrypting the same data for `hub`, decryptable using [Self::make_local_decryption_key].
+    pub fn convert_to_local_data(&self, data: elgamal::Triple, hub: &Hub) -> elgamal::Triple {
+        let k = self.libpepcli_factor(FactorType::Decryption, &hub.decryption_context());
+
+        data.rekey(&k)
+    }
+
+    /// Turns the given encrypted local pseudonyms for hub `from` into encrypted local pseudonyms
+    /// of the same users for hub `to`, in place, without decrypting them.  Used when hubs merge,
+    /// or when a hub changes its id.
+    ///
+    /// Fails, leaving `encrypted_local_pseudonyms` untouched, when one of them is not encrypted
+    /// for `from`'s local decryption key.
+    pub fn convert_between_hubs(
+        &self,
+        encrypted_local_pseudonyms: &mut [elgamal::Triple],
+        from: &Hub,
+        to: &Hub,
+    ) -> Result<()> {
+        let from_k = self.libpepcli_factor(FactorType::Decryption, &from.decryption_context());
+
+        let from_pk: elgamal::PublicKey =
+            elgamal::PrivateKey::from(from_k * self.global_secret_key.as_scalar()).public_key();
+
+        if let Some(i) = encrypted_local_pseudonyms
+            .iter()
+            .position(|elp| !elp.targets(&from_pk))
+        {
+            anyhow::bail!(
+                "encrypted local pseudonym #{i} is not encrypted for hub {}",
+                from.id
+            );
+        }
+
+        let from_s = self.libpepcli_factor(
+            FactorType::Pseudonymisation,
+            &from.pseudonymisation_context(),
+        );
+
+        // removes the factors applied by convert_to_local_pseudonym for 'from',
+        // and applies those for 'to' instead
+        let s = self.libpepcli_factor(FactorType::Pseudonymisation, &to.pseudonymisation_context())
+            * from_s.invert();
+        let k = self.libpepcli_factor(FactorType::Decryption, &to.decryption_context())
+            * from_k.invert();
+
+        elgamal::batch::rsk(encrypted_local_pseudonyms, elgamal::rsk::SAndK::new(&s, &k));
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -141,6 +212,7 @@ mod tests {
         let hub = Hub {
             id: Hubid::from_str("936da01f-9abd-4d9d-80c7-02af85c822a8").unwrap(),
             decryption_id: Hubid::from_str("936da01f-9abd-4d9d-80c7-02af85c822a8").unwrap(),
+            previous_decryption_id: None,
             name: "not used".to_string(),
             active: false,
             oidc_redirect_uri: "https://not-used.com".to_string(),
@@ -179,6 +251,57 @@ mod tests {
             local_pseudonym.to_hex(),
             "ccc4b20926e440f7d992b2483b97cdfbeeec5b83aa344a8d42689aa4d23e8a4a"
         );
+
+        let data = pep.global_public_key.encrypt_data(b"over 18").unwrap();
+        assert_eq!(
+            pep.convert_to_local_data(data, &hub)
+                .decrypt_data(&local_decryption_key)
+                .unwrap(),
+            b"over 18"
+        );
+    }
+
+    #[test]
+    fn test_convert_between_hubs() {
+        let pep = PepContext::test_config();
+
+        let hub = |id: &str| Hub {
+            id: Hubid::from_str(id).unwrap(),
+            decryption_id: Hubid::from_str(id).unwrap(),
+            previous_decryption_id: None,
+            name: "not used".to_string(),
+            active: true,
+            oidc_redirect_uri: "https://not-used.com".to_string(),
+            description: "not used".to_string(),
+            client_uri: "not_used".to_string(),
+        };
+        let a = hub("936da01f-9abd-4d9d-80c7-02af85c822a8");
+        let b = hub("c6a2b2a5-1d7b-4a5e-8f4c-27d0e1f1f6a3");
+
+        let pseudonyms: Vec<elgamal::Triple> = (0..3).map(|_| pep.generate_pseudonym()).collect();
+
+        let mut elps: Vec<elgamal::Triple> = pseudonyms
+            .iter()
+            .map(|pp| pep.convert_to_local_pseudonym(pp.clone(), &a).unwrap())
+            .collect();
+
+        pep.convert_between_hubs(&mut elps, &a, &b).unwrap();
+
+        let b_key = pep.make_local_decryption_key(&b).unwrap();
+
+        for (elp, pp) in elps.iter().zip(pseudonyms.iter()) {
+            assert_eq!(
+                elp.clone().decrypt(&b_key),
+                pep.convert_to_local_pseudonym(pp.clone(), &b)
+                    .unwrap()
+                    .decrypt(&b_key)
+            );
+        }
+
+        // the triples are now encrypted for b, and no longer for a
+        let before = elps.clone();
+        assert!(pep.convert_between_hubs(&mut elps, &a, &b).is_err());
+        assert_eq!(elps, before);
     }
 
     impl PepContext {
@@ -189,11 +312,13 @@ mod tests {
                     "1c561577b91b0ea945a95161dd1fe44c1433ff6a21419aa606838a9db5c6106c",
                 )
                 .unwrap(),
-                global_secret_key: elgamal::PrivateKey::from_hex(
-                    "1ff1accd4b711f1e3b149fdbe2254fb3397b3f2b1fd09f15c8d79c1a99b5330b",
-                )
-                .unwrap(),
-                factor_secret: "some secret".to_string(),
+                global_secret_key: Arc::new(
+                    elgamal::PrivateKey::from_hex(
+                        "1ff1accd4b711f1e3b149fdbe2254fb3397b3f2b1fd09f15c8d79c1a99b5330b",
+                    )
+                    .unwrap(),
+                ),
+                factor_secret: Arc::new("some secret".to_string().into()),
             }
         }
     }
@@ -0,0 +1,132 @@
+//! Admin endpoint to carry the local pseudonyms of a hub's users over to another hub,
+//! for example when two hubs merge, or when a hub gets a new id.
+//!
+//! The hub being merged (`from`) decrypts nothing:  it sends its users' encrypted local
+//! pseudonyms, which it obtains by encrypting the local pseudonyms it stores for its users
+//! using its own public key (see `pubhubs_public_key` in the ABI), in batches to
+//!
+//! ```text
+//! POST /admin/hubs/{from}/repseudonymise/{to}
+//! X-Admin-API-Key: ...
+//!
+//! { "encrypted_local_pseudonyms": [ "<hex encoded triple>", ... ] }
+//! ```
+//!
+//! and gets back, in the same order, the encrypted local pseudonyms of the same users for
+//! hub `to`, which only `to` can decrypt.  Since hubs are only found when active, convert
+//! before deactivating `from`.
+use crate::data::{DataCommands, Hub, HubHandle, Hubid};
+use crate::elgamal::{self, Encoding as _};
+use crate::error::AnyhowExt as _;
+use crate::error::TranslatedError;
+use anyhow::Result;
+use std::str::FromStr as _;
+
+pub mod reason {
+    pub const INVALID_HUB_ID: &str = "Bad Request - not a hub id";
+    pub const UNKNOWN_HUB: &str = "Not Found - no (active) hub with this id";
+    pub const INVALID_TRIPLE: &str = "Bad Request - not a hex-encoded encrypted local pseudonym";
+    pub const WRONG_HUB: &str =
+        "Bad Request - not all encrypted local pseudonyms were encrypted for the 'from' hub";
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct EncryptedLocalPseudonyms {
+    pub encrypted_local_pseudonyms: Vec<String>,
+}
+
+pub async fn post(
+    req: actix_web::HttpRequest,
+    path: actix_web::web::Path<(String, String)>,
+    context: actix_web::web::Data<crate::context::Main>,
+    body: actix_web::web::Json<EncryptedLocalPseudonyms>,
+) -> Result<actix_web::HttpResponse, TranslatedError> {
+    let (from, to) = path.into_inner();
+
+    post_anyhow(&context, &from, &to, body.into_inner())
+        .await
+        .into_translated_error(&req)
+}
+
+async fn post_anyhow(
+    context: &crate::context::Main,
+    from: &str,
+    to: &str,
+    body: EncryptedLocalPseudonyms,
+) -> Result<actix_web::HttpResponse> {
+    macro_rules! bad_req {
+        ($r:expr) => {
+            return Ok(actix_web::HttpResponse::BadRequest().reason($r).finish())
+        };
+    }
+
+    let from: Hub = match get_hub(context, from).await? {
+        Ok(hub) => hub,
+        Err(resp) => return Ok(resp),
+    };
+    let to: Hub = match get_hub(context, to).await? {
+        Ok(hub) => hub,
+        Err(resp) => return Ok(resp),
+    };
+
+    let mut elps: Vec<elgamal::Triple> = Vec::with_capacity(body.encrypted_local_pseudonyms.len());
+
+    for elp in body.encrypted_local_pseudonyms.iter() {
+        match elgamal::Triple::from_hex(elp) {
+            Some(elp) => elps.push(elp),
+            None => bad_req!(reason::INVALID_TRIPLE),
+        }
+    }
+
+    if context
+        .pep
+        .convert_between_hubs(&mut elps, &from, &to)
+        .is_err()
+    {
+        bad_req!(reason::WRONG_HUB);
+    }
+
+    log::info!(
+        "converted {} encrypted local pseudonyms from hub {} ({}) to hub {} ({})",
+        elps.len(),
+        from.id,
+        from.name,
+        to.id,
+        to.name
+    );
+
+    Ok(
+        actix_web::HttpResponse::Ok().json(EncryptedLocalPseudonyms {
+            encrypted_local_pseudonyms: elps.iter().map(|elp| elp.to_hex()).collect(),
+        }),
+    )
+}
+
+/// Gets the active hub with the given id, or the response to return when there is no such hub.
+async fn get_hub(
+    context: &crate::context::Main,
+    id: &str,
+) -> Result<Result<Hub, actix_web::HttpResponse>> {
+    let Ok(id) = Hubid::from_str(id) else {
+        return Ok(Err(actix_web::HttpResponse::BadRequest()
+            .reason(reason::INVALID_HUB_ID)
+            .finish()));
+    };
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    context
+        .db_tx
+        .send(DataCommands::GetHub {
+            resp: tx,
+            handle: HubHandle::Id(id),
+        })
+        .await?;
+
+    match rx.await? {
+        Ok(hub) => Ok(Ok(hub)),
+        Err(err) if crate::data::no_result(&err) => Ok(Err(actix_web::HttpResponse::NotFound()
+            .reason(reason::UNKNOWN_HUB)
+            .finish())),
+        Err(err) => Err(err),
+    }
+}
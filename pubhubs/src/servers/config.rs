@@ -7,6 +7,7 @@ use anyhow::{Context as _, Result};
 use rand::Rng as _;
 use url::Url;
 
+use crate::elgamal;
 use crate::hub;
 use crate::misc::serde_ext;
 use crate::servers::for_all_servers;
@@ -44,9 +45,10 @@ pub struct ServerConfig<ServerSpecific> {
     /// May be set manually when multiple instances of the same server are used.
     pub self_check_code: Option<String>,
 
-    /// Key used to sign JSON web tokens generated by this server.
-    /// If `None`, one is generated automatically (which is not suitable for production.)
-    pub jwt_key: Option<serde_ext::B16<ed25519_dalek::SigningKey>>,
+    /// Hex-encoded key used to sign JSON web tokens generated by this server, see [serde_ext::Secret]
+    /// for how to specify it.  If `None`, one is generated automatically (which is not suitable
+    /// for production.)
+    pub jwt_key: Option<serde_ext::Secret<serde_ext::B16<ed25519_dalek::SigningKey>>>,
 
     #[serde(flatten)]
     pub extra: ServerSpecific,
@@ -63,6 +65,22 @@ impl<Extra> ServerConfig<Extra> {
     }
 }
 
+impl<Extra: LoadSecrets> LoadSecrets for ServerConfig<Extra> {
+    fn load_secrets(&mut self, wd: &Path) -> Result<()> {
+        if let Some(jwt_key) = self.jwt_key.as_mut() {
+            jwt_key.load(wd).context("loading jwt_key")?;
+        }
+
+        self.extra.load_secrets(wd)
+    }
+}
+
+/// Implemented by the parts of [Config] that contain [serde_ext::Secret]s.
+pub trait LoadSecrets {
+    /// Calls [serde_ext::Secret::load] on all secrets.
+    fn load_secrets(&mut self, wd: &Path) -> Result<()>;
+}
+
 impl Config {
     /// Loads [Config] from `path`.  
     ///
@@ -98,6 +116,10 @@ impl Config {
             );
         }
 
+        res.load_secrets().with_context(|| {
+            format!("could not load secrets for config file {}", path.display())
+        })?;
+
         log::info!(
             "loaded config file from {};  interpretting relative paths in {}",
             path.display(),
@@ -106,6 +128,22 @@ impl Config {
 
         Ok(Some(res))
     }
+
+    fn load_secrets(&mut self) -> Result<()> {
+        macro_rules! load_server_secrets {
+            ($server:ident) => {
+                if let Some(server_config) = self.$server.as_mut() {
+                    server_config
+                        .load_secrets(&self.wd)
+                        .context(stringify!($server))?;
+                }
+            };
+        }
+
+        for_all_servers!(load_server_secrets);
+
+        Ok(())
+    }
 }
 
 pub mod phc {
@@ -120,10 +158,21 @@ pub mod phc {
         /// Where can we reach the authentication server?
         pub auths_url: Url,
 
-        pub master_private_key_part: Option<serde_ext::B16<curve25519_dalek::Scalar>>,
+        /// PHC's part of the master ElGamal private key, hex-encoded, see [serde_ext::Secret]
+        pub master_private_key_part: Option<serde_ext::Secret<serde_ext::B16<elgamal::PrivateKey>>>,
 
-        /// The hubs that are known to us
-        pub hubs: Vec<hub::BasicInfo>,
+        /// The hubs that are known to us.  No two hubs may share a name or id.
+        pub hubs: hub::Hubs,
+    }
+
+    impl LoadSecrets for ExtraConfig {
+        fn load_secrets(&mut self, wd: &Path) -> Result<()> {
+            if let Some(key) = self.master_private_key_part.as_mut() {
+                key.load(wd).context("loading master_private_key_part")?;
+            }
+
+            Ok(())
+        }
     }
 }
 
@@ -132,7 +181,19 @@ pub mod transcryptor {
 
     #[derive(serde::Deserialize, Debug, Clone)]
     #[serde(deny_unknown_fields)]
-    pub struct ExtraConfig {}
+    pub struct ExtraConfig {
+        /// Secret from which the Transcryptor derives its factors for each hub, see
+        /// [crate::factors], hex-encoded, see [serde_ext::Secret].
+        ///
+        /// Never change it, because the users' pseudonyms are derived from it.
+        pub factor_secret: serde_ext::Secret<serde_ext::B16>,
+    }
+
+    impl LoadSecrets for ExtraConfig {
+        fn load_secrets(&mut self, wd: &Path) -> Result<()> {
+            self.factor_secret.load(wd).context("loading factor_secret")
+        }
+    }
 }
 
 pub mod auths {
@@ -141,6 +202,12 @@ pub mod auths {
     #[derive(serde::Deserialize, Debug, Clone)]
     #[serde(deny_unknown_fields)]
     pub struct ExtraConfig {}
+
+    impl LoadSecrets for ExtraConfig {
+        fn load_secrets(&mut self, _wd: &Path) -> Result<()> {
+            Ok(())
+        }
+    }
 }
 
 pub trait GetServerConfig {
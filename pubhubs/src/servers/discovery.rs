@@ -1,4 +1,4 @@
-use crate::misc::fmt_ext;
+use crate::misc::{fmt_ext, task};
 use crate::servers::{self, api, Constellation};
 
 use anyhow::ensure;
@@ -49,11 +49,21 @@ pub async fn drive_discovery(phc_url: &url::Url) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// How discovery retries its queries.  Discovery is driven while the servers are being started,
+/// possibly on other machines, so there's no telling how long that takes: we wait without deadline.
+fn retry_options() -> task::RetryOptions {
+    task::RetryOptions {
+        deadline: None,
+        ..Default::default()
+    }
+}
+
 /// Drive discovery of the server at the given url, returns the
 /// [api::DiscoveryInfoResp] returned by the server when discovery has been completed.
 async fn drive_discovery_of(url: &url::Url) -> anyhow::Result<api::DiscoveryInfoResp> {
     let inf = {
-        let res = api::query::<api::DiscoveryInfo>(url, &()).await;
+        let res =
+            api::query_with_retry_options::<api::DiscoveryInfo>(url, &(), &retry_options()).await;
         ensure!(
             res.is_ok(),
             "could not get discovery info from {}: {}",
@@ -67,7 +77,7 @@ async fn drive_discovery_of(url: &url::Url) -> anyhow::Result<api::DiscoveryInfo
         return Ok(inf);
     }
 
-    let res = api::query_with_retry::<api::DiscoveryRun>(url, &()).await;
+    let res = api::query_with_retry_options::<api::DiscoveryRun>(url, &(), &retry_options()).await;
     ensure!(
         res.is_ok(),
         "running discovery of {} at {} failed: {}",
@@ -76,24 +86,25 @@ async fn drive_discovery_of(url: &url::Url) -> anyhow::Result<api::DiscoveryInfo
         fmt_ext::Json(res.unwrap_err())
     );
 
-    crate::misc::task::retry(|| async {
-        let res = api::query::<api::DiscoveryInfo>(url, &()).await.retryable();
-
-        // retry if query returned a retryable error,
-        // or if PHC's state is still Discovery
-        match res.as_ref() {
-            Ok(Some(inf)) => {
-                if inf.state == api::ServerState::Discovery {
-                    Ok(None)
-                } else {
-                    res
+    retry_options()
+        .retry(|| async {
+            let res = api::query::<api::DiscoveryInfo>(url, &()).await.retryable();
+
+            // retry if query returned a retryable error,
+            // or if PHC's state is still Discovery
+            match res.as_ref() {
+                Ok(Some(inf)) => {
+                    if inf.state == api::ServerState::Discovery {
+                        Ok(None)
+                    } else {
+                        res
+                    }
                 }
+                _ => res,
             }
-            _ => res,
-        }
-    })
-    .await?
-    .ok_or_else(|| anyhow::anyhow!("timeout waiting for {} to leave discovery state", inf.name))
+        })
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("timeout waiting for {} to leave discovery state", inf.name))
 }
 
 /// Specifies what to check about  a [api::DiscoveryInfoResp]
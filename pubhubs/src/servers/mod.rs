@@ -1,6 +1,6 @@
 //! New, multi-server setup
 
-mod api;
+pub(crate) mod api;
 mod config;
 mod constellation;
 mod discovery;
@@ -18,6 +18,6 @@ pub use discovery::drive_discovery;
 pub(super) use macros::for_all_servers;
 pub use run::run;
 pub(super) use server::{
-    App, AppBase, AppCreator, AppCreatorBase, Name, Server, ServerBase, ShutdownCommand,
+    App, AppBase, AppCreator, AppCreatorBase, AppMethod, Name, Server, ServerBase, ShutdownCommand,
     ShutdownSender,
 };
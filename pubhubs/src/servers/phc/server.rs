@@ -1,16 +1,31 @@
+use std::collections::HashMap;
 use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 
+use actix_web::http::header::{AcceptLanguage, Preference};
 use actix_web::web;
 
 use futures_util::future::LocalBoxFuture;
 
-use crate::servers::{self, api, discovery, AppBase, AppCreatorBase, Constellation, ServerBase};
+use crate::api::phc::hub;
+use crate::api::transcryptor as tr;
+use crate::api::EndpointDetails as _;
+use crate::elgamal::{proof, PrivateKey, Triple};
+use crate::servers::{
+    self, api, discovery, AppBase, AppCreatorBase, AppMethod, Constellation, ServerBase,
+};
 
 /// PubHubs Central server
 pub struct Server {
     base: ServerBase,
+
+    /// Commitments to the Transcryptor's factors per hub, pinned on first use.  Kept here, so
+    /// that they survive restarts.
+    rsk_commitments: RskCommitments,
 }
 
+type RskCommitments = Arc<Mutex<HashMap<crate::hub::Id, proof::FactorCommitments>>>;
+
 impl crate::servers::Server for Server {
     const NAME: crate::servers::Name = crate::servers::Name::PubhubsCentral;
     type AppT = Rc<App>;
@@ -19,6 +34,7 @@ impl crate::servers::Server for Server {
     fn new(config: &crate::servers::Config) -> Self {
         Server {
             base: ServerBase::new::<Server>(config),
+            rsk_commitments: Default::default(),
         }
     }
 
@@ -29,6 +45,8 @@ impl crate::servers::Server for Server {
             base: AppCreatorBase::new(&self.base),
             transcryptor_url: xconf.transcryptor_url.clone(),
             auths_url: xconf.auths_url.clone(),
+            hubs: Arc::new(xconf.hubs.clone()),
+            rsk_commitments: self.rsk_commitments.clone(),
         }
     }
 
@@ -41,10 +59,21 @@ pub struct App {
     base: AppBase<Server>,
     transcryptor_url: url::Url,
     auths_url: url::Url,
+    hubs: Arc<crate::hub::Hubs>,
+    rsk_commitments: RskCommitments,
 }
 
 impl crate::servers::App<Server> for Rc<App> {
-    fn configure_actix_app(&self, _sc: &mut web::ServiceConfig) {}
+    fn configure_actix_app(&self, sc: &mut web::ServiceConfig) {
+        sc.route(
+            hub::Resolve::PATH,
+            web::method(hub::Resolve::METHOD).to(AppMethod::new(self, App::handle_hub_resolve)),
+        )
+        .route(
+            hub::Directory::PATH,
+            web::method(hub::Directory::METHOD).to(AppMethod::new(self, App::handle_hub_directory)),
+        );
+    }
 
     fn discover(
         &self,
@@ -59,6 +88,12 @@ impl crate::servers::App<Server> for Rc<App> {
             let tdi = api::return_if_ec!(tdi_res);
             let asdi = api::return_if_ec!(asdi_res);
 
+            // Check that the Transcryptor proves its rsks for each hub, pinning its commitments.
+            let pk = PrivateKey::random().public_key();
+            for hub in self.hubs.iter() {
+                api::return_if_ec!(self.rsk(*hub.id(), vec![pk.encrypt_random()]).await);
+            }
+
             api::ok(crate::servers::Constellation {
                 phc_url: self.base.phc_url.clone(),
                 phc_jwt_key: self.base.jwt_key.verifying_key().into(),
@@ -76,6 +111,102 @@ impl crate::servers::App<Server> for Rc<App> {
 }
 
 impl App {
+    /// Implements [hub::Resolve]
+    async fn handle_hub_resolve(
+        app: Rc<Self>,
+        req: web::Json<hub::ResolveReq>,
+    ) -> api::Result<hub::ResolveResp> {
+        let Some(resolved) = app.hubs.resolve(&req.name_or_id) else {
+            return api::err(api::ErrorCode::UnknownHub);
+        };
+
+        api::ok(hub::ResolveResp {
+            hub: resolved.hub.clone(),
+            redirect_to: (!resolved.by_default_name).then(|| resolved.hub.default_name().clone()),
+        })
+    }
+
+    /// Implements [hub::Directory]
+    async fn handle_hub_directory(
+        app: Rc<Self>,
+        req: web::Json<hub::DirectoryReq>,
+        accept_language: Option<web::Header<AcceptLanguage>>,
+    ) -> api::Result<hub::DirectoryResp> {
+        let preferred: Vec<String> = req
+            .lang
+            .iter()
+            .cloned()
+            .chain(
+                accept_language
+                    .iter()
+                    .flat_map(|al| al.ranked())
+                    .filter_map(|pref| match pref {
+                        Preference::Specific(tag) => Some(tag.primary_language().to_string()),
+                        Preference::Any => None,
+                    }),
+            )
+            .collect();
+
+        api::ok(hub::DirectoryResp {
+            hubs: app
+                .hubs
+                .iter()
+                .map(|info| {
+                    let (lang, description) = info.description().get(&preferred);
+
+                    hub::DirectoryEntry {
+                        name: info.default_name().clone(),
+                        id: *info.id(),
+                        description: description.to_string(),
+                        description_lang: lang.map(str::to_string),
+                        info_url: info.info_url().clone(),
+                        tags: info.tags().to_vec(),
+                        contact_url: info.contact_url().cloned(),
+                        logo_url: info.logo_url().cloned(),
+                    }
+                })
+                .collect(),
+        })
+    }
+
+    /// Has the Transcryptor apply [Triple::rsk] to `triples` with its factors for `hub`, see
+    /// [tr::Rsk].  Fails unless all proofs check out against the commitments pinned for `hub`,
+    /// which are taken from the first response that checks out.
+    async fn rsk(&self, hub: crate::hub::Id, triples: Vec<Triple>) -> api::Result<Vec<Triple>> {
+        let req = tr::RskReq {
+            hub,
+            triples: triples.into_iter().map(Into::into).collect(),
+        };
+
+        let resp = api::return_if_ec!(api::query::<tr::Rsk>(&self.transcryptor_url, &req)
+            .await
+            .into_server_result());
+
+        let pinned = self.rsk_commitments.lock().unwrap().get(&hub).cloned();
+        let commitments = pinned.unwrap_or_else(|| (*resp.commitments).clone());
+
+        let Some(result) = resp.verify(&req, &commitments) else {
+            log::error!(
+                "transcryptor's rsk for hub {} did not check out against {} commitments",
+                hub,
+                if commitments == *resp.commitments {
+                    "its own"
+                } else {
+                    "the pinned"
+                }
+            );
+            return api::err(api::ErrorCode::InternalError);
+        };
+
+        self.rsk_commitments
+            .lock()
+            .unwrap()
+            .entry(hub)
+            .or_insert(commitments);
+
+        api::ok(result)
+    }
+
     /// Obtains and checks [api::DiscoveryInfoResp] from the given server
     async fn discovery_info_of(
         &self,
@@ -101,6 +232,8 @@ pub struct AppCreator {
     base: AppCreatorBase,
     transcryptor_url: url::Url,
     auths_url: url::Url,
+    hubs: Arc<crate::hub::Hubs>,
+    rsk_commitments: RskCommitments,
 }
 
 impl crate::servers::AppCreator<Server> for AppCreator {
@@ -109,6 +242,8 @@ impl crate::servers::AppCreator<Server> for AppCreator {
             base: AppBase::new(&self.base, shutdown_sender),
             transcryptor_url: self.transcryptor_url.clone(),
             auths_url: self.auths_url.clone(),
+            hubs: self.hubs.clone(),
+            rsk_commitments: self.rsk_commitments.clone(),
         })
     }
 }
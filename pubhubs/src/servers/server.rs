@@ -193,7 +193,8 @@ pub struct ServerBase {
     pub config: crate::servers::Config,
     pub state: State,
     pub self_check_code: String,
-    pub jwt_key: ed25519_dalek::SigningKey,
+    /// Shared with the [AppCreatorBase] and [AppBase]s, so that no copies linger in the workers
+    pub jwt_key: Arc<ed25519_dalek::SigningKey>,
 }
 
 impl ServerBase {
@@ -206,13 +207,13 @@ impl ServerBase {
                 task_lock: Arc::new(tokio::sync::Mutex::new(())),
             },
             self_check_code: server_config.self_check_code(),
-            jwt_key: server_config
-                .jwt_key
-                .clone()
-                .unwrap_or_else(|| {
-                    ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng).into()
-                })
-                .into_inner(),
+            jwt_key: Arc::new(
+                server_config
+                    .jwt_key
+                    .clone()
+                    .map(|key| key.into_value().into_inner())
+                    .unwrap_or_else(|| ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng)),
+            ),
         }
     }
 }
@@ -223,7 +224,7 @@ pub struct AppCreatorBase {
     pub state: State,
     pub phc_url: url::Url,
     pub self_check_code: String,
-    pub jwt_key: ed25519_dalek::SigningKey,
+    pub jwt_key: Arc<ed25519_dalek::SigningKey>,
 }
 
 impl AppCreatorBase {
@@ -243,7 +244,7 @@ pub struct AppBase<S: Server> {
     pub shutdown_sender: ShutdownSender<S>,
     pub self_check_code: String,
     pub phc_url: url::Url,
-    pub jwt_key: ed25519_dalek::SigningKey,
+    pub jwt_key: Arc<ed25519_dalek::SigningKey>,
 }
 
 impl<S: Server> AppBase<S> {
@@ -313,7 +314,8 @@ impl<S: Server> AppBase<S> {
         .route(
             api::DiscoveryInfo::PATH,
             web::method(api::DiscoveryInfo::METHOD).to(app_method!(handle_discovery_info)),
-        );
+        )
+        .route(api::JWKS_PATH, web::get().to(app_method!(handle_jwks)));
     }
 
     /// Run the discovery process, and restarts server if necessary.  Returns when
@@ -397,6 +399,17 @@ impl<S: Server> AppBase<S> {
         .check(pdi, &base.phc_url)
     }
 
+    /// Publishes [AppBase::jwt_key] as JSON Web Key Set, so that JWTs from this server can be
+    /// verified using off-the-shelf libraries.
+    async fn handle_jwks(app: S::AppT) -> web::Json<serde_json::Value> {
+        web::Json(serde_json::json!({
+            // See RFC8037 and RFC7517.
+            "keys": [
+                crate::misc::jwt::SigningKey::jwk(&*app.base().jwt_key),
+            ]
+        }))
+    }
+
     async fn handle_discovery_info(app: S::AppT) -> api::Result<api::DiscoveryInfoResp> {
         let app_base = app.base();
 
@@ -426,7 +439,7 @@ pub struct AppMethod<App, F> {
 }
 
 impl<App: Clone, F> AppMethod<App, F> {
-    fn new(app: &App, f: F) -> Self {
+    pub fn new(app: &App, f: F) -> Self {
         AppMethod {
             app: app.clone(),
             f,
@@ -2,7 +2,11 @@ use std::rc::Rc;
 
 use actix_web::web;
 
-use crate::servers::{AppBase, AppCreatorBase, ServerBase, ShutdownSender};
+use crate::api::transcryptor as tr;
+use crate::api::EndpointDetails as _;
+use crate::elgamal::{proof, rsk};
+use crate::factors::{self, FactorType};
+use crate::servers::{api, AppBase, AppCreatorBase, AppMethod, ServerBase, ShutdownSender};
 
 /// Transcryptor
 pub struct Server {
@@ -21,8 +25,11 @@ impl crate::servers::Server for Server {
     }
 
     fn app_creator(&self) -> AppCreator {
+        let xconf = &self.base.config.transcryptor.as_ref().unwrap().extra;
+
         AppCreator {
             base: AppCreatorBase::new(&self.base),
+            factor_secret: xconf.factor_secret.value().to_vec(),
         }
     }
 
@@ -33,25 +40,69 @@ impl crate::servers::Server for Server {
 
 pub struct App {
     base: AppBase<Server>,
+    factor_secret: Vec<u8>,
 }
 
 impl crate::servers::App<Server> for Rc<App> {
-    fn configure_actix_app(&self, _sc: &mut web::ServiceConfig) {}
+    fn configure_actix_app(&self, sc: &mut web::ServiceConfig) {
+        sc.route(
+            tr::Rsk::PATH,
+            web::method(tr::Rsk::METHOD).to(AppMethod::new(self, App::handle_rsk)),
+        );
+    }
 
     fn base(&self) -> &AppBase<Server> {
         &self.base
     }
 }
 
+impl App {
+    /// Implements [tr::Rsk]
+    async fn handle_rsk(app: Rc<Self>, req: web::Json<tr::RskReq>) -> api::Result<tr::RskResp> {
+        let context = req.hub.to_string();
+        let s = factors::derive(
+            factors::Version::V1,
+            FactorType::Pseudonymisation,
+            &app.factor_secret,
+            &context,
+        );
+        let k = factors::derive(
+            factors::Version::V1,
+            FactorType::Decryption,
+            &app.factor_secret,
+            &context,
+        );
+
+        api::ok(tr::RskResp {
+            commitments: proof::FactorCommitments::new(&s, &k).into(),
+            results: req
+                .triples
+                .iter()
+                .map(|triple| {
+                    let (triple, proof) =
+                        (**triple).clone().rsk_with_proof(rsk::SAndK::new(&s, &k));
+
+                    tr::RskResult {
+                        triple: triple.into(),
+                        proof: proof.into(),
+                    }
+                })
+                .collect(),
+        })
+    }
+}
+
 #[derive(Clone)]
 pub struct AppCreator {
     base: AppCreatorBase,
+    factor_secret: Vec<u8>,
 }
 
 impl crate::servers::AppCreator<Server> for AppCreator {
     fn create(&self, shutdown_sender: &ShutdownSender<Server>) -> Rc<App> {
         Rc::new(App {
             base: AppBase::new(&self.base, shutdown_sender),
+            factor_secret: self.factor_secret.clone(),
         })
     }
 }
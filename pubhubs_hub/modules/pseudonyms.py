@@ -1,7 +1,8 @@
 #
-# This module does two things:
+# This module does three things:
 #   - decrypt pseudonym from pubhubs server at registration / login
 #   - makes sure that the displayname always ends with pseudonym or is the same as the pseudonym
+#   - deactivates the account of a user that deleted their PubHubs account
 #
 
 import re
@@ -10,12 +11,14 @@ import logging
 import subprocess
 import ctypes
 
-from synapse.types import UserID
+from synapse.types import UserID, create_requester
 from synapse.module_api import ModuleApi
 from synapse.http.server import DirectServeJsonResource, respond_with_json
 from synapse.http.site import SynapseRequest
 from synapse.handlers.oidc import UserAttributeDict
 from synapse.module_api.errors import ConfigError
+from authlib.jose import JsonWebToken
+from authlib.jose.errors import JoseError
 
 logger = logging.getLogger(__name__)
 
@@ -61,37 +64,52 @@ class Pseudonym:
 
 
 
-# Oidc mapping provider for PubHubs that decrypts the encrypted local pseudonym, and
-# turns it into a short pseudonym.
-# 
-# References:
-#   - https://matrix-org.github.io/synapse/latest/sso_mapping_providers.html
-#   - https://github.com/matrix-org/synapse/blob/6ac35667af31f6d3aa81a8b5d00425e6e7e657e7/synapse/handlers/oidc.py#L1532
-class OidcMappingProvider:
-    def __init__(self, config):
-        self._config = config
-        self._secret = os.environ['HUB_SECRET']
-        self._libpubhubs = ctypes.CDLL(config["libpubhubspath"])
-
-    @staticmethod
-    def parse_config(config):
-        if "libpubhubspath" not in config:
-            logger.error(f"the invalid config was: {config}")
-            raise ConfigError("Please configure 'libpubhubspath'")
-        return config
-
-    def get_remote_user_id(self, userinfo):
-        logger.info(f"get_remote_user_id {userinfo}")
-        encrypted_local_pseudonym = userinfo["sub"]
-
+# The hub's decryption keys from HUB_SECRET, loaded into libpubhubs.so, used to decrypt the
+# encrypted local pseudonyms sent by PubHubs Central.
+class HubKeys:
+    def __init__(self, libpubhubspath):
+        self._libpubhubs = ctypes.CDLL(libpubhubspath)
+        self._libpubhubs.pubhubs_key_load.restype = ctypes.c_uint8
+        self._libpubhubs.pubhubs_key_load.argtypes = [ctypes.POINTER(ctypes.c_void_p), ctypes.c_char_p]
+        self._libpubhubs.pubhubs_keys_decrypt.restype = ctypes.c_uint8
+        self._libpubhubs.pubhubs_keys_decrypt.argtypes = [ctypes.c_char_p, ctypes.c_char_p, ctypes.POINTER(ctypes.c_void_p), ctypes.c_size_t, ctypes.POINTER(ctypes.c_size_t)]
+        self._libpubhubs.pubhubs_key_free.restype = None
+        self._libpubhubs.pubhubs_key_free.argtypes = [ctypes.c_void_p]
+        PseudonymHelper.use_libpubhubs(self._libpubhubs)
+
+        # Load HUB_SECRET once into key handles, so that it need not be kept around in Python.
+        #
+        # HUB_SECRET may contain several comma-separated keys, like the new and the previous key
+        # the PubHubs Central admin page shows during the grace period after a key rotation.
+        key_handles = []
+        for hub_secret in os.environ['HUB_SECRET'].split(","):
+            key = ctypes.c_void_p()
+            private_key_buf = ctypes.create_string_buffer(bytes.fromhex(hub_secret.strip()), 32)
+            result = self._libpubhubs.pubhubs_key_load(ctypes.byref(key), private_key_buf)
+            ctypes.memset(private_key_buf, 0, 32)
+            if result != 1: # Ok
+                for handle in key_handles:
+                    self._libpubhubs.pubhubs_key_free(handle)
+                raise ConfigError("invalid HUB_SECRET")
+            key_handles.append(key)
+        self._keys = (ctypes.c_void_p * len(key_handles))(*key_handles)
+
+    def __del__(self):
+        if getattr(self, "_keys", None):
+            for key in self._keys:
+                self._libpubhubs.pubhubs_key_free(key)
+            self._keys = None
+
+    # Decrypts the hex-encoded encrypted local pseudonym, returning the hex-encoded local pseudonym.
+    def decrypt(self, encrypted_local_pseudonym):
         result_buf = ctypes.create_string_buffer(32)
         ciphertext_buf = ctypes.create_string_buffer(96)
-        private_key_buf = ctypes.create_string_buffer(32)
 
         ciphertext_buf.raw = bytes.fromhex(encrypted_local_pseudonym)
-        private_key_buf.raw = bytes.fromhex(self._secret)
 
-        match self._libpubhubs.decrypt(ctypes.byref(result_buf), ctypes.byref(ciphertext_buf), ctypes.byref(private_key_buf)):
+        used = ctypes.c_size_t()
+
+        match self._libpubhubs.pubhubs_keys_decrypt(result_buf, ciphertext_buf, self._keys, len(self._keys), ctypes.byref(used)):
             case 1: # Ok
                 pass
             case 2: # WrongPublicKey
@@ -107,6 +125,31 @@ class OidcMappingProvider:
 
         assert decrypted_local_pseudonym != "0000000000000000000000000000000000000000000000000000000000000000"
 
+        return decrypted_local_pseudonym
+
+
+# Oidc mapping provider for PubHubs that decrypts the encrypted local pseudonym, and
+# turns it into a short pseudonym.
+# 
+# References:
+#   - https://matrix-org.github.io/synapse/latest/sso_mapping_providers.html
+#   - https://github.com/matrix-org/synapse/blob/6ac35667af31f6d3aa81a8b5d00425e6e7e657e7/synapse/handlers/oidc.py#L1532
+class OidcMappingProvider:
+    def __init__(self, config):
+        self._config = config
+        self._keys = HubKeys(config["libpubhubspath"])
+
+    @staticmethod
+    def parse_config(config):
+        if "libpubhubspath" not in config:
+            logger.error(f"the invalid config was: {config}")
+            raise ConfigError("Please configure 'libpubhubspath'")
+        return config
+
+    def get_remote_user_id(self, userinfo):
+        logger.info(f"get_remote_user_id {userinfo}")
+        decrypted_local_pseudonym = self._keys.decrypt(userinfo["sub"])
+
         # HACK: For efficiency's sake, we add the decrypted local pseudonym to userinfo,
         # so that it can be used in map_user_attributes below.  This seems to work for now,
         # but might break in the future as it's not clear that mutating userinfo like this is
@@ -127,21 +170,159 @@ class OidcMappingProvider:
         return {}
 
 
+#
+# Deactivates (and erases) the account of a user that deleted their PubHubs account.
+#
+# PubHubs Central then posts a logout token to ACCOUNT_DELETED_PATH: a JWT signed with the same key
+# as its id tokens, with as 'sub' the user's encrypted local pseudonym, and ACCOUNT_DELETED_EVENT as
+# only event, see pubhubs/src/old/account.rs.
+#
+# Configure with the same 'libpubhubspath' as the OidcMappingProvider, and the 'idp_id' of PubHubs
+# in oidc_providers.
+#
+#
+# NOTE: the module API offers no way to check tokens of an OpenID Connect provider, or to
+# deactivate an account, so this module uses synapse internals:  ModuleApi's _hs and _store,
+# OidcHandler._providers, OidcProvider._config and load_jwks, the store's
+# get_user_by_external_id and remove_user_external_id, and
+# DeactivateAccountHandler.deactivate_account.
+#
+# These were checked against the synapse versions in SYNAPSE_VERSIONS: 1.87 (requirements.txt)
+# and 1.94 (Dockerfile).  TestSynapseInternals in test/pseudonyms_test.py fails for any other
+# version, so recheck them when upgrading synapse.
+#
+class AccountDeletion:
+
+    SYNAPSE_VERSIONS = ("1.87", "1.94")
+
+    ACCOUNT_DELETED_PATH = "/_synapse/client/pubhubs/account_deleted"
+    ACCOUNT_DELETED_EVENT = "https://pubhubs.net/event/account-deleted"
+
+    # same leeway as synapse uses for id tokens
+    LEEWAY = 120
+
+    def __init__(self, config: dict, api: ModuleApi):
+        self.api = api
+        self._keys = HubKeys(config["libpubhubspath"])
+
+        # synapse prefixes the idp_id of OpenID Connect providers, see synapse/config/oidc.py
+        idp_id = config["idp_id"]
+        self._idp_id = idp_id if idp_id == "oidc" else "oidc-" + idp_id
+
+        self.api.register_web_resource(
+            AccountDeletion.ACCOUNT_DELETED_PATH, AccountDeletedResource(self))
+
+    @staticmethod
+    def parse_config(config):
+        for key in ("libpubhubspath", "idp_id"):
+            if key not in config:
+                logger.error(f"the invalid config was: {config}")
+                raise ConfigError(f"Please configure '{key}'")
+        return config
+
+    #
+    # Checks the logout token like synapse checks id tokens, using PubHubs Central's keys
+    # from the OpenID Connect provider, and returns its claims.  Raises JoseError if invalid.
+    #
+    async def verify_logout_token(self, logout_token: str):
+        provider = self.api._hs.get_oidc_handler()._providers[self._idp_id]
+
+        claims = JsonWebToken(["EdDSA"]).decode(
+            logout_token,
+            key = await provider.load_jwks(),
+            claims_options = {
+                "iss": { "essential": True, "value": provider._config.issuer },
+                "aud": { "essential": True, "value": provider._config.client_id },
+                "exp": { "essential": True },
+                "sub": { "essential": True },
+                "events": { "essential": True },
+            })
+        claims.validate(leeway=AccountDeletion.LEEWAY)
+
+        if AccountDeletion.ACCOUNT_DELETED_EVENT not in claims["events"]:
+            raise JoseError("not an account deletion token")
+
+        return claims
+
+    #
+    # Deactivates the account of the given encrypted local pseudonym, and removes its mapping
+    # to the local pseudonym.  Returns the deactivated user id, if any.
+    #
+    async def delete_account(self, encrypted_local_pseudonym: str):
+        local_pseudonym = self._keys.decrypt(encrypted_local_pseudonym)
+
+        user_id = await self.api._store.get_user_by_external_id(self._idp_id, local_pseudonym)
+        if user_id is None:
+            logger.info("a user without an account on this hub deleted their PubHubs account")
+            return None
+
+        await self.api._hs.get_deactivate_account_handler().deactivate_account(
+            user_id, erase_data=True, requester=create_requester(user_id), by_admin=True)
+        await self.api._store.remove_user_external_id(self._idp_id, local_pseudonym, user_id)
+
+        logger.info(f"deactivated {user_id}, who deleted their PubHubs account")
+        return user_id
+
+
+class AccountDeletedResource(DirectServeJsonResource):
+
+    def __init__(self, module: AccountDeletion):
+        super().__init__()
+        self._module = module
+
+    async def _async_render_POST(self, request: SynapseRequest):
+        logout_token = request.args.get(b"logout_token", [None])[0]
+        if not logout_token:
+            respond_with_json(request, 400, { "error": "invalid_request" })
+            return
+
+        try:
+            claims = await self._module.verify_logout_token(logout_token.decode())
+        except (JoseError, ValueError) as e:
+            logger.warning(f"received an invalid account deletion token: {e}")
+            respond_with_json(request, 400, { "error": "invalid_request" })
+            return
+
+        await self._module.delete_account(claims["sub"])
+        respond_with_json(request, 200, {})
+
+
 class PseudonymHelper:
 
     local_pseudonym_pattern = re.compile("[a-f0-9]{64}")
     checkdigit_alphabet = "0123456789abcdefg"
 
+    # libpubhubs.so, which implements the check digits and short pseudonyms,
+    # see pubhubs/src/common/short_pseudonym.rs.  Set by use_libpubhubs.
+    libpubhubs = None
+
+    # PUBHUBS_SHORT_PSEUDONYM_MAX_LEN from pubhubs.h
+    short_pseudonym_max_len = 33
+
+    @classmethod
+    def use_libpubhubs(cls, libpubhubs):
+        libpubhubs.pubhubs_checkdigit.restype = ctypes.c_uint8
+        libpubhubs.pubhubs_checkdigit.argtypes = [ctypes.c_char_p, ctypes.c_char_p, ctypes.c_size_t]
+        libpubhubs.pubhubs_short_pseudonym.restype = ctypes.c_uint8
+        libpubhubs.pubhubs_short_pseudonym.argtypes = [ctypes.c_char_p, ctypes.c_char_p, ctypes.c_size_t]
+        cls.libpubhubs = libpubhubs
+
+    @classmethod
+    def _libpubhubs(cls):
+        if cls.libpubhubs is None:
+            raise RuntimeError("libpubhubs.so was not loaded; see PseudonymHelper.use_libpubhubs")
+        return cls.libpubhubs
+
     # computes an ISBN10-style checksum for the string s, using mod 17 instead
     # of mod 11, so that s can be a 15 character hex-string (instead of 9
     # decimal digits.)
-    def checkdigit(s):
-        N = len(s)
-        assert(N <= 15)
-        cs = 0
-        for i in range(1, N+1):
-            cs = (cs - (i+1) * int(s[N-i], 17)) % 17
-        return PseudonymHelper.checkdigit_alphabet[cs]
+    @classmethod
+    def checkdigit(cls, s):
+        digits = s.encode()
+        result_buf = ctypes.create_string_buffer(1)
+        if cls._libpubhubs().pubhubs_checkdigit(result_buf, digits, len(digits)) != 1: # Ok
+            raise ValueError(f"{ s } is not a string of at most 15 base 17 digits")
+        return result_buf.raw.decode()
 
     def is_local_pseudonym(s):
         return PseudonymHelper.local_pseudonym_pattern.fullmatch(s) != None
@@ -154,16 +335,17 @@ class PseudonymHelper:
 
     # returns the nth short pseudonym associated to the local_pseudonym,
     # the nth consisting of 2*(3+n) letters and a '-'. 
-    def short_pseudonym_nr(local_pseudonym, n):
-        if not PseudonymHelper.is_local_pseudonym(local_pseudonym):
+    @classmethod
+    def short_pseudonym_nr(cls, local_pseudonym, n):
+        if not cls.is_local_pseudonym(local_pseudonym):
             raise ValueError(
                 f"{ local_pseudonym } is not a valid local pseudonym")
         if n > 13:
             raise ValueError("n can't exceed 14")
-        prefix_len = n + 2
-        prefix, suffix = local_pseudonym[:prefix_len], local_pseudonym[-prefix_len:]
-
-        return f"{ prefix }{ PseudonymHelper.checkdigit(prefix) }-{ PseudonymHelper.checkdigit(suffix) }{ suffix }"
+        result_buf = ctypes.create_string_buffer(cls.short_pseudonym_max_len + 1)
+        if cls._libpubhubs().pubhubs_short_pseudonym(result_buf, bytes.fromhex(local_pseudonym), n) != 1: # Ok
+            raise RuntimeError("failed to compute short pseudonym")
+        return result_buf.value.decode()
 
     #
     # Cleanup localpart/pseudonyms from displayname, and add it again at end of displayname. Or if empty, just the localpart
@@ -1,17 +1,27 @@
 import os
+import ctypes
 import unittest
 import subprocess
 from unittest import IsolatedAsyncioTestCase
-from unittest.mock import patch
+from unittest.mock import patch, AsyncMock, MagicMock
 from synapse.module_api import ModuleApi
 
 import sys
 sys.path.append("modules")
-from pseudonyms import PseudonymHelper, Pseudonym
+from pseudonyms import PseudonymHelper, Pseudonym, AccountDeletion
+
+
+# libpubhubs.so, built using `cargo build --no-default-features --lib --features=abi` in pubhubs/
+LIBPUBHUBS_PATH = os.environ.get("LIBPUBHUBS_PATH",
+        os.path.join(os.path.dirname(__file__), "../../pubhubs/target/debug/libpubhubs.so"))
 
 
 class TestPseudonymHelper(unittest.TestCase):
 
+    @classmethod
+    def setUpClass(cls):
+        PseudonymHelper.use_libpubhubs(ctypes.CDLL(LIBPUBHUBS_PATH))
+
     def test_checkdigit(self):
         self.assertEqual(PseudonymHelper.checkdigit("000"), "0")
         self.assertEqual(PseudonymHelper.checkdigit("1"), "f")
@@ -29,6 +39,11 @@ class TestPseudonymHelper(unittest.TestCase):
         # all ones will give the sum -2 - 3 - 4 - ... - 16
         self.assertEqual(PseudonymHelper.checkdigit_alphabet[(1-17*16//2) % 17], PseudonymHelper.checkdigit(15*'1'))
 
+        with self.assertRaises(ValueError):
+            PseudonymHelper.checkdigit(16*'1')
+        with self.assertRaises(ValueError):
+            PseudonymHelper.checkdigit("h")
+
     def test_short_pseudonums(self):
         self.assertEqual(
             list(PseudonymHelper.short_pseudonyms("0123456789"*6+"abcd")), [
@@ -61,6 +76,65 @@ class TestPseudonymHelper(unittest.TestCase):
 
 
 
+# Checks the synapse internals used by AccountDeletion are still there, see the note there.
+class TestSynapseInternals(unittest.TestCase):
+
+    def test_synapse_version(self):
+        import synapse
+        major_minor = ".".join(synapse.__version__.split(".")[:2])
+        self.assertIn(major_minor, AccountDeletion.SYNAPSE_VERSIONS,
+            "recheck the synapse internals used by AccountDeletion, and add this version to SYNAPSE_VERSIONS")
+
+    def test_internals(self):
+        import inspect
+        from synapse.handlers.oidc import OidcHandler, OidcProvider
+        from synapse.handlers.deactivate_account import DeactivateAccountHandler
+        from synapse.storage.databases.main import DataStore
+
+        self.assertIn("self._hs =", inspect.getsource(ModuleApi.__init__))
+        self.assertIn("self._store =", inspect.getsource(ModuleApi.__init__))
+        self.assertIn("self._providers", inspect.getsource(OidcHandler.__init__))
+        self.assertIn("self._config =", inspect.getsource(OidcProvider.__init__))
+        self.assertTrue(inspect.iscoroutinefunction(OidcProvider.load_jwks))
+
+        self.assertEqual(list(inspect.signature(DataStore.get_user_by_external_id).parameters),
+            ["self", "auth_provider", "external_id"])
+        self.assertEqual(list(inspect.signature(DataStore.remove_user_external_id).parameters),
+            ["self", "auth_provider", "external_id", "user_id"])
+        self.assertLessEqual({"user_id", "erase_data", "requester", "by_admin"},
+            set(inspect.signature(DeactivateAccountHandler.deactivate_account).parameters))
+
+
+class TestAccountDeletion(IsolatedAsyncioTestCase):
+
+    def setUp(self):
+        self.api = MagicMock()
+        self.api._store.get_user_by_external_id = AsyncMock(return_value="@01f-6cd:hub")
+        self.api._store.remove_user_external_id = AsyncMock()
+        self.deactivate_account = self.api._hs.get_deactivate_account_handler().deactivate_account = AsyncMock()
+
+        with patch("pseudonyms.HubKeys") as hub_keys:
+            hub_keys.return_value.decrypt.return_value = "local pseudonym"
+            self.module = AccountDeletion(AccountDeletion.parse_config(
+                { "libpubhubspath": "libpubhubs.so", "idp_id": "pubhubs" }), self.api)
+
+    def test_register_web_resource(self):
+        self.api.register_web_resource.assert_called_once()
+        self.assertEqual(self.api.register_web_resource.call_args.args[0], "/_synapse/client/pubhubs/account_deleted")
+
+    async def test_delete_account(self):
+        self.assertEqual(await self.module.delete_account("encrypted local pseudonym"), "@01f-6cd:hub")
+        self.api._store.get_user_by_external_id.assert_awaited_once_with("oidc-pubhubs", "local pseudonym")
+        self.deactivate_account.assert_awaited_once()
+        self.assertEqual(self.deactivate_account.call_args.args[0], "@01f-6cd:hub")
+        self.assertTrue(self.deactivate_account.call_args.kwargs["erase_data"])
+        self.api._store.remove_user_external_id.assert_awaited_once_with("oidc-pubhubs", "local pseudonym", "@01f-6cd:hub")
+
+    async def test_delete_unknown_account(self):
+        self.api._store.get_user_by_external_id.return_value = None
+        self.assertIsNone(await self.module.delete_account("encrypted local pseudonym"))
+        self.deactivate_account.assert_not_awaited()
+        self.api._store.remove_user_external_id.assert_not_awaited()
 
 
 if __name__ == '__main__':